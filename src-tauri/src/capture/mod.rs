@@ -0,0 +1,912 @@
+// Screen capture backends. Every backend records into capture_pre.mp4, which
+// stop_capture then compresses into the project's capture.mp4.
+//
+// windows: Windows.Graphics.Capture through windows_capture
+// x11: XComposite + MIT-SHM, also works under Xvfb
+// portal: xdg-desktop-portal ScreenCast + PipeWire for Wayland sessions
+// synthetic: deterministic test pattern or file replay, for headless end to end runs
+//
+// webcam records alongside any of them into webcam.mp4, for the picture-in-picture overlay
+
+#[cfg(target_os = "linux")]
+pub mod portal;
+pub mod synthetic;
+pub mod webcam;
+#[cfg(target_os = "windows")]
+pub mod windows;
+#[cfg(target_os = "linux")]
+pub mod x11;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling;
+use ffmpeg_next::util::frame::video::Video;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const CAPTURE_FPS: u32 = 60;
+const THUMBNAIL_WIDTH: u32 = 320;
+
+// what the user picked to record, monitors are indexed in enumeration order
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Source {
+    Window {
+        id: usize,
+    },
+    Monitor {
+        index: usize,
+    },
+    // x and y are relative to the monitor's top left
+    Region {
+        monitor: usize,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RectInfo {
+    pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl RectInfo {
+    pub fn new(left: i32, top: i32, width: i32, height: i32) -> Self {
+        Self {
+            left,
+            right: left + width,
+            top,
+            bottom: top + height,
+            width,
+            height,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SourceInfo {
+    pub source: Source,
+    pub title: String,
+    pub app_name: Option<String>,
+    pub rect: RectInfo, // desktop coordinates
+    pub scale_factor: f64,
+    pub thumbnail: Option<String>, // jpeg data url, only filled when asked for
+}
+
+// resolves a region against the monitor it sits on, clamped to the monitor's bounds
+pub fn region_info(monitor: SourceInfo, source: &Source) -> Result<SourceInfo, String> {
+    let Source::Region {
+        x,
+        y,
+        width,
+        height,
+        ..
+    } = *source
+    else {
+        return Err("Not a region source".to_string());
+    };
+
+    let left = x.clamp(0, monitor.rect.width);
+    let top = y.clamp(0, monitor.rect.height);
+    let width = width.min(monitor.rect.width - left);
+    let height = height.min(monitor.rect.height - top);
+
+    if width <= 0 || height <= 0 {
+        return Err("Region is outside of its monitor".to_string());
+    }
+
+    Ok(SourceInfo {
+        source: source.clone(),
+        title: format!("{}x{} region of {}", width, height, monitor.title),
+        app_name: None,
+        rect: RectInfo::new(
+            monitor.rect.left + left,
+            monitor.rect.top + top,
+            width,
+            height,
+        ),
+        scale_factor: monitor.scale_factor,
+        thumbnail: None,
+    })
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorMode {
+    Hidden,
+    #[default]
+    Embedded,
+}
+
+pub struct CapturedFrame<'a> {
+    pub data: &'a [u8],
+    pub width: u32,
+    pub height: u32,
+    pub stride: usize,
+    pub pix_fmt: &'static str, // ffmpeg name of the 4 byte pixel layout
+    pub elapsed: Duration,
+}
+
+// a single grabbed frame, used for source thumbnails
+pub struct OwnedFrame {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub stride: usize,
+    pub pix_fmt: &'static str,
+}
+
+impl OwnedFrame {
+    pub fn crop(self, x: u32, y: u32, width: u32, height: u32) -> OwnedFrame {
+        let x = x.min(self.width);
+        let y = y.min(self.height);
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+        let row_bytes = (width * 4) as usize;
+
+        let mut data = Vec::with_capacity(row_bytes * height as usize);
+        for row in y..y + height {
+            let start = row as usize * self.stride + (x * 4) as usize;
+            data.extend_from_slice(&self.data[start..start + row_bytes]);
+        }
+
+        OwnedFrame {
+            data,
+            width,
+            height,
+            stride: row_bytes,
+            pix_fmt: self.pix_fmt,
+        }
+    }
+}
+
+pub type FrameCallback = Box<dyn FnMut(&CapturedFrame) + Send>;
+pub type SourceBoundsCallback = Box<dyn FnOnce(&RectInfo) + Send>;
+
+pub struct CaptureOptions {
+    pub source: Source,
+    pub width: u32,
+    pub height: u32,
+    pub output_path: String,
+    // the recording is downscaled to fit, keeping its aspect ratio. None keeps native size
+    pub max_size: Option<(u32, u32)>,
+    pub cursor_mode: CursorMode,
+    // shared with MouseTrackingState, capture runs until it goes false
+    pub is_recording: Arc<Mutex<bool>>,
+    // also shared with the mouse tracker, frames are timestamped with it
+    pub clock: RecordingClock,
+    pub on_frame: Option<FrameCallback>,
+    // for backends that only learn where the source is once capture starts (portal)
+    pub on_source_bounds: Option<SourceBoundsCallback>,
+}
+
+pub trait CaptureBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // capturable windows (visible, uncloaked, non empty) followed by monitors
+    fn sources(&self) -> Result<Vec<SourceInfo>, String>;
+
+    fn source_info(&self, source: &Source) -> Result<SourceInfo, String>;
+
+    // one still of the source as it looks right now
+    fn grab_frame(&self, _source: &Source) -> Result<OwnedFrame, String> {
+        Err(format!("{} capture can't grab single frames", self.name()))
+    }
+
+    // returns once capture is running, frames are recorded on a background thread
+    fn start(&self, options: CaptureOptions) -> Result<(), String>;
+
+    fn stop(&self) -> Result<(), String>;
+
+    // blocks until the stopped capture has finalized its output file
+    fn wait_finished(&self, timeout: Duration) -> Result<(), String>;
+}
+
+// wall clock time with the paused spans cut out. The mouse tracker and the capture share
+// one, so mousePositions.json and the recorded video stay on the same timeline
+#[derive(Clone)]
+pub struct RecordingClock {
+    state: Arc<Mutex<ClockState>>,
+}
+
+struct ClockState {
+    started: Instant,
+    paused_at: Option<Instant>,
+    paused_total: Duration,
+}
+
+impl Default for RecordingClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecordingClock {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ClockState {
+                started: Instant::now(),
+                paused_at: None,
+                paused_total: Duration::ZERO,
+            })),
+        }
+    }
+
+    // starts over from zero, unpaused. Clones see the restart too
+    pub fn restart(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        state.started = Instant::now();
+        state.paused_at = None;
+        state.paused_total = Duration::ZERO;
+    }
+
+    // stands still while paused
+    pub fn elapsed(&self) -> Duration {
+        let state = self.state.lock().unwrap();
+        let now = state.paused_at.unwrap_or_else(Instant::now);
+
+        now.duration_since(state.started)
+            .saturating_sub(state.paused_total)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().paused_at.is_some()
+    }
+
+    pub fn pause(&self) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.paused_at.is_some() {
+            return Err("Recording is already paused".to_string());
+        }
+
+        state.paused_at = Some(Instant::now());
+        Ok(())
+    }
+
+    pub fn resume(&self) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+
+        let paused_at = state.paused_at.take().ok_or("Recording is not paused")?;
+        state.paused_total += paused_at.elapsed();
+
+        Ok(())
+    }
+}
+
+// picks the platform backend, SUNSHOT_CAPTURE_BACKEND overrides it (e.g. "synthetic" on CI)
+pub fn default_backend() -> Box<dyn CaptureBackend> {
+    let requested = std::env::var("SUNSHOT_CAPTURE_BACKEND").unwrap_or_default();
+
+    let backend: Box<dyn CaptureBackend> = match requested.as_str() {
+        "synthetic" => Box::new(synthetic::SyntheticBackend::new()),
+        #[cfg(target_os = "linux")]
+        "x11" => Box::new(x11::X11Backend::new()),
+        #[cfg(target_os = "linux")]
+        "portal" => Box::new(portal::PortalBackend::new()),
+        _ => platform_backend(),
+    };
+
+    println!("Using {} capture backend", backend.name());
+
+    backend
+}
+
+#[cfg(target_os = "windows")]
+fn platform_backend() -> Box<dyn CaptureBackend> {
+    Box::new(windows::WindowsBackend::new())
+}
+
+#[cfg(target_os = "linux")]
+fn platform_backend() -> Box<dyn CaptureBackend> {
+    if is_wayland_session() {
+        Box::new(portal::PortalBackend::new())
+    } else {
+        Box::new(x11::X11Backend::new())
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn platform_backend() -> Box<dyn CaptureBackend> {
+    Box::new(synthetic::SyntheticBackend::new())
+}
+
+// largest even size with the same aspect ratio that fits in max_size, never upscales
+pub fn fit_within(width: u32, height: u32, max_size: Option<(u32, u32)>) -> (u32, u32) {
+    let (width, height) = (width.max(2), height.max(2));
+
+    let scale = match max_size {
+        Some((max_width, max_height)) => (max_width as f64 / width as f64)
+            .min(max_height as f64 / height as f64)
+            .min(1.0),
+        None => 1.0,
+    };
+
+    let even = |value: f64| ((value as u32) & !1).max(2);
+
+    (even(width as f64 * scale), even(height as f64 * scale))
+}
+
+pub fn list_sources(
+    backend: &dyn CaptureBackend,
+    include_thumbnails: bool,
+) -> Result<Vec<SourceInfo>, String> {
+    let mut sources = backend.sources()?;
+
+    if include_thumbnails {
+        for source in sources.iter_mut() {
+            // a window that can't be grabbed is still listed, just without a preview
+            source.thumbnail = backend
+                .grab_frame(&source.source)
+                .and_then(|frame| encode_thumbnail(&frame))
+                .map_err(|e| eprintln!("No thumbnail for {}: {}", source.title, e))
+                .ok();
+        }
+    }
+
+    Ok(sources)
+}
+
+// scales a frame down to THUMBNAIL_WIDTH and returns it as a jpeg data url
+pub fn encode_thumbnail(frame: &OwnedFrame) -> Result<String, String> {
+    let mut child = Command::new("ffmpeg")
+        .arg("-nostdin")
+        .arg("-f")
+        .arg("rawvideo")
+        .arg("-pix_fmt")
+        .arg(frame.pix_fmt)
+        .arg("-s")
+        .arg(format!("{}x{}", frame.stride / 4, frame.height))
+        .arg("-i")
+        .arg("-")
+        .arg("-vf")
+        .arg(format!(
+            "crop={}:{}:0:0,scale={}:-2",
+            frame.width, frame.height, THUMBNAIL_WIDTH
+        ))
+        .arg("-frames:v")
+        .arg("1")
+        .arg("-f")
+        .arg("image2pipe")
+        .arg("-c:v")
+        .arg("mjpeg")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+
+    let mut stdin = child.stdin.take().ok_or("ffmpeg has no stdin")?;
+    let data = frame.data[..frame.stride * frame.height as usize].to_vec();
+    let writer = thread::spawn(move || stdin.write_all(&data));
+
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    let _ = writer.join();
+
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(format!("ffmpeg exited with {}", output.status));
+    }
+
+    Ok(format!(
+        "data:image/jpeg;base64,{}",
+        BASE64.encode(output.stdout)
+    ))
+}
+
+// the recording flag of the capture in flight, shared by the backends for stop(), and
+// the result the capture thread sends once its output file is finalized
+#[derive(Default)]
+pub struct CaptureWorker {
+    is_recording: Mutex<Option<Arc<Mutex<bool>>>>,
+    finished: Mutex<Option<Receiver<Result<(), String>>>>,
+}
+
+impl CaptureWorker {
+    // capture must only return once the output file is complete
+    pub fn spawn<F>(&self, is_recording: Arc<Mutex<bool>>, capture: F)
+    where
+        F: FnOnce() -> Result<(), String> + Send + 'static,
+    {
+        let (finished_tx, finished_rx) = mpsc::channel();
+
+        *self.is_recording.lock().unwrap() = Some(is_recording.clone());
+        *self.finished.lock().unwrap() = Some(finished_rx);
+
+        thread::spawn(move || {
+            let result = capture();
+
+            if let Err(e) = &result {
+                eprintln!("Capture error: {}", e);
+                // Ensure is_recording is set to false if an error occurs
+                *is_recording.lock().unwrap() = false;
+            }
+
+            let _ = finished_tx.send(result);
+        });
+    }
+
+    pub fn wait_finished(&self, timeout: Duration) -> Result<(), String> {
+        let finished = self
+            .finished
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or("No capture to wait for")?;
+
+        match finished.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(format!(
+                "Capture did not finalize within {} seconds",
+                timeout.as_secs()
+            )),
+            Err(RecvTimeoutError::Disconnected) => {
+                Err("Capture thread exited without finalizing".to_string())
+            }
+        }
+    }
+
+    pub fn stop(&self) -> Result<(), String> {
+        match self.is_recording.lock().unwrap().take() {
+            Some(is_recording) => {
+                *is_recording.lock().unwrap() = false;
+                Ok(())
+            }
+            None => Err("No capture in progress".to_string()),
+        }
+    }
+}
+
+pub struct FramePipe {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    width: u32,
+    height: u32,
+    pix_fmt: &'static str,
+    frame: Vec<u8>, // fixed size scratch frame, windows may resize mid recording
+    resizer: Option<Resizer>,
+    frames_written: u64,
+    on_frame: Option<FrameCallback>,
+}
+
+// scales frames of one size to another with swscale, kept while a resized window stays at
+// the same size
+struct Resizer {
+    input: Video,
+    output: Video,
+    context: scaling::Context,
+}
+
+impl Resizer {
+    fn new(pix_fmt: &str, from: (u32, u32), to: (u32, u32)) -> Result<Self, String> {
+        let format = match pix_fmt {
+            "bgr0" => Pixel::BGRZ,
+            "bgra" => Pixel::BGRA,
+            "rgb0" => Pixel::RGBZ,
+            "rgba" => Pixel::RGBA,
+            _ => return Err(format!("Can't scale {} frames", pix_fmt)),
+        };
+
+        ffmpeg_next::init().map_err(|e| e.to_string())?;
+
+        let context = scaling::Context::get(
+            format,
+            from.0,
+            from.1,
+            format,
+            to.0,
+            to.1,
+            scaling::Flags::BILINEAR,
+        )
+        .map_err(|e| format!("Failed to create scaler: {}", e))?;
+
+        Ok(Self {
+            input: Video::new(format, from.0, from.1),
+            output: Video::new(format, to.0, to.1),
+            context,
+        })
+    }
+
+    fn input_size(&self) -> (u32, u32) {
+        (self.input.width(), self.input.height())
+    }
+
+    // scales data into target, a tightly packed frame of the output size
+    fn scale(&mut self, data: &[u8], stride: usize, target: &mut [u8]) -> Result<(), String> {
+        copy_rows(data, stride, self.input.stride(0), self.input.data_mut(0));
+
+        self.context
+            .run(&self.input, &mut self.output)
+            .map_err(|e| format!("Failed to scale frame: {}", e))?;
+
+        let row_bytes = (self.output.width() * 4) as usize;
+        copy_rows(
+            self.output.data(0),
+            self.output.stride(0),
+            row_bytes,
+            target,
+        );
+
+        Ok(())
+    }
+}
+
+// copies as many whole rows as both buffers hold, from one row stride to another
+fn copy_rows(source: &[u8], source_stride: usize, target_stride: usize, target: &mut [u8]) {
+    let row_bytes = source_stride.min(target_stride);
+
+    for (source_row, target_row) in source
+        .chunks(source_stride)
+        .zip(target.chunks_mut(target_stride))
+    {
+        let len = row_bytes.min(source_row.len()).min(target_row.len());
+        target_row[..len].copy_from_slice(&source_row[..len]);
+    }
+}
+
+impl FramePipe {
+    // pix_fmt is the ffmpeg name of the incoming 4 byte pixel layout (bgr0, bgra, rgb0, rgba)
+    pub fn new(
+        output_path: &str,
+        width: u32,
+        height: u32,
+        pix_fmt: &'static str,
+        max_size: Option<(u32, u32)>,
+        on_frame: Option<FrameCallback>,
+    ) -> Result<Self, String> {
+        let (output_width, output_height) = fit_within(width, height, max_size);
+
+        // yuv420p needs even dimensions
+        let mut filter = "crop=trunc(iw/2)*2:trunc(ih/2)*2".to_string();
+        if (output_width, output_height) != (width & !1, height & !1) {
+            filter.push_str(&format!(
+                ",scale={}:{}:flags=lanczos",
+                output_width, output_height
+            ));
+        }
+
+        let mut child = Command::new("ffmpeg")
+            .arg("-y")
+            .arg("-f")
+            .arg("rawvideo")
+            .arg("-pix_fmt")
+            .arg(pix_fmt)
+            .arg("-s")
+            .arg(format!("{}x{}", width, height))
+            .arg("-r")
+            .arg(CAPTURE_FPS.to_string())
+            .arg("-i")
+            .arg("-")
+            .arg("-vf")
+            .arg(filter)
+            .arg("-c:v")
+            .arg("libx264")
+            .arg("-preset")
+            .arg("ultrafast")
+            .arg("-pix_fmt")
+            .arg("yuv420p")
+            .arg(output_path)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+
+        let stdin = child.stdin.take();
+
+        Ok(Self {
+            child,
+            stdin,
+            width,
+            height,
+            pix_fmt,
+            frame: vec![0; (width * height * 4) as usize],
+            resizer: None,
+            frames_written: 0,
+            on_frame,
+        })
+    }
+
+    // copies a frame into the fixed output size. A window resized mid recording is scaled
+    // back to the size it started at, mouse_to_frame follows the bounds it had at the time
+    fn copy_frame(
+        &mut self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        stride: usize,
+    ) -> Result<(), String> {
+        if (width, height) == (self.width, self.height) {
+            copy_rows(data, stride, (self.width * 4) as usize, &mut self.frame);
+            return Ok(());
+        }
+
+        // minimized, keep showing the last frame
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        let mut resizer = match self.resizer.take() {
+            Some(resizer) if resizer.input_size() == (width, height) => resizer,
+            _ => Resizer::new(self.pix_fmt, (width, height), (self.width, self.height))?,
+        };
+
+        resizer.scale(data, stride, &mut self.frame)?;
+        self.resizer = Some(resizer);
+
+        Ok(())
+    }
+
+    // writes the frame as many times as needed to keep the file in sync with wall time,
+    // so slow grabs don't speed the recording up against mousePositions.json
+    pub fn write_paced(
+        &mut self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        stride: usize,
+        elapsed: Duration,
+    ) -> Result<(), String> {
+        if let Some(on_frame) = self.on_frame.as_mut() {
+            on_frame(&CapturedFrame {
+                data,
+                width,
+                height,
+                stride,
+                pix_fmt: self.pix_fmt,
+                elapsed,
+            });
+        }
+
+        self.copy_frame(data, width, height, stride)?;
+
+        let target_frames = (elapsed.as_secs_f64() * CAPTURE_FPS as f64) as u64 + 1;
+
+        let stdin = self.stdin.as_mut().ok_or("Frame pipe already closed")?;
+
+        while self.frames_written < target_frames {
+            stdin
+                .write_all(&self.frame)
+                .map_err(|e| format!("Failed to write frame to ffmpeg: {}", e))?;
+            self.frames_written += 1;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        // closing stdin lets ffmpeg flush and write the trailer
+        drop(self.stdin.take());
+
+        let status = self.child.wait().map_err(|e| e.to_string())?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("ffmpeg exited with {}", status))
+        }
+    }
+}
+
+// decodes input with ffmpeg, scaled by filter to the pipe's frame size, and writes it to pipe
+// on the recording clock until is_recording goes false. input_args go before -i, eg. -re
+// to decode a file in real time or -f v4l2 for a camera
+pub fn decode_into_pipe(
+    input_args: &[&str],
+    input: &str,
+    filter: &str,
+    mut pipe: FramePipe,
+    is_recording: Arc<Mutex<bool>>,
+    clock: RecordingClock,
+) -> Result<(), String> {
+    let (width, height) = (pipe.width, pipe.height);
+
+    let mut decoder = Command::new("ffmpeg")
+        .arg("-nostdin")
+        .args(input_args)
+        .arg("-i")
+        .arg(input)
+        .arg("-vf")
+        .arg(filter)
+        .arg("-f")
+        .arg("rawvideo")
+        .arg("-pix_fmt")
+        .arg("bgr0")
+        .arg("-")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+
+    let mut stdout = decoder.stdout.take().ok_or("ffmpeg has no stdout")?;
+    let mut frame = vec![0; (width * height * 4) as usize];
+
+    let result = (|| -> Result<(), String> {
+        while *is_recording.lock().unwrap() {
+            stdout
+                .read_exact(&mut frame)
+                .map_err(|e| format!("Decoder of {} stopped: {}", input, e))?;
+
+            // keep draining the decoder while paused so it doesn't block on a full pipe
+            if clock.is_paused() {
+                continue;
+            }
+
+            pipe.write_paced(&frame, width, height, (width * 4) as usize, clock.elapsed())?;
+        }
+
+        Ok(())
+    })();
+
+    let _ = decoder.kill();
+    let _ = decoder.wait();
+
+    result.and(pipe.finish())
+}
+
+// desktop coordinates in physical pixels for the calling thread, mouse tracking and
+// source queries both use this so they agree across monitors with different DPI
+pub fn use_physical_coordinates() {
+    #[cfg(target_os = "windows")]
+    windows::use_physical_coordinates();
+}
+
+#[cfg(target_os = "linux")]
+pub fn is_wayland_session() -> bool {
+    std::env::var("XDG_SESSION_TYPE")
+        .map(|t| t == "wayland")
+        .unwrap_or(false)
+        || std::env::var("WAYLAND_DISPLAY").is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // width and height from the jpeg's start of frame marker
+    fn jpeg_size(data: &[u8]) -> Option<(u32, u32)> {
+        let at = data.windows(2).position(|marker| marker == [0xFF, 0xC0])?;
+        let read = |offset: usize| u16::from_be_bytes([data[at + offset], data[at + offset + 1]]);
+
+        Some((read(7) as u32, read(5) as u32))
+    }
+
+    #[test]
+    fn lists_windows_before_monitors_with_thumbnails() {
+        let backend = synthetic::SyntheticBackend::new();
+        let sources = list_sources(&backend, true).unwrap();
+
+        assert!(matches!(sources[0].source, Source::Window { .. }));
+        assert!(matches!(
+            sources.last().unwrap().source,
+            Source::Monitor { index: 0 }
+        ));
+
+        for info in &sources {
+            let thumbnail = info.thumbnail.as_ref().expect("missing thumbnail");
+            assert!(thumbnail.starts_with("data:image/jpeg;base64,"));
+        }
+
+        // thumbnails stay out unless asked for
+        assert!(list_sources(&backend, false)
+            .unwrap()
+            .iter()
+            .all(|info| info.thumbnail.is_none()));
+    }
+
+    #[test]
+    fn regions_are_clamped_to_their_monitor() {
+        let monitor = SourceInfo {
+            source: Source::Monitor { index: 1 },
+            title: "Display 2".to_string(),
+            app_name: None,
+            rect: RectInfo::new(1920, 0, 1280, 720),
+            scale_factor: 1.5,
+            thumbnail: None,
+        };
+        let region = |x, y, width, height| Source::Region {
+            monitor: 1,
+            x,
+            y,
+            width,
+            height,
+        };
+
+        let info = region_info(monitor.clone(), &region(1000, 600, 500, 500)).unwrap();
+        assert_eq!(
+            (
+                info.rect.left,
+                info.rect.top,
+                info.rect.width,
+                info.rect.height
+            ),
+            (2920, 600, 280, 120)
+        );
+        assert_eq!(info.scale_factor, 1.5);
+
+        assert!(region_info(monitor.clone(), &region(1280, 0, 100, 100)).is_err());
+        assert!(region_info(monitor, &Source::Monitor { index: 1 }).is_err());
+    }
+
+    #[test]
+    fn thumbnails_are_scaled_jpegs_of_the_visible_pixels() {
+        // rows padded past the visible width, like GPU and shm buffers
+        let (width, height, stride) = (640, 360, 700 * 4);
+        let frame = OwnedFrame {
+            data: vec![128; stride * height as usize],
+            width,
+            height,
+            stride,
+            pix_fmt: "bgr0",
+        };
+
+        let thumbnail = encode_thumbnail(&frame).unwrap();
+        let jpeg = BASE64
+            .decode(thumbnail.strip_prefix("data:image/jpeg;base64,").unwrap())
+            .unwrap();
+
+        assert_eq!(&jpeg[..2], [0xFF, 0xD8]);
+        assert_eq!(jpeg_size(&jpeg), Some((THUMBNAIL_WIDTH, 180)));
+    }
+
+    #[test]
+    fn crop_keeps_rows_inside_the_frame() {
+        let frame = OwnedFrame {
+            data: (0..4 * 4 * 3).map(|value| value as u8).collect(),
+            width: 4,
+            height: 3,
+            stride: 16,
+            pix_fmt: "bgr0",
+        };
+
+        let cropped = frame.crop(3, 1, 10, 10);
+
+        assert_eq!((cropped.width, cropped.height, cropped.stride), (1, 2, 4));
+        assert_eq!(cropped.data, [28, 29, 30, 31, 44, 45, 46, 47]);
+    }
+
+    #[test]
+    fn resized_frames_are_scaled_to_the_starting_size() {
+        let path = std::env::temp_dir().join(format!("sunshot-resize-{}.mp4", std::process::id()));
+        let output = path.to_string_lossy().to_string();
+        let mut pipe = FramePipe::new(&output, 320, 180, "bgr0", None, None).unwrap();
+
+        // the window grew to twice its width, left half white and right half black
+        let (width, height) = (640, 180);
+        let mut data = vec![0; (width * height * 4) as usize];
+        for row in data.chunks_mut((width * 4) as usize) {
+            row[..(width * 2) as usize].fill(255);
+        }
+
+        pipe.write_paced(&data, width, height, (width * 4) as usize, Duration::ZERO)
+            .unwrap();
+        pipe.finish().unwrap();
+
+        let gray = Command::new("ffmpeg")
+            .args(["-v", "error", "-i", &output])
+            .args(["-f", "rawvideo", "-pix_fmt", "gray", "-"])
+            .output()
+            .unwrap()
+            .stdout;
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(gray.len(), 320 * 180);
+
+        // cropping would have kept only the white half
+        let row = &gray[90 * 320..91 * 320];
+        assert!(row[40] > 200 && row[120] > 200);
+        assert!(row[200] < 50 && row[280] < 50);
+    }
+}
@@ -0,0 +1,421 @@
+// Wayland capture: the xdg-desktop-portal ScreenCast interface lets the user pick a
+// monitor or window, then hands back a PipeWire node we read raw frames from.
+
+use std::cell::RefCell;
+use std::os::fd::OwnedFd;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ashpd::desktop::screencast::{CursorMode as PortalCursorMode, Screencast, SourceType};
+use ashpd::desktop::PersistMode;
+use ashpd::WindowIdentifier;
+use pipewire as pw;
+use pw::spa;
+use pw::spa::param::video::{VideoFormat, VideoInfoRaw};
+use pw::spa::pod::Pod;
+
+use super::{
+    CaptureBackend, CaptureOptions, CaptureWorker, CursorMode, FrameCallback, FramePipe,
+    RecordingClock, RectInfo, Source, SourceInfo, CAPTURE_FPS,
+};
+
+// stands in for whatever the user picks in the screencast portal,
+// x11 ids are 29 bits so this never collides with a real window
+const PORTAL_SOURCE_ID: usize = u32::MAX as usize;
+
+#[derive(Default)]
+pub struct PortalBackend {
+    worker: CaptureWorker,
+}
+
+impl PortalBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn portal_source_info() -> SourceInfo {
+    SourceInfo {
+        source: Source::Window {
+            id: PORTAL_SOURCE_ID,
+        },
+        title: "Choose a screen or window...".to_string(),
+        app_name: None,
+        rect: RectInfo::new(0, 0, 0, 0),
+        scale_factor: 1.0,
+        thumbnail: None,
+    }
+}
+
+impl CaptureBackend for PortalBackend {
+    fn name(&self) -> &'static str {
+        "portal"
+    }
+
+    // wayland doesn't let apps see other windows, the portal picker chooses at record time
+    fn sources(&self) -> Result<Vec<SourceInfo>, String> {
+        Ok(vec![portal_source_info()])
+    }
+
+    fn source_info(&self, _source: &Source) -> Result<SourceInfo, String> {
+        Ok(portal_source_info())
+    }
+
+    fn start(&self, options: CaptureOptions) -> Result<(), String> {
+        let CaptureOptions {
+            output_path,
+            max_size,
+            cursor_mode,
+            is_recording,
+            clock,
+            on_frame,
+            on_source_bounds,
+            ..
+        } = options;
+
+        let flag = is_recording.clone();
+
+        // pipewire objects aren't Send, the whole stream lives on the worker thread
+        self.worker.spawn(is_recording, move || {
+            let stream = tauri::async_runtime::block_on(open_screencast(cursor_mode))?;
+
+            // the picked source is only known now
+            if let (Some(on_source_bounds), Some((x, y)), Some((width, height))) =
+                (on_source_bounds, stream.position, stream.size)
+            {
+                on_source_bounds(&RectInfo::new(x, y, width, height));
+            }
+
+            capture_stream(stream, &output_path, max_size, flag, clock, on_frame)
+        });
+
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), String> {
+        self.worker.stop()
+    }
+
+    fn wait_finished(&self, timeout: Duration) -> Result<(), String> {
+        self.worker.wait_finished(timeout)
+    }
+}
+
+struct PortalStream {
+    node_id: u32,
+    fd: OwnedFd,
+    position: Option<(i32, i32)>,
+    size: Option<(i32, i32)>,
+}
+
+// shows the system picker, resolves once the user has chosen a source
+async fn open_screencast(cursor_mode: CursorMode) -> Result<PortalStream, String> {
+    let portal_error = |e: ashpd::Error| format!("Screencast portal failed: {}", e);
+
+    let proxy = Screencast::new().await.map_err(portal_error)?;
+    let session = proxy.create_session().await.map_err(portal_error)?;
+
+    proxy
+        .select_sources(
+            &session,
+            match cursor_mode {
+                CursorMode::Hidden => PortalCursorMode::Hidden,
+                CursorMode::Embedded => PortalCursorMode::Embedded,
+            },
+            SourceType::Monitor | SourceType::Window,
+            false,
+            None,
+            PersistMode::DoNot,
+        )
+        .await
+        .map_err(portal_error)?;
+
+    let response = proxy
+        .start(&session, &WindowIdentifier::default())
+        .await
+        .map_err(portal_error)?
+        .response()
+        .map_err(portal_error)?;
+
+    let stream = response
+        .streams()
+        .first()
+        .ok_or("Screencast portal returned no streams")?;
+
+    let fd = proxy
+        .open_pipe_wire_remote(&session)
+        .await
+        .map_err(portal_error)?;
+
+    Ok(PortalStream {
+        node_id: stream.pipe_wire_node_id(),
+        fd,
+        position: stream.position(),
+        size: stream.size(),
+    })
+}
+
+fn ffmpeg_pix_fmt(format: VideoFormat) -> Option<&'static str> {
+    match format {
+        VideoFormat::BGRx => Some("bgr0"),
+        VideoFormat::BGRA => Some("bgra"),
+        VideoFormat::RGBx => Some("rgb0"),
+        VideoFormat::RGBA => Some("rgba"),
+        _ => None,
+    }
+}
+
+struct StreamState {
+    format: VideoInfoRaw,
+    frame: Vec<u8>,
+    stride: usize,
+    pipe: Option<FramePipe>,
+    error: Option<String>,
+}
+
+// blocks until is_recording goes false, then finalizes output_path
+fn capture_stream(
+    stream: PortalStream,
+    output_path: &str,
+    max_size: Option<(u32, u32)>,
+    is_recording: Arc<Mutex<bool>>,
+    clock: RecordingClock,
+    on_frame: Option<FrameCallback>,
+) -> Result<(), String> {
+    pw::init();
+
+    let mainloop = pw::main_loop::MainLoop::new(None).map_err(|e| e.to_string())?;
+    let context = pw::context::Context::new(&mainloop).map_err(|e| e.to_string())?;
+    let core = context
+        .connect_fd(stream.fd, None)
+        .map_err(|e| format!("Failed to connect to PipeWire: {}", e))?;
+
+    let state = Rc::new(RefCell::new(StreamState {
+        format: Default::default(),
+        frame: Vec::new(),
+        stride: 0,
+        pipe: None,
+        error: None,
+    }));
+
+    let pw_stream = pw::stream::Stream::new(
+        &core,
+        "sunshot-capture",
+        pw::properties::properties! {
+            *pw::keys::MEDIA_TYPE => "Video",
+            *pw::keys::MEDIA_CATEGORY => "Capture",
+            *pw::keys::MEDIA_ROLE => "Screen",
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    let param_state = state.clone();
+    let process_state = state.clone();
+
+    let _listener = pw_stream
+        .add_local_listener_with_user_data(())
+        .param_changed(move |_, _, id, param| {
+            let Some(param) = param else {
+                return;
+            };
+            if id != pw::spa::param::ParamType::Format.as_raw() {
+                return;
+            }
+
+            let mut state = param_state.borrow_mut();
+            if state.format.parse(param).is_ok() {
+                println!(
+                    "PipeWire format: {:?} {}x{}",
+                    state.format.format(),
+                    state.format.size().width,
+                    state.format.size().height
+                );
+            }
+        })
+        .process(move |stream, _| {
+            let Some(mut buffer) = stream.dequeue_buffer() else {
+                return;
+            };
+            let datas = buffer.datas_mut();
+            if datas.is_empty() {
+                return;
+            }
+
+            let data = &mut datas[0];
+            let offset = data.chunk().offset() as usize;
+            let size = data.chunk().size() as usize;
+            let stride = data.chunk().stride() as usize;
+
+            if let Some(bytes) = data.data() {
+                if offset + size <= bytes.len() && size > 0 {
+                    // keep only the latest frame, the pacing timer writes it out
+                    let mut state = process_state.borrow_mut();
+                    state.frame.clear();
+                    state.frame.extend_from_slice(&bytes[offset..offset + size]);
+                    state.stride = stride;
+                }
+            }
+        })
+        .register()
+        .map_err(|e| e.to_string())?;
+
+    let format_pod = pw::spa::pod::object!(
+        pw::spa::utils::SpaTypes::ObjectParamFormat,
+        pw::spa::param::ParamType::EnumFormat,
+        pw::spa::pod::property!(
+            pw::spa::param::format::FormatProperties::MediaType,
+            Id,
+            pw::spa::param::format::MediaType::Video
+        ),
+        pw::spa::pod::property!(
+            pw::spa::param::format::FormatProperties::MediaSubtype,
+            Id,
+            pw::spa::param::format::MediaSubtype::Raw
+        ),
+        pw::spa::pod::property!(
+            pw::spa::param::format::FormatProperties::VideoFormat,
+            Choice,
+            Enum,
+            Id,
+            VideoFormat::BGRx,
+            VideoFormat::BGRx,
+            VideoFormat::BGRA,
+            VideoFormat::RGBx,
+            VideoFormat::RGBA,
+        ),
+        pw::spa::pod::property!(
+            pw::spa::param::format::FormatProperties::VideoSize,
+            Choice,
+            Range,
+            Rectangle,
+            pw::spa::utils::Rectangle {
+                width: 1920,
+                height: 1080
+            },
+            pw::spa::utils::Rectangle {
+                width: 1,
+                height: 1
+            },
+            pw::spa::utils::Rectangle {
+                width: 8192,
+                height: 8192
+            }
+        ),
+        pw::spa::pod::property!(
+            pw::spa::param::format::FormatProperties::VideoFramerate,
+            Choice,
+            Range,
+            Fraction,
+            pw::spa::utils::Fraction {
+                num: CAPTURE_FPS,
+                denom: 1
+            },
+            pw::spa::utils::Fraction { num: 0, denom: 1 },
+            pw::spa::utils::Fraction {
+                num: 1000,
+                denom: 1
+            }
+        ),
+    );
+
+    let values: Vec<u8> = pw::spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &pw::spa::pod::Value::Object(format_pod),
+    )
+    .map_err(|e| format!("Failed to build stream format: {:?}", e))?
+    .0
+    .into_inner();
+
+    let mut params = [Pod::from_bytes(&values).ok_or("Invalid stream format")?];
+
+    pw_stream
+        .connect(
+            spa::utils::Direction::Input,
+            Some(stream.node_id),
+            pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+            &mut params,
+        )
+        .map_err(|e| format!("Failed to connect to screencast stream: {}", e))?;
+
+    // compositors only send frames on damage, so a timer repeats the last one at a fixed rate
+    let timer_state = state.clone();
+    let timer_mainloop = mainloop.clone();
+    let output_path = output_path.to_string();
+    let on_frame = RefCell::new(on_frame);
+
+    let timer = mainloop.loop_().add_timer(move |_| {
+        if !*is_recording.lock().unwrap() {
+            timer_mainloop.quit();
+            return;
+        }
+
+        let mut state = timer_state.borrow_mut();
+        let state = &mut *state;
+
+        if state.frame.is_empty() || state.error.is_some() || clock.is_paused() {
+            return;
+        }
+
+        let size = state.format.size();
+
+        if state.pipe.is_none() {
+            let Some(pix_fmt) = ffmpeg_pix_fmt(state.format.format()) else {
+                state.error = Some(format!(
+                    "Unsupported stream format {:?}",
+                    state.format.format()
+                ));
+                timer_mainloop.quit();
+                return;
+            };
+
+            match FramePipe::new(
+                &output_path,
+                size.width,
+                size.height,
+                pix_fmt,
+                max_size,
+                on_frame.borrow_mut().take(),
+            ) {
+                Ok(pipe) => state.pipe = Some(pipe),
+                Err(e) => {
+                    state.error = Some(e);
+                    timer_mainloop.quit();
+                    return;
+                }
+            }
+        }
+
+        if let Some(pipe) = state.pipe.as_mut() {
+            if let Err(e) = pipe.write_paced(
+                &state.frame,
+                size.width,
+                size.height,
+                state.stride,
+                clock.elapsed(),
+            ) {
+                state.error = Some(e);
+                timer_mainloop.quit();
+            }
+        }
+    });
+
+    let frame_interval = Duration::from_secs_f64(1.0 / CAPTURE_FPS as f64);
+    let _ = timer.update_timer(Some(frame_interval), Some(frame_interval));
+
+    mainloop.run();
+
+    let _ = pw_stream.disconnect();
+
+    let mut state = state.borrow_mut();
+
+    let finished = match state.pipe.take() {
+        Some(pipe) => pipe.finish(),
+        None => Err("No frames were received from the screencast".to_string()),
+    };
+
+    match state.error.take() {
+        Some(e) => Err(e),
+        None => finished,
+    }
+}
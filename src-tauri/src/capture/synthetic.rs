@@ -0,0 +1,274 @@
+// Synthetic capture: no screen involved, so the whole record -> export path can run
+// headless (CI, containers). Window 1 and monitor 0 show a deterministic test pattern,
+// window 2 replays the video file named by SUNSHOT_SYNTHETIC_FILE in a loop.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::{
+    decode_into_pipe, region_info, CaptureBackend, CaptureOptions, CaptureWorker, FramePipe,
+    OwnedFrame, RecordingClock, RectInfo, Source, SourceInfo, CAPTURE_FPS,
+};
+
+const TEST_PATTERN_ID: usize = 1;
+const FILE_REPLAY_ID: usize = 2;
+const TEST_PATTERN_WIDTH: i32 = 1920;
+const TEST_PATTERN_HEIGHT: i32 = 1080;
+
+#[derive(Default)]
+pub struct SyntheticBackend {
+    worker: CaptureWorker,
+}
+
+impl SyntheticBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn replay_file() -> Option<String> {
+    std::env::var("SUNSHOT_SYNTHETIC_FILE")
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+fn probe_size(path: &str) -> Result<(i32, i32), String> {
+    ffmpeg_next::init().map_err(|e| e.to_string())?;
+
+    let input =
+        ffmpeg_next::format::input(&path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let stream = input
+        .streams()
+        .best(ffmpeg_next::media::Type::Video)
+        .ok_or("Replay file has no video stream")?;
+    let decoder = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())
+        .and_then(|context| context.decoder().video())
+        .map_err(|e| e.to_string())?;
+
+    Ok((decoder.width() as i32, decoder.height() as i32))
+}
+
+fn source_info(source: Source, title: &str, width: i32, height: i32) -> SourceInfo {
+    SourceInfo {
+        source,
+        title: title.to_string(),
+        app_name: Some("SunShot".to_string()),
+        rect: RectInfo::new(0, 0, width, height),
+        scale_factor: 1.0,
+        thumbnail: None,
+    }
+}
+
+impl CaptureBackend for SyntheticBackend {
+    fn name(&self) -> &'static str {
+        "synthetic"
+    }
+
+    fn sources(&self) -> Result<Vec<SourceInfo>, String> {
+        let mut sources = vec![self.source_info(&Source::Window {
+            id: TEST_PATTERN_ID,
+        })?];
+
+        if replay_file().is_some() {
+            sources.push(self.source_info(&Source::Window { id: FILE_REPLAY_ID })?);
+        }
+
+        sources.push(self.source_info(&Source::Monitor { index: 0 })?);
+
+        Ok(sources)
+    }
+
+    fn source_info(&self, source: &Source) -> Result<SourceInfo, String> {
+        match *source {
+            Source::Window {
+                id: TEST_PATTERN_ID,
+            } => Ok(source_info(
+                source.clone(),
+                "Test Pattern",
+                TEST_PATTERN_WIDTH,
+                TEST_PATTERN_HEIGHT,
+            )),
+            Source::Window { id: FILE_REPLAY_ID } => {
+                let path = replay_file().ok_or("SUNSHOT_SYNTHETIC_FILE is not set")?;
+                let (width, height) = probe_size(&path)?;
+                Ok(source_info(source.clone(), "File Replay", width, height))
+            }
+            Source::Monitor { index: 0 } => Ok(source_info(
+                source.clone(),
+                "Synthetic Display",
+                TEST_PATTERN_WIDTH,
+                TEST_PATTERN_HEIGHT,
+            )),
+            Source::Region { monitor, .. } => region_info(
+                self.source_info(&Source::Monitor { index: monitor })?,
+                source,
+            ),
+            _ => Err(format!("Unknown synthetic source {:?}", source)),
+        }
+    }
+
+    fn grab_frame(&self, source: &Source) -> Result<OwnedFrame, String> {
+        let info = self.source_info(source)?;
+
+        if let Source::Window { id: FILE_REPLAY_ID } = source {
+            return Err("File replay has no preview".to_string());
+        }
+
+        let (width, height) = (TEST_PATTERN_WIDTH as u32, TEST_PATTERN_HEIGHT as u32);
+        let mut data = vec![0; (width * height * 4) as usize];
+        draw_test_pattern(&mut data, width, height, 0);
+
+        let frame = OwnedFrame {
+            data,
+            width,
+            height,
+            stride: (width * 4) as usize,
+            pix_fmt: "bgr0",
+        };
+
+        Ok(frame.crop(
+            info.rect.left as u32,
+            info.rect.top as u32,
+            info.rect.width as u32,
+            info.rect.height as u32,
+        ))
+    }
+
+    // cursor_mode has no effect, there is no cursor to draw
+    fn start(&self, options: CaptureOptions) -> Result<(), String> {
+        let info = self.source_info(&options.source)?;
+        let area = info.rect;
+        let (width, height) = (area.width as u32, area.height as u32);
+
+        let CaptureOptions {
+            source,
+            output_path,
+            max_size,
+            is_recording,
+            clock,
+            on_frame,
+            ..
+        } = options;
+
+        let flag = is_recording.clone();
+
+        self.worker.spawn(is_recording, move || {
+            let pipe = FramePipe::new(&output_path, width, height, "bgr0", max_size, on_frame)?;
+
+            if source == (Source::Window { id: FILE_REPLAY_ID }) {
+                let path = replay_file().ok_or("SUNSHOT_SYNTHETIC_FILE is not set")?;
+                replay(&path, pipe, width, height, flag, clock)
+            } else {
+                test_pattern(pipe, area, flag, clock)
+            }
+        });
+
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), String> {
+        self.worker.stop()
+    }
+
+    fn wait_finished(&self, timeout: Duration) -> Result<(), String> {
+        self.worker.wait_finished(timeout)
+    }
+}
+
+// color bars with a square that sweeps across once per second, frame n is always the same
+fn draw_test_pattern(frame: &mut [u8], width: u32, height: u32, index: u64) {
+    const BARS: [[u8; 3]; 7] = [
+        [192, 192, 192],
+        [0, 192, 192],
+        [192, 192, 0],
+        [0, 192, 0],
+        [192, 0, 192],
+        [0, 0, 192],
+        [192, 0, 0],
+    ];
+
+    let square = (height / 6).max(1);
+    let travel = width.saturating_sub(square).max(1) as u64;
+    let square_x = ((index % CAPTURE_FPS as u64) * travel / CAPTURE_FPS as u64) as u32;
+    let square_y = (height - square) / 2;
+
+    for y in 0..height {
+        for x in 0..width {
+            let in_square =
+                x >= square_x && x < square_x + square && y >= square_y && y < square_y + square;
+            let [b, g, r] = if in_square {
+                [255, 255, 255]
+            } else {
+                BARS[(x * BARS.len() as u32 / width) as usize]
+            };
+
+            let offset = ((y * width + x) * 4) as usize;
+            frame[offset..offset + 4].copy_from_slice(&[b, g, r, 0]);
+        }
+    }
+}
+
+// area is the part of the pattern being recorded, all of it unless the source is a region
+fn test_pattern(
+    mut pipe: FramePipe,
+    area: RectInfo,
+    is_recording: Arc<Mutex<bool>>,
+    clock: RecordingClock,
+) -> Result<(), String> {
+    let (width, height) = (TEST_PATTERN_WIDTH as u32, TEST_PATTERN_HEIGHT as u32);
+    let stride = (width * 4) as usize;
+    let offset = area.top as usize * stride + area.left as usize * 4;
+    let mut frame = vec![0; (width * height * 4) as usize];
+    let frame_interval = Duration::from_secs_f64(1.0 / CAPTURE_FPS as f64);
+
+    // frames are counted rather than taken from the clock, so frame n of the file is always
+    // pattern n. The clock only decides when the next one is due
+    let mut index: u64 = 0;
+
+    let result = (|| -> Result<(), String> {
+        while *is_recording.lock().unwrap() {
+            let due = frame_interval * index as u32;
+
+            if clock.is_paused() || clock.elapsed() < due {
+                thread::sleep(frame_interval / 4);
+                continue;
+            }
+
+            draw_test_pattern(&mut frame, width, height, index);
+            pipe.write_paced(
+                &frame[offset..],
+                area.width as u32,
+                area.height as u32,
+                stride,
+                // mid frame, so rounding can't make the pipe skip or repeat it
+                due + frame_interval / 2,
+            )?;
+
+            index += 1;
+        }
+
+        Ok(())
+    })();
+
+    result.and(pipe.finish())
+}
+
+// -re decodes at the file's own rate, so replay is paced like a live source
+fn replay(
+    path: &str,
+    pipe: FramePipe,
+    width: u32,
+    height: u32,
+    is_recording: Arc<Mutex<bool>>,
+    clock: RecordingClock,
+) -> Result<(), String> {
+    decode_into_pipe(
+        &["-re", "-stream_loop", "-1"],
+        path,
+        &format!("scale={}:{}", width, height),
+        pipe,
+        is_recording,
+        clock,
+    )
+}
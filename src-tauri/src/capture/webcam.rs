@@ -0,0 +1,72 @@
+// Webcam recording for the picture-in-picture overlay. ffmpeg decodes a V4L2 device, or
+// any video file looped in real time as a stand in for a camera, and the frames go through
+// a FramePipe on the recording clock. webcam.mp4 therefore pauses with the screen capture
+// and lines up with mousePositions.json.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{decode_into_pipe, CaptureWorker, FramePipe, RecordingClock};
+
+// cropped to fill, the renderer masks and scales it again anyway
+pub const WEBCAM_WIDTH: u32 = 640;
+pub const WEBCAM_HEIGHT: u32 = 480;
+
+#[derive(Default)]
+pub struct WebcamCapture {
+    worker: CaptureWorker,
+}
+
+impl WebcamCapture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // input is a device like /dev/video0 or a video file, recording runs until the screen
+    // capture's is_recording goes false
+    pub fn start(
+        &self,
+        input: &str,
+        output_path: &str,
+        is_recording: Arc<Mutex<bool>>,
+        clock: RecordingClock,
+    ) -> Result<(), String> {
+        let input_args: &'static [&'static str] = if is_device(input) {
+            &["-f", "v4l2"]
+        } else if Path::new(input).is_file() {
+            &["-re", "-stream_loop", "-1"]
+        } else {
+            return Err(format!("Webcam input not found: {}", input));
+        };
+
+        let filter = format!(
+            "scale={w}:{h}:force_original_aspect_ratio=increase,crop={w}:{h}",
+            w = WEBCAM_WIDTH,
+            h = WEBCAM_HEIGHT
+        );
+        let pipe = FramePipe::new(output_path, WEBCAM_WIDTH, WEBCAM_HEIGHT, "bgr0", None, None)?;
+
+        let input = input.to_string();
+
+        // the worker gets a flag of its own, a failing camera shouldn't stop the screen capture
+        self.worker.spawn(Arc::new(Mutex::new(true)), move || {
+            decode_into_pipe(input_args, &input, &filter, pipe, is_recording, clock)
+        });
+
+        Ok(())
+    }
+
+    // Ok if there is no webcam recording to finish
+    pub fn finish(&self, timeout: Duration) -> Result<(), String> {
+        if self.worker.stop().is_err() {
+            return Ok(());
+        }
+
+        self.worker.wait_finished(timeout)
+    }
+}
+
+fn is_device(input: &str) -> bool {
+    input.starts_with("/dev/video")
+}
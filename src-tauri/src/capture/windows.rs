@@ -0,0 +1,651 @@
+// Windows capture: Windows.Graphics.Capture through windows_capture, piped to ffmpeg
+// like the other backends so scaling happens in the encoder. Sources are listed with
+// EnumWindows / EnumDisplayMonitors, thumbnails are grabbed through GDI.
+
+use std::ffi::c_void;
+use std::mem::size_of;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use windows::{
+    core::PWSTR,
+    Win32::Foundation::{CloseHandle, BOOL, HWND, LPARAM, RECT},
+    Win32::Graphics::Dwm::{DwmGetWindowAttribute, DWMWA_CLOAKED},
+    Win32::Graphics::Gdi::{
+        BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject,
+        EnumDisplayMonitors, GetDC, GetDIBits, GetMonitorInfoW, ReleaseDC, SelectObject,
+        BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS, HDC, HMONITOR, MONITORINFO,
+        MONITORINFOEXW, SRCCOPY,
+    },
+    Win32::Storage::Xps::{PrintWindow, PRINT_WINDOW_FLAGS},
+    Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    },
+    Win32::UI::HiDpi::{
+        GetDpiForMonitor, GetDpiForWindow, SetThreadDpiAwarenessContext,
+        DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, MDT_EFFECTIVE_DPI,
+    },
+    Win32::UI::WindowsAndMessaging::{
+        EnumWindows, GetWindow, GetWindowLongW, GetWindowRect, GetWindowTextW,
+        GetWindowThreadProcessId, IsWindowVisible, GWL_EXSTYLE, GW_OWNER, WS_EX_TOOLWINDOW,
+    },
+};
+use windows_capture::monitor::Monitor;
+use windows_capture::window::Window;
+use windows_capture::{
+    capture::GraphicsCaptureApiHandler,
+    frame::Frame,
+    graphics_capture_api::InternalCaptureControl,
+    settings::{ColorFormat, CursorCaptureSettings, DrawBorderSettings, Settings},
+};
+
+use super::{
+    region_info, CaptureBackend, CaptureOptions, CaptureWorker, CursorMode, FrameCallback,
+    FramePipe, OwnedFrame, RecordingClock, RectInfo, Source, SourceInfo,
+};
+
+const DEFAULT_DPI: f64 = 96.0;
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(20);
+const MONITORINFOF_PRIMARY: u32 = 1;
+const PW_RENDERFULLCONTENT: u32 = 2;
+
+#[derive(Default)]
+pub struct WindowsBackend {
+    worker: CaptureWorker,
+}
+
+impl WindowsBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CaptureBackend for WindowsBackend {
+    fn name(&self) -> &'static str {
+        "windows"
+    }
+
+    fn sources(&self) -> Result<Vec<SourceInfo>, String> {
+        use_physical_coordinates();
+
+        let mut sources = get_windows()?;
+        sources.extend(get_monitors().into_iter().map(|(_, info)| info));
+
+        Ok(sources)
+    }
+
+    fn source_info(&self, source: &Source) -> Result<SourceInfo, String> {
+        use_physical_coordinates();
+
+        match *source {
+            Source::Window { id } => get_window_info_by_usize(id),
+            Source::Monitor { index } => get_monitors()
+                .into_iter()
+                .nth(index)
+                .map(|(_, info)| info)
+                .ok_or_else(|| format!("Monitor {} not found", index)),
+            Source::Region { monitor, .. } => region_info(
+                self.source_info(&Source::Monitor { index: monitor })?,
+                source,
+            ),
+        }
+    }
+
+    fn grab_frame(&self, source: &Source) -> Result<OwnedFrame, String> {
+        let info = self.source_info(source)?;
+
+        match *source {
+            Source::Window { id } => unsafe { grab_gdi(Some(HWND(id as *mut _)), &info.rect) },
+            _ => unsafe { grab_gdi(None, &info.rect) },
+        }
+    }
+
+    fn start(&self, options: CaptureOptions) -> Result<(), String> {
+        // a region is recorded as its monitor with every frame cropped to it
+        let crop = match options.source {
+            Source::Region { monitor, .. } => {
+                let monitor_rect = self.source_info(&Source::Monitor { index: monitor })?.rect;
+                let region_rect = self.source_info(&options.source)?.rect;
+
+                Some(RectInfo::new(
+                    region_rect.left - monitor_rect.left,
+                    region_rect.top - monitor_rect.top,
+                    region_rect.width,
+                    region_rect.height,
+                ))
+            }
+            _ => None,
+        };
+
+        let CaptureOptions {
+            source,
+            output_path,
+            max_size,
+            cursor_mode,
+            is_recording,
+            clock,
+            on_frame,
+            ..
+        } = options;
+
+        // capture items hold raw handles which aren't Send, pass the handle value instead
+        let monitor_handle = match source {
+            Source::Window { .. } => None,
+            Source::Monitor { index } | Source::Region { monitor: index, .. } => Some(
+                get_monitors()
+                    .into_iter()
+                    .nth(index)
+                    .map(|(hmonitor, _)| hmonitor.0 as usize)
+                    .ok_or_else(|| format!("Monitor {} not found", index))?,
+            ),
+        };
+
+        let cursor_settings = match cursor_mode {
+            CursorMode::Hidden => CursorCaptureSettings::WithoutCursor,
+            CursorMode::Embedded => CursorCaptureSettings::WithCursor,
+        };
+
+        let flag = is_recording.clone();
+
+        self.worker.spawn(is_recording, move || {
+            let pipe = Arc::new(Mutex::new(None));
+
+            let flags = CaptureFlags {
+                output_path,
+                max_size,
+                is_recording: flag.clone(),
+                pipe: pipe.clone(),
+                on_frame,
+                clock,
+                crop,
+            };
+
+            let control = if let Some(monitor_handle) = monitor_handle {
+                let target_monitor = Monitor::from_raw_hmonitor(monitor_handle as *mut c_void);
+
+                Capture::start_free_threaded(Settings::new(
+                    target_monitor,
+                    cursor_settings,
+                    DrawBorderSettings::Default,
+                    ColorFormat::Rgba8,
+                    flags,
+                ))
+            } else {
+                let Source::Window { id } = source else {
+                    unreachable!()
+                };
+                let hwnd = HWND(id as *mut _);
+                let raw_hwnd = hwnd.0 as *mut c_void;
+                let target_window: Window = unsafe { Window::from_raw_hwnd(raw_hwnd) };
+
+                Capture::start_free_threaded(Settings::new(
+                    target_window,
+                    cursor_settings,
+                    DrawBorderSettings::Default,
+                    ColorFormat::Rgba8,
+                    flags,
+                ))
+            }
+            .map_err(|e| e.to_string())?;
+
+            // frames only arrive when the content changes, so stopping can't wait on a last
+            // frame. finished early means the window was closed or the handler failed
+            while *flag.lock().unwrap() && !control.is_finished() {
+                thread::sleep(STOP_POLL_INTERVAL);
+            }
+
+            control.stop().map_err(|e| e.to_string())?;
+
+            // writes the trailer, the file is complete once this returns
+            let pipe = pipe.lock().unwrap().take();
+            match pipe {
+                Some(pipe) => pipe.finish(),
+                None => Err("Capture never started encoding".to_string()),
+            }
+        });
+
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), String> {
+        self.worker.stop()
+    }
+
+    fn wait_finished(&self, timeout: Duration) -> Result<(), String> {
+        self.worker.wait_finished(timeout)
+    }
+}
+
+struct CaptureFlags {
+    output_path: String,
+    max_size: Option<(u32, u32)>,
+    is_recording: Arc<Mutex<bool>>,
+    pipe: Arc<Mutex<Option<FramePipe>>>, // finished by the worker, not the handler
+    on_frame: Option<FrameCallback>,
+    clock: RecordingClock,
+    crop: Option<RectInfo>, // relative to the captured item
+}
+
+struct Capture {
+    output_path: String,
+    max_size: Option<(u32, u32)>,
+    pipe: Arc<Mutex<Option<FramePipe>>>,
+    is_recording: Arc<Mutex<bool>>,
+    on_frame: Option<FrameCallback>,
+    clock: RecordingClock,
+    crop: Option<RectInfo>,
+    frame_buffer: Vec<u8>,
+}
+
+impl GraphicsCaptureApiHandler for Capture {
+    type Flags = CaptureFlags;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn new(flags: Self::Flags) -> Result<Self, Self::Error> {
+        Ok(Self {
+            output_path: flags.output_path,
+            max_size: flags.max_size,
+            pipe: flags.pipe,
+            is_recording: flags.is_recording,
+            on_frame: flags.on_frame,
+            clock: flags.clock,
+            crop: flags.crop,
+            frame_buffer: Vec::new(),
+        })
+    }
+
+    fn on_frame_arrived(
+        &mut self,
+        frame: &mut Frame,
+        capture_control: InternalCaptureControl,
+    ) -> Result<(), Self::Error> {
+        if !*self.is_recording.lock().unwrap() {
+            capture_control.stop();
+            return Ok(());
+        }
+
+        // paused spans are dropped, not encoded
+        if self.clock.is_paused() {
+            return Ok(());
+        }
+
+        // frames are paced on the recording clock rather than their own timespan, so
+        // the video timeline skips paused spans the same way the mouse positions do
+        let elapsed = self.clock.elapsed();
+
+        let (left, top, right, bottom) = match &self.crop {
+            Some(crop) => (
+                (crop.left.max(0) as u32).min(frame.width()),
+                (crop.top.max(0) as u32).min(frame.height()),
+                (crop.right.max(0) as u32).min(frame.width()),
+                (crop.bottom.max(0) as u32).min(frame.height()),
+            ),
+            None => (0, 0, frame.width(), frame.height()),
+        };
+
+        let (width, height) = (right - left, bottom - top);
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        let mut buffer = frame.buffer_crop(left, top, right, bottom)?;
+        let data = buffer.as_nopadding_buffer(&mut self.frame_buffer);
+
+        // the pipe is sized from the first frame that actually arrives rather than the
+        // window rect, which can be off by borders or dpi. max_size and windows resized
+        // mid recording are scaled by the pipe
+        let mut pipe = self.pipe.lock().unwrap();
+        if pipe.is_none() {
+            *pipe = Some(FramePipe::new(
+                &self.output_path,
+                width,
+                height,
+                "rgba",
+                self.max_size,
+                self.on_frame.take(),
+            )?);
+        }
+
+        if let Some(pipe) = pipe.as_mut() {
+            pipe.write_paced(data, width, height, (width * 4) as usize, elapsed)?;
+        }
+
+        Ok(())
+    }
+
+    fn on_closed(&mut self) -> Result<(), Self::Error> {
+        println!("Capture Session Closed");
+        Ok(())
+    }
+}
+
+// window and monitor rects are otherwise virtualized for dpi unaware threads
+pub fn use_physical_coordinates() {
+    unsafe {
+        let _ = SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+    }
+}
+
+// what decides whether a top level window is listed
+#[derive(Debug, Clone, Copy, Default)]
+struct WindowTraits {
+    visible: bool,
+    cloaked: bool, // other virtual desktops, suspended UWP apps
+    tool_window: bool,
+    owned: bool, // popups and dialogs of another window
+}
+
+impl WindowTraits {
+    unsafe fn of(hwnd: HWND) -> Self {
+        let mut cloaked: u32 = 0;
+        let cloaked_result = DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_CLOAKED,
+            &mut cloaked as *mut _ as *mut c_void,
+            size_of::<u32>() as u32,
+        );
+
+        WindowTraits {
+            visible: IsWindowVisible(hwnd).as_bool(),
+            cloaked: cloaked_result.is_ok() && cloaked != 0,
+            tool_window: GetWindowLongW(hwnd, GWL_EXSTYLE) as u32 & WS_EX_TOOLWINDOW.0 != 0,
+            owned: GetWindow(hwnd, GW_OWNER)
+                .map(|owner| !owner.0.is_null())
+                .unwrap_or(false),
+        }
+    }
+
+    // only windows a user could pick: visible, not cloaked, not tool windows or owned
+    // popups. get_windows also drops untitled and empty ones
+    fn is_capturable(&self) -> bool {
+        self.visible && !self.cloaked && !self.tool_window && !self.owned
+    }
+}
+
+fn get_windows() -> Result<Vec<SourceInfo>, String> {
+    let mut windows: Vec<SourceInfo> = Vec::new();
+
+    // EnumWindows callback to enumerate all top-level windows
+    unsafe extern "system" fn enum_windows_callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        if WindowTraits::of(hwnd).is_capturable() {
+            if let Ok(info) = get_window_info_by_usize(hwnd.0 as usize) {
+                if !info.title.is_empty() && info.rect.width > 0 && info.rect.height > 0 {
+                    let sources = lparam.0 as *mut Vec<SourceInfo>;
+                    (*sources).push(info);
+                }
+            }
+        }
+
+        true.into() // Continue enumeration
+    }
+
+    unsafe {
+        // Enumerate all top-level windows
+        EnumWindows(
+            Some(enum_windows_callback),
+            LPARAM(&mut windows as *mut _ as isize),
+        )
+        .map_err(|e| format!("Couldn't enumerate windows: {}", e))?;
+    }
+
+    Ok(windows)
+}
+
+fn get_window_info(hwnd: HWND) -> Result<(String, RECT), String> {
+    unsafe {
+        let mut rect = RECT::default();
+        GetWindowRect(hwnd, &mut rect).map_err(|e| e.to_string())?;
+        let mut title: [u16; 512] = [0; 512];
+        let len = GetWindowTextW(hwnd, &mut title);
+        let title = String::from_utf16_lossy(&title[..len as usize]);
+        Ok((title, rect))
+    }
+}
+
+// executable name of the process owning the window, e.g. "Code" for Code.exe
+fn get_window_app_name(hwnd: HWND) -> Option<String> {
+    unsafe {
+        let mut process_id: u32 = 0;
+        GetWindowThreadProcessId(hwnd, Some(&mut process_id));
+
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id).ok()?;
+
+        let mut path: [u16; 1024] = [0; 1024];
+        let mut len = path.len() as u32;
+        let queried = QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            PWSTR(path.as_mut_ptr()),
+            &mut len,
+        );
+        let _ = CloseHandle(process);
+        queried.ok()?;
+
+        let path = String::from_utf16_lossy(&path[..len as usize]);
+        Path::new(&path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+    }
+}
+
+fn get_window_info_by_usize(hwnd_value: usize) -> Result<SourceInfo, String> {
+    // Convert the usize back into an HWND
+    let hwnd = HWND(hwnd_value as *mut _);
+
+    if let Ok((title, rect)) = get_window_info(hwnd) {
+        let dpi = unsafe { GetDpiForWindow(hwnd) };
+
+        Ok(SourceInfo {
+            source: Source::Window { id: hwnd_value },
+            title: title,
+            app_name: get_window_app_name(hwnd),
+            rect: RectInfo::new(
+                rect.left,
+                rect.top,
+                rect.right - rect.left,
+                rect.bottom - rect.top,
+            ),
+            scale_factor: if dpi > 0 {
+                dpi as f64 / DEFAULT_DPI
+            } else {
+                1.0
+            },
+            thumbnail: None,
+        })
+    } else {
+        Err("Failed to get window information".to_string())
+    }
+}
+
+// monitors in EnumDisplayMonitors order, which is what Source::Monitor indexes into
+fn get_monitors() -> Vec<(HMONITOR, SourceInfo)> {
+    let mut handles: Vec<HMONITOR> = Vec::new();
+
+    unsafe extern "system" fn enum_monitors_callback(
+        hmonitor: HMONITOR,
+        _hdc: HDC,
+        _rect: *mut RECT,
+        lparam: LPARAM,
+    ) -> BOOL {
+        let handles = lparam.0 as *mut Vec<HMONITOR>;
+        (*handles).push(hmonitor);
+
+        true.into() // Continue enumeration
+    }
+
+    unsafe {
+        let _ = EnumDisplayMonitors(
+            HDC::default(),
+            None,
+            Some(enum_monitors_callback),
+            LPARAM(&mut handles as *mut _ as isize),
+        );
+    }
+
+    handles
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, hmonitor)| unsafe {
+            let mut info = MONITORINFOEXW::default();
+            info.monitorInfo.cbSize = size_of::<MONITORINFOEXW>() as u32;
+
+            if !GetMonitorInfoW(hmonitor, &mut info as *mut _ as *mut MONITORINFO).as_bool() {
+                return None;
+            }
+
+            let rect = info.monitorInfo.rcMonitor;
+            let is_primary = info.monitorInfo.dwFlags & MONITORINFOF_PRIMARY != 0;
+
+            let (mut dpi_x, mut dpi_y) = (0, 0);
+            let scale_factor =
+                match GetDpiForMonitor(hmonitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) {
+                    Ok(()) if dpi_x > 0 => dpi_x as f64 / DEFAULT_DPI,
+                    _ => 1.0,
+                };
+
+            let title = if is_primary {
+                format!("Display {} (Primary)", index + 1)
+            } else {
+                format!("Display {}", index + 1)
+            };
+
+            Some((
+                hmonitor,
+                SourceInfo {
+                    source: Source::Monitor { index },
+                    title,
+                    app_name: None,
+                    rect: RectInfo::new(
+                        rect.left,
+                        rect.top,
+                        rect.right - rect.left,
+                        rect.bottom - rect.top,
+                    ),
+                    scale_factor,
+                    thumbnail: None,
+                },
+            ))
+        })
+        .collect()
+}
+
+// PrintWindow renders covered windows too, monitors are copied off the screen
+unsafe fn grab_gdi(hwnd: Option<HWND>, rect: &RectInfo) -> Result<OwnedFrame, String> {
+    let (width, height) = (rect.width, rect.height);
+    if width <= 0 || height <= 0 {
+        return Err("Source has no area".to_string());
+    }
+
+    let screen_dc = GetDC(HWND::default());
+    let mem_dc = CreateCompatibleDC(screen_dc);
+    let bitmap = CreateCompatibleBitmap(screen_dc, width, height);
+    let previous = SelectObject(mem_dc, bitmap);
+
+    let drawn = match hwnd {
+        Some(hwnd) => PrintWindow(hwnd, mem_dc, PRINT_WINDOW_FLAGS(PW_RENDERFULLCONTENT)).as_bool(),
+        None => BitBlt(
+            mem_dc, 0, 0, width, height, screen_dc, rect.left, rect.top, SRCCOPY,
+        )
+        .is_ok(),
+    };
+
+    let mut info = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width,
+            biHeight: -height, // top down rows
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut data = vec![0u8; (width * height * 4) as usize];
+
+    let lines = GetDIBits(
+        mem_dc,
+        bitmap,
+        0,
+        height as u32,
+        Some(data.as_mut_ptr() as *mut c_void),
+        &mut info,
+        DIB_RGB_COLORS,
+    );
+
+    SelectObject(mem_dc, previous);
+    let _ = DeleteObject(bitmap);
+    let _ = DeleteDC(mem_dc);
+    ReleaseDC(HWND::default(), screen_dc);
+
+    if !drawn || lines == 0 {
+        return Err("Failed to grab source image".to_string());
+    }
+
+    Ok(OwnedFrame {
+        data,
+        width: width as u32,
+        height: height as u32,
+        stride: (width * 4) as usize,
+        pix_fmt: "bgr0",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_plain_visible_windows_are_capturable() {
+        let plain = WindowTraits {
+            visible: true,
+            ..Default::default()
+        };
+
+        assert!(plain.is_capturable());
+        assert!(!WindowTraits::default().is_capturable());
+
+        for hidden in [
+            WindowTraits {
+                cloaked: true,
+                ..plain
+            },
+            WindowTraits {
+                tool_window: true,
+                ..plain
+            },
+            WindowTraits {
+                owned: true,
+                ..plain
+            },
+        ] {
+            assert!(!hidden.is_capturable(), "{:?} should be skipped", hidden);
+        }
+    }
+
+    #[test]
+    fn lists_windows_before_monitors() {
+        let sources = WindowsBackend::new().sources().unwrap();
+        let first_monitor = sources
+            .iter()
+            .position(|info| matches!(info.source, Source::Monitor { .. }))
+            .expect("no monitors listed");
+
+        assert!(sources[first_monitor..]
+            .iter()
+            .all(|info| matches!(info.source, Source::Monitor { .. })));
+
+        for info in &sources[..first_monitor] {
+            let Source::Window { id } = info.source else {
+                unreachable!()
+            };
+
+            assert!(unsafe { WindowTraits::of(HWND(id as *mut _)) }.is_capturable());
+            assert!(!info.title.is_empty());
+            assert!(info.rect.width > 0 && info.rect.height > 0);
+        }
+    }
+}
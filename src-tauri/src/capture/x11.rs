@@ -0,0 +1,717 @@
+// X11 capture: windows are redirected with XComposite so they can be grabbed even when
+// covered, frames are pulled through MIT-SHM when the server is local. Works under Xvfb.
+// Monitors come from RandR and are captured as a crop of the root window.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection;
+use x11rb::protocol::composite::{ConnectionExt as _, Redirect};
+use x11rb::protocol::randr::ConnectionExt as _;
+use x11rb::protocol::shm::ConnectionExt as _;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _, ImageFormat, MapState, Window};
+use x11rb::rust_connection::RustConnection;
+
+use super::{
+    region_info, CaptureBackend, CaptureOptions, CaptureWorker, FramePipe, OwnedFrame, RectInfo,
+    Source, SourceInfo, CAPTURE_FPS,
+};
+
+#[derive(Default)]
+pub struct X11Backend {
+    worker: CaptureWorker,
+    // opened on first use and kept, the mouse tracker asks for window bounds every 500ms.
+    // Recording opens a connection of its own for the capture thread
+    connection: Mutex<Option<Arc<(RustConnection, Window)>>>,
+    // None connects to $DISPLAY
+    display: Option<String>,
+}
+
+impl X11Backend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_display(display: &str) -> Self {
+        Self {
+            display: Some(display.to_string()),
+            ..Self::default()
+        }
+    }
+
+    // the shared connection, opened again if the server dropped it
+    fn connection(&self) -> Result<Arc<(RustConnection, Window)>, String> {
+        let mut connection = self.connection.lock().unwrap();
+
+        if let Some(existing) = connection.as_ref() {
+            if existing.0.flush().is_ok() {
+                return Ok(existing.clone());
+            }
+        }
+
+        let opened = Arc::new(connect(self.display.as_deref())?);
+        *connection = Some(opened.clone());
+
+        Ok(opened)
+    }
+}
+
+impl CaptureBackend for X11Backend {
+    fn name(&self) -> &'static str {
+        "x11"
+    }
+
+    fn sources(&self) -> Result<Vec<SourceInfo>, String> {
+        let connection = self.connection()?;
+        let (conn, root) = &*connection;
+
+        list_sources(conn, *root)
+    }
+
+    fn source_info(&self, source: &Source) -> Result<SourceInfo, String> {
+        let connection = self.connection()?;
+        let (conn, root) = (&connection.0, connection.1);
+
+        match *source {
+            Source::Window { id } => window_info(conn, root, id as Window),
+            Source::Monitor { index } => monitors(conn, root)?
+                .into_iter()
+                .nth(index)
+                .ok_or_else(|| format!("Monitor {} not found", index)),
+            Source::Region { monitor, .. } => region_info(
+                self.source_info(&Source::Monitor { index: monitor })?,
+                source,
+            ),
+        }
+    }
+
+    fn grab_frame(&self, source: &Source) -> Result<OwnedFrame, String> {
+        let connection = self.connection()?;
+        let (conn, root) = (&connection.0, connection.1);
+
+        let (drawable, x, y, width, height) = match source {
+            Source::Window { id } => {
+                let geometry = conn
+                    .get_geometry(*id as Window)
+                    .map_err(|e| e.to_string())?
+                    .reply()
+                    .map_err(|e| e.to_string())?;
+                (*id as Window, 0, 0, geometry.width, geometry.height)
+            }
+            _ => {
+                let rect = self.source_info(source)?.rect;
+                (
+                    root,
+                    rect.left as i16,
+                    rect.top as i16,
+                    rect.width as u16,
+                    rect.height as u16,
+                )
+            }
+        };
+
+        let image = conn
+            .get_image(ImageFormat::Z_PIXMAP, drawable, x, y, width, height, !0)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?;
+
+        Ok(OwnedFrame {
+            data: image.data,
+            width: width as u32,
+            height: height as u32,
+            stride: width as usize * 4,
+            pix_fmt: "bgr0",
+        })
+    }
+
+    // GetImage never includes the pointer, so cursor_mode has no effect here
+    fn start(&self, options: CaptureOptions) -> Result<(), String> {
+        let (window, area) = match options.source {
+            Source::Window { id } => (id as Window, None),
+            // monitors and regions are both a fixed crop of the root window
+            Source::Monitor { .. } | Source::Region { .. } => {
+                let root = self.connection()?.1;
+                (root, Some(self.source_info(&options.source)?.rect))
+            }
+        };
+
+        let connection = connect(self.display.as_deref())?;
+        let is_recording = options.is_recording.clone();
+
+        self.worker.spawn(is_recording, move || {
+            capture_window(connection, window, area, options)
+        });
+
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), String> {
+        self.worker.stop()
+    }
+
+    fn wait_finished(&self, timeout: Duration) -> Result<(), String> {
+        self.worker.wait_finished(timeout)
+    }
+}
+
+fn connect(display: Option<&str>) -> Result<(RustConnection, Window), String> {
+    let (conn, screen_num) =
+        x11rb::connect(display).map_err(|e| format!("Failed to connect to X server: {}", e))?;
+    let root = conn.setup().roots[screen_num].root;
+
+    Ok((conn, root))
+}
+
+fn intern_atom(conn: &RustConnection, name: &str) -> Result<u32, String> {
+    Ok(conn
+        .intern_atom(false, name.as_bytes())
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map_err(|e| e.to_string())?
+        .atom)
+}
+
+fn window_title(conn: &RustConnection, window: Window) -> String {
+    let net_wm_name = intern_atom(conn, "_NET_WM_NAME").unwrap_or(0);
+    let utf8_string = intern_atom(conn, "UTF8_STRING").unwrap_or(0);
+
+    let title = conn
+        .get_property(false, window, net_wm_name, utf8_string, 0, 1024)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .filter(|reply| !reply.value.is_empty())
+        .or_else(|| {
+            conn.get_property(false, window, AtomEnum::WM_NAME, AtomEnum::STRING, 0, 1024)
+                .ok()
+                .and_then(|cookie| cookie.reply().ok())
+        });
+
+    title
+        .map(|reply| String::from_utf8_lossy(&reply.value).to_string())
+        .unwrap_or_default()
+}
+
+// WM_CLASS is "instance\0Class\0", the class is the application's name
+fn window_app_name(conn: &RustConnection, window: Window) -> Option<String> {
+    let reply = conn
+        .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 1024)
+        .ok()?
+        .reply()
+        .ok()?;
+
+    reply
+        .value
+        .split(|byte| *byte == 0)
+        .filter(|part| !part.is_empty())
+        .last()
+        .map(|class| String::from_utf8_lossy(class).to_string())
+}
+
+// X11 has one scale for every monitor, taken from Xft.dpi like toolkits do
+fn scale_factor(conn: &RustConnection, root: Window) -> f64 {
+    conn.get_property(
+        false,
+        root,
+        AtomEnum::RESOURCE_MANAGER,
+        AtomEnum::STRING,
+        0,
+        u32::MAX,
+    )
+    .ok()
+    .and_then(|cookie| cookie.reply().ok())
+    .and_then(|reply| {
+        String::from_utf8_lossy(&reply.value)
+            .lines()
+            .find_map(|line| line.strip_prefix("Xft.dpi:"))
+            .and_then(|dpi| dpi.trim().parse::<f64>().ok())
+    })
+    .map(|dpi| dpi / 96.0)
+    .unwrap_or(1.0)
+}
+
+fn window_rect(conn: &RustConnection, root: Window, window: Window) -> Result<RectInfo, String> {
+    let geometry = conn
+        .get_geometry(window)
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map_err(|e| e.to_string())?;
+
+    // geometry is relative to the parent (usually a wm frame), we want root coordinates
+    let origin = conn
+        .translate_coordinates(window, root, 0, 0)
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map_err(|e| e.to_string())?;
+
+    Ok(RectInfo::new(
+        origin.dst_x as i32,
+        origin.dst_y as i32,
+        geometry.width as i32,
+        geometry.height as i32,
+    ))
+}
+
+fn is_viewable(conn: &RustConnection, window: Window) -> bool {
+    conn.get_window_attributes(window)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .map(|attributes| attributes.map_state == MapState::VIEWABLE)
+        .unwrap_or(false)
+}
+
+fn window_info(conn: &RustConnection, root: Window, window: Window) -> Result<SourceInfo, String> {
+    Ok(SourceInfo {
+        source: Source::Window {
+            id: window as usize,
+        },
+        title: window_title(conn, window),
+        app_name: window_app_name(conn, window),
+        rect: window_rect(conn, root, window)?,
+        scale_factor: scale_factor(conn, root),
+        thumbnail: None,
+    })
+}
+
+// RandR monitors, or the whole root window when the extension isn't there (plain Xvfb)
+fn monitors(conn: &RustConnection, root: Window) -> Result<Vec<SourceInfo>, String> {
+    let scale_factor = scale_factor(conn, root);
+
+    let randr_monitors = conn
+        .randr_get_monitors(root, true)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .map(|reply| reply.monitors)
+        .unwrap_or_default();
+
+    if randr_monitors.is_empty() {
+        return Ok(vec![SourceInfo {
+            source: Source::Monitor { index: 0 },
+            title: "Entire Screen".to_string(),
+            app_name: None,
+            rect: window_rect(conn, root, root)?,
+            scale_factor,
+            thumbnail: None,
+        }]);
+    }
+
+    Ok(randr_monitors
+        .iter()
+        .enumerate()
+        .map(|(index, monitor)| {
+            let name = conn
+                .get_atom_name(monitor.name)
+                .ok()
+                .and_then(|cookie| cookie.reply().ok())
+                .map(|reply| String::from_utf8_lossy(&reply.name).to_string())
+                .unwrap_or_else(|| format!("Display {}", index + 1));
+
+            SourceInfo {
+                source: Source::Monitor { index },
+                title: if monitor.primary {
+                    format!("{} (Primary)", name)
+                } else {
+                    name
+                },
+                app_name: None,
+                rect: RectInfo::new(
+                    monitor.x as i32,
+                    monitor.y as i32,
+                    monitor.width as i32,
+                    monitor.height as i32,
+                ),
+                scale_factor,
+                thumbnail: None,
+            }
+        })
+        .collect())
+}
+
+fn list_sources(conn: &RustConnection, root: Window) -> Result<Vec<SourceInfo>, String> {
+    // a window manager keeps the real client list, bare servers (Xvfb) only have the tree
+    let net_client_list = intern_atom(conn, "_NET_CLIENT_LIST")?;
+    let client_list: Vec<Window> = conn
+        .get_property(false, root, net_client_list, AtomEnum::WINDOW, 0, u32::MAX)
+        .map_err(|e| e.to_string())?
+        .reply()
+        .ok()
+        .and_then(|reply| reply.value32().map(|values| values.collect()))
+        .unwrap_or_default();
+
+    let candidates = if client_list.is_empty() {
+        conn.query_tree(root)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?
+            .children
+    } else {
+        client_list
+    };
+
+    let mut sources: Vec<SourceInfo> = Vec::new();
+
+    for window in candidates {
+        if !is_viewable(conn, window) {
+            continue;
+        }
+
+        if let Ok(info) = window_info(conn, root, window) {
+            if info.title.is_empty() || info.rect.width <= 0 || info.rect.height <= 0 {
+                continue;
+            }
+            sources.push(info);
+        }
+    }
+
+    sources.extend(monitors(conn, root)?);
+
+    Ok(sources)
+}
+
+struct ShmSegment {
+    seg: u32,
+    addr: *mut libc::c_void,
+    size: usize,
+}
+
+impl ShmSegment {
+    fn new(conn: &RustConnection, size: usize) -> Result<Self, String> {
+        unsafe {
+            let shmid = libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600);
+            if shmid < 0 {
+                return Err("shmget failed".to_string());
+            }
+
+            let addr = libc::shmat(shmid, std::ptr::null(), 0);
+            if addr as isize == -1 {
+                libc::shmctl(shmid, libc::IPC_RMID, std::ptr::null_mut());
+                return Err("shmat failed".to_string());
+            }
+
+            let seg = conn.generate_id().map_err(|e| e.to_string())?;
+            let attached = conn
+                .shm_attach(seg, shmid as u32, false)
+                .map_err(|e| e.to_string())
+                .and_then(|cookie| cookie.check().map_err(|e| e.to_string()));
+
+            // marked for removal now, it goes away once both sides detach
+            libc::shmctl(shmid, libc::IPC_RMID, std::ptr::null_mut());
+
+            if let Err(e) = attached {
+                libc::shmdt(addr);
+                return Err(format!("Server could not attach shm segment: {}", e));
+            }
+
+            Ok(Self { seg, addr, size })
+        }
+    }
+
+    fn data(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.addr as *const u8, self.size) }
+    }
+
+    fn release(self, conn: &RustConnection) {
+        let _ = conn.shm_detach(self.seg);
+        let _ = conn.flush();
+        unsafe {
+            libc::shmdt(self.addr);
+        }
+    }
+}
+
+// blocks until is_recording goes false, then finalizes output_path.
+// area crops the window to a fixed rect, used to record a monitor or region off the root window
+fn capture_window(
+    (conn, root): (RustConnection, Window),
+    window: Window,
+    area: Option<RectInfo>,
+    options: CaptureOptions,
+) -> Result<(), String> {
+    let CaptureOptions {
+        output_path,
+        max_size,
+        is_recording,
+        clock,
+        on_frame,
+        ..
+    } = options;
+
+    let has_composite = window != root
+        && conn
+            .composite_query_version(0, 4)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .is_some();
+    let has_shm = conn
+        .shm_query_version()
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .is_some();
+
+    println!(
+        "X11 capture of {} (composite: {}, shm: {})",
+        window, has_composite, has_shm
+    );
+
+    if has_composite {
+        conn.composite_redirect_window(window, Redirect::AUTOMATIC)
+            .map_err(|e| e.to_string())?;
+    }
+
+    let rect = match &area {
+        Some(area) => area.clone(),
+        None => window_rect(&conn, root, window)?,
+    };
+    let (width, height) = (rect.width as u32, rect.height as u32);
+
+    let mut pipe = FramePipe::new(&output_path, width, height, "bgr0", max_size, on_frame)?;
+    let mut shm: Option<ShmSegment> = None;
+    let mut pixmap: Option<(u32, u16, u16)> = None;
+
+    let frame_interval = Duration::from_secs_f64(1.0 / CAPTURE_FPS as f64);
+
+    let result = (|| -> Result<(), String> {
+        while *is_recording.lock().unwrap() {
+            let frame_start = Instant::now();
+
+            if clock.is_paused() {
+                thread::sleep(frame_interval);
+                continue;
+            }
+
+            let (frame_x, frame_y, frame_width, frame_height) = match &area {
+                Some(area) => (
+                    area.left as i16,
+                    area.top as i16,
+                    area.width as u16,
+                    area.height as u16,
+                ),
+                None => {
+                    let geometry = conn
+                        .get_geometry(window)
+                        .map_err(|e| e.to_string())?
+                        .reply()
+                        .map_err(|e| format!("Source window went away: {}", e))?;
+                    (0, 0, geometry.width, geometry.height)
+                }
+            };
+
+            // the composite pixmap is replaced whenever the window resizes
+            let drawable = if has_composite {
+                match pixmap {
+                    Some((id, w, h)) if w == frame_width && h == frame_height => id,
+                    _ => {
+                        if let Some((id, _, _)) = pixmap.take() {
+                            let _ = conn.free_pixmap(id);
+                        }
+                        let id = conn.generate_id().map_err(|e| e.to_string())?;
+                        conn.composite_name_window_pixmap(window, id)
+                            .map_err(|e| e.to_string())?;
+                        pixmap = Some((id, frame_width, frame_height));
+                        id
+                    }
+                }
+            } else {
+                window
+            };
+
+            let frame_bytes = frame_width as usize * frame_height as usize * 4;
+
+            if has_shm && shm.as_ref().map(|s| s.size < frame_bytes).unwrap_or(true) {
+                if let Some(old) = shm.take() {
+                    old.release(&conn);
+                }
+                shm = ShmSegment::new(&conn, frame_bytes)
+                    .map_err(|e| eprintln!("Falling back to GetImage: {}", e))
+                    .ok();
+            }
+
+            let elapsed = clock.elapsed();
+
+            if let Some(segment) = &shm {
+                conn.shm_get_image(
+                    drawable,
+                    frame_x,
+                    frame_y,
+                    frame_width,
+                    frame_height,
+                    !0,
+                    ImageFormat::Z_PIXMAP.into(),
+                    segment.seg,
+                    0,
+                )
+                .map_err(|e| e.to_string())?
+                .reply()
+                .map_err(|e| e.to_string())?;
+
+                pipe.write_paced(
+                    &segment.data()[..frame_bytes],
+                    frame_width as u32,
+                    frame_height as u32,
+                    frame_width as usize * 4,
+                    elapsed,
+                )?;
+            } else {
+                let image = conn
+                    .get_image(
+                        ImageFormat::Z_PIXMAP,
+                        drawable,
+                        frame_x,
+                        frame_y,
+                        frame_width,
+                        frame_height,
+                        !0,
+                    )
+                    .map_err(|e| e.to_string())?
+                    .reply()
+                    .map_err(|e| e.to_string())?;
+
+                pipe.write_paced(
+                    &image.data,
+                    frame_width as u32,
+                    frame_height as u32,
+                    frame_width as usize * 4,
+                    elapsed,
+                )?;
+            }
+
+            if let Some(remaining) = frame_interval.checked_sub(frame_start.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+
+        Ok(())
+    })();
+
+    if let Some((id, _, _)) = pixmap {
+        let _ = conn.free_pixmap(id);
+    }
+    if let Some(segment) = shm {
+        segment.release(&conn);
+    }
+    if has_composite {
+        let _ = conn.composite_unredirect_window(window, Redirect::AUTOMATIC);
+    }
+    let _ = conn.flush();
+
+    // always finish so whatever was recorded before an error is still playable
+    let finished = pipe.finish();
+
+    result.and(finished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use x11rb::protocol::xproto::{CreateWindowAux, PropMode, WindowClass};
+    use x11rb::wrapper::ConnectionExt as _;
+
+    struct Xvfb {
+        child: Child,
+        display: String,
+    }
+
+    impl Drop for Xvfb {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+
+    // Xvfb on the first free display, None when it isn't installed or doesn't come up
+    fn start_xvfb() -> Option<Xvfb> {
+        let child = Command::new("Xvfb")
+            .args(["-displayfd", "1", "-screen", "0", "1280x720x24"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut xvfb = Xvfb {
+            child,
+            display: String::new(),
+        };
+
+        // the display number is written to the fd once the server accepts connections
+        let mut line = String::new();
+        let stdout = xvfb.child.stdout.take()?;
+        BufReader::new(stdout).read_line(&mut line).ok()?;
+
+        let number = line.trim();
+        if number.is_empty() {
+            return None;
+        }
+
+        xvfb.display = format!(":{}", number);
+        Some(xvfb)
+    }
+
+    #[test]
+    fn finds_and_grabs_windows_under_xvfb() {
+        let Some(xvfb) = start_xvfb() else {
+            eprintln!("Xvfb is not available, skipping");
+            return;
+        };
+
+        let (conn, screen_num) = x11rb::connect(Some(&xvfb.display)).unwrap();
+        let screen = &conn.setup().roots[screen_num];
+        let window = conn.generate_id().unwrap();
+
+        conn.create_window(
+            screen.root_depth,
+            window,
+            screen.root,
+            10,
+            20,
+            320,
+            240,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            0,
+            &CreateWindowAux::new().background_pixel(screen.white_pixel),
+        )
+        .unwrap();
+        conn.change_property8(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_NAME,
+            AtomEnum::STRING,
+            b"Xvfb Test",
+        )
+        .unwrap();
+        conn.map_window(window).unwrap();
+        conn.get_input_focus().unwrap().reply().unwrap();
+
+        let backend = X11Backend::with_display(&xvfb.display);
+        let source = Source::Window {
+            id: window as usize,
+        };
+
+        let sources = backend.sources().unwrap();
+        assert!(sources.iter().any(|info| info.source == source));
+
+        // the tracker polls like this, all on the one connection
+        for _ in 0..3 {
+            let info = backend.source_info(&source).unwrap();
+            assert_eq!(info.title, "Xvfb Test");
+            assert_eq!(
+                (
+                    info.rect.left,
+                    info.rect.top,
+                    info.rect.width,
+                    info.rect.height
+                ),
+                (10, 20, 320, 240)
+            );
+        }
+
+        let frame = backend.grab_frame(&source).unwrap();
+        assert_eq!((frame.width, frame.height), (320, 240));
+        assert_eq!(frame.data.len(), 320 * 240 * 4);
+
+        let monitor = backend.source_info(&Source::Monitor { index: 0 }).unwrap();
+        assert_eq!((monitor.rect.width, monitor.rect.height), (1280, 720));
+    }
+}
@@ -19,20 +19,24 @@ use std::error::Error;
 use std::fs;
 use std::fs::File;
 use std::io::BufReader;
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time;
 use std::time::Instant;
-use windows_capture::window::Window;
+
+mod capture;
 
 use rayon::prelude::*;
 
 // FFmpeg bindings
 use ffmpeg_next as ffmpeg;
 
+use ab_glyph::{point, Font, FontArc, PxScale, ScaleFont};
+
 // Constants
-const GRADIENT_SPEED: i32 = 2;
+const GRADIENT_SPEED: f64 = 2.0; // degrees per second gradients turn by default
+const BACKGROUND_CROSSFADE_MS: i32 = 500;
 const ZOOM_FACTOR: f32 = 2.0;
 
 fn spring_animation(
@@ -196,23 +200,13 @@ fn calculate_v(r: f64, g: f64, b: f64) -> f64 {
     0.5 * r - 0.41869 * g - 0.08131 * b
 }
 
-fn precalculate_gradient(
-    width: usize,
-    start_color: (f64, f64, f64),
-    end_color: (f64, f64, f64),
-) -> Vec<(f64, f64, f64)> {
-    (0..width)
-        .map(|x| {
-            let gradient_position = x as f64 / (width - 1) as f64;
-            let color_r = start_color.0 as f64
-                + gradient_position * (end_color.0 as f64 - start_color.0 as f64);
-            let color_g = start_color.1 as f64
-                + gradient_position * (end_color.1 as f64 - start_color.1 as f64);
-            let color_b = start_color.2 as f64
-                + gradient_position * (end_color.2 as f64 - start_color.2 as f64);
-            (color_r, color_g, color_b)
-        })
-        .collect()
+// y, u, v of a color as stored in yuv420p planes
+fn yuv_color(color: &RgbField) -> [f64; 3] {
+    [
+        calculate_y(color.r, color.g, color.b),
+        calculate_u(color.r, color.g, color.b) + 128.0,
+        calculate_v(color.r, color.g, color.b) + 128.0,
+    ]
 }
 
 // fn rgb_to_yuv(r: u8, g: u8, b: u8) -> (u8, u8, u8) {
@@ -222,24 +216,585 @@ fn precalculate_gradient(
 //     (y, u, v)
 // }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 struct ZoomInfo {
     start: i32,
     end: i32,
     zoom: f64,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 struct RgbField {
     r: f64,
     g: f64,
     b: f64,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+struct GradientStop {
+    position: f64, // 0 to 1 along the gradient
+    color: RgbField,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+enum ImageFit {
+    #[default]
+    Cover,
+    Contain, // letterboxed in black
+}
+
+fn default_gradient_angle() -> f64 {
+    90.0
+}
+
+fn default_gradient_center() -> f64 {
+    0.5
+}
+
+fn default_background_blur() -> u32 {
+    24
+}
+
+fn default_background_zoom() -> f64 {
+    1.2
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Background {
+    Solid {
+        color: RgbField,
+    },
+    LinearGradient {
+        stops: Vec<GradientStop>,
+        // degrees like css, 0 runs bottom to top and 90 left to right
+        #[serde(default = "default_gradient_angle")]
+        angle: f64,
+    },
+    RadialGradient {
+        stops: Vec<GradientStop>,
+        // fractions of the frame size, the gradient ends at the farthest corner
+        #[serde(default = "default_gradient_center")]
+        center_x: f64,
+        #[serde(default = "default_gradient_center")]
+        center_y: f64,
+    },
+    Image {
+        path: String, // relative to config.json like the other inputs
+        #[serde(default)]
+        fit: ImageFit,
+    },
+    // the recording itself, enlarged by zoom and blurred by downscaling it blur times
+    BlurredRecording {
+        #[serde(default = "default_background_blur")]
+        blur: u32,
+        #[serde(default = "default_background_zoom")]
+        zoom: f64,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+enum BackgroundKind {
+    Background(Background),
+    // two stop left to right gradient, configs from before backgrounds had a kind
+    Legacy { start: RgbField, end: RgbField },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+struct BackgroundAnimation {
+    // degrees per second, linear gradients turn and radial gradients circle their center
+    shift: f64,
+    // degrees per second the hue turns, works on every kind of background
+    hue_rotation: f64,
+}
+
+impl Default for BackgroundAnimation {
+    fn default() -> Self {
+        Self {
+            shift: GRADIENT_SPEED,
+            hue_rotation: 0.0,
+        }
+    }
+}
+
+// shown from start_time to end_time (ms, open ended when missing). Where a background
+// takes over from another the two crossfade
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 struct BackgroundInfo {
-    start: RgbField,
-    end: RgbField,
+    #[serde(flatten)]
+    kind: BackgroundKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    start_time: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    end_time: Option<i32>,
+    #[serde(default)]
+    animation: BackgroundAnimation,
+}
+
+impl BackgroundInfo {
+    fn background(&self) -> Background {
+        match &self.kind {
+            BackgroundKind::Background(background) => background.clone(),
+            BackgroundKind::Legacy { start, end } => Background::LinearGradient {
+                stops: vec![
+                    GradientStop {
+                        position: 0.0,
+                        color: start.clone(),
+                    },
+                    GradientStop {
+                        position: 1.0,
+                        color: end.clone(),
+                    },
+                ],
+                angle: default_gradient_angle(),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+enum WebcamCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+enum WebcamShape {
+    Rectangle,
+    #[default]
+    Rounded,
+    Circle,
+}
+
+// what the overlay does while the video is zoomed in
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+enum WebcamZoomBehavior {
+    Stay,
+    #[default]
+    Shrink,
+    Move, // to the other side of the frame when the cursor gets close
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+struct WebcamInfo {
+    input_file: String,
+    corner: WebcamCorner,
+    size: f64,   // overlay width as a fraction of the output width
+    margin: u32, // px between the overlay and the frame edges
+    shape: WebcamShape,
+    corner_radius: u32, // px, for the rounded shape
+    border_width: u32,
+    border_color: RgbField,
+    shadow: u32, // px the drop shadow spreads out, 0 for none
+    zoom_behavior: WebcamZoomBehavior,
+}
+
+impl Default for WebcamInfo {
+    fn default() -> Self {
+        WebcamInfo {
+            input_file: "webcam.mp4".to_string(),
+            corner: WebcamCorner::default(),
+            size: 0.25,
+            margin: 32,
+            shape: WebcamShape::default(),
+            corner_radius: 24,
+            border_width: 4,
+            border_color: RgbField {
+                r: 255.0,
+                g: 255.0,
+                b: 255.0,
+            },
+            shadow: 24,
+            zoom_behavior: WebcamZoomBehavior::default(),
+        }
+    }
+}
+
+// a padding side, either a percentage of the output frame (of its width for left and
+// right, of its height for top and bottom) or output pixels
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "unit", content = "value", rename_all = "snake_case")]
+enum PaddingValue {
+    Percent(f64),
+    Px(f64),
+}
+
+impl PaddingValue {
+    fn to_px(self, frame_size: u32) -> f64 {
+        match self {
+            PaddingValue::Percent(percent) => frame_size as f64 * percent / 100.0,
+            PaddingValue::Px(px) => px,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+struct Padding {
+    top: PaddingValue,
+    right: PaddingValue,
+    bottom: PaddingValue,
+    left: PaddingValue,
+}
+
+impl Default for Padding {
+    fn default() -> Self {
+        let side = PaddingValue::Percent(10.0);
+
+        Padding {
+            top: side,
+            right: side,
+            bottom: side,
+            left: side,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+struct FrameShadow {
+    offset_x: f64,
+    offset_y: f64,
+    blur: f64,
+    color: RgbField,
+    opacity: f64,
+}
+
+impl Default for FrameShadow {
+    fn default() -> Self {
+        FrameShadow {
+            offset_x: 0.0,
+            offset_y: 12.0,
+            blur: 40.0,
+            color: RgbField {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            },
+            opacity: 0.5,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+struct FrameBorder {
+    width: f64,
+    color: RgbField,
+}
+
+// size of the exported video. Presets are 1080p in the given aspect ratio, the recording is
+// fitted inside and the background fills the rest
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "preset", rename_all = "snake_case")]
+enum Canvas {
+    #[default]
+    Source, // same size as the capture
+    Landscape, // 16:9, 1920x1080
+    Vertical,  // 9:16, 1080x1920
+    Square,    // 1:1, 1080x1080
+    Portrait,  // 4:5, 1080x1350
+    Custom {
+        width: u32,
+        height: u32,
+    },
+}
+
+impl Canvas {
+    fn size(&self, source_width: u32, source_height: u32) -> (u32, u32) {
+        let (width, height) = match self {
+            Canvas::Source => (source_width, source_height),
+            Canvas::Landscape => (1920, 1080),
+            Canvas::Vertical => (1080, 1920),
+            Canvas::Square => (1080, 1080),
+            Canvas::Portrait => (1080, 1350),
+            Canvas::Custom { width, height } => (*width, *height),
+        };
+
+        // yuv420p needs even sizes
+        (make_even(width.max(2)), make_even(height.max(2)))
+    }
+}
+
+// how the recording sits on the background, sizes are in output pixels. The defaults
+// are the plain inset at 80% the editor always had
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+struct FrameStyle {
+    padding: Padding,
+    corner_radius: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shadow: Option<FrameShadow>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    border: Option<FrameBorder>,
+}
+
+fn default_overlay_color() -> RgbField {
+    RgbField {
+        r: 255.0,
+        g: 255.0,
+        b: 255.0,
+    }
+}
+
+fn default_highlight_color() -> RgbField {
+    RgbField {
+        r: 255.0,
+        g: 230.0,
+        b: 0.0,
+    }
+}
+
+fn default_text_size() -> f64 {
+    48.0
+}
+
+fn default_text_background_opacity() -> f64 {
+    0.7
+}
+
+fn default_line_width() -> f64 {
+    6.0
+}
+
+fn default_highlight_opacity() -> f64 {
+    0.35
+}
+
+// positions and sizes are fractions of the output frame, so overlays stay put whatever
+// the canvas. Sizes of text and lines are output pixels
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum OverlayShape {
+    // centered on x, y. The default font is the bundled Inter
+    Text {
+        text: String,
+        #[serde(default)]
+        bold: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        font_file: Option<String>,
+        #[serde(default = "default_text_size")]
+        size: f64,
+        #[serde(default = "default_overlay_color")]
+        color: RgbField,
+        // pill behind the text, none for bare text
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        background: Option<RgbField>,
+        #[serde(default = "default_text_background_opacity")]
+        background_opacity: f64,
+        x: f64,
+        y: f64,
+    },
+    Arrow {
+        from_x: f64,
+        from_y: f64,
+        to_x: f64,
+        to_y: f64,
+        #[serde(default = "default_overlay_color")]
+        color: RgbField,
+        #[serde(default = "default_line_width")]
+        width: f64,
+    },
+    // outline, x, y is the top left corner
+    Rectangle {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        #[serde(default = "default_overlay_color")]
+        color: RgbField,
+        #[serde(default = "default_line_width")]
+        line_width: f64,
+    },
+    // translucent fill, x, y is the top left corner
+    Highlight {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        #[serde(default = "default_highlight_color")]
+        color: RgbField,
+        #[serde(default = "default_highlight_opacity")]
+        opacity: f64,
+    },
+}
+
+// drawn over the finished frame from start to end (ms), fading over fade_in and fade_out
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+struct Overlay {
+    start: i32,
+    end: i32,
+    #[serde(default)]
+    fade_in: i32,
+    #[serde(default)]
+    fade_out: i32,
+    #[serde(flatten)]
+    shape: OverlayShape,
+}
+
+fn default_redaction_strength() -> u32 {
+    16
+}
+
+fn default_redaction_color() -> RgbField {
+    RgbField {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+enum RedactionStyle {
+    #[default]
+    Blur,
+    Pixelate,
+    Fill,
+}
+
+// what x, y, width, height of a redaction are measured in
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+enum RegionSpace {
+    #[default]
+    Source, // pixels of the capture
+    // desktop coordinates like mousePositions.json, so the region stays on the same spot of
+    // the screen while the captured window moves around (see sourceData.json bounds)
+    Desktop,
+}
+
+// hides part of the recording from start_time to end_time (ms, open ended when missing).
+// Applied to the captured frame before anything is scaled, so it holds when zoomed in
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+struct Redaction {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    start_time: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    end_time: Option<i32>,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    #[serde(default)]
+    space: RegionSpace,
+    #[serde(default)]
+    style: RedactionStyle,
+    // how far blur and pixelate shrink the region, in pixels per block
+    #[serde(default = "default_redaction_strength")]
+    strength: u32,
+    #[serde(default = "default_redaction_color")]
+    color: RgbField, // for fill
+}
+
+fn default_card_duration() -> i32 {
+    3000
+}
+
+// shown before or after the recording
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Bumper {
+    // text over the export's background for duration ms
+    TitleCard {
+        title: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        subtitle: Option<String>,
+        #[serde(default = "default_card_duration")]
+        duration: i32,
+    },
+    // an existing video, like a company bumper. Fitted to the canvas, its audio is dropped
+    Clip {
+        path: String,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+struct Bumpers {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    intro: Option<Bumper>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outro: Option<Bumper>,
+    crossfade: i32, // ms, 0 cuts straight over
+}
+
+impl Default for Bumpers {
+    fn default() -> Self {
+        Bumpers {
+            intro: None,
+            outro: None,
+            crossfade: 500,
+        }
+    }
+}
+
+// which key presses are kept. Applied while recording, so filtered keys never reach
+// keyEvents.json, and again when rendering
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+struct KeystrokeFilter {
+    // leave out plain typing, only combos with ctrl, alt or meta and keys like Enter or
+    // F5 are kept. Keeps typed passwords out of the recording
+    only_shortcuts: bool,
+    // keys or combos never kept, like "Ctrl+V" or "Enter"
+    ignore: Vec<String>,
+}
+
+impl Default for KeystrokeFilter {
+    fn default() -> Self {
+        KeystrokeFilter {
+            only_shortcuts: true,
+            ignore: Vec::new(),
+        }
+    }
+}
+
+// keys pressed while recording, drawn as a HUD. Keys hold the names recorded in
+// keyEvents.json, like "LControl" or "P"
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+struct KeystrokeInfo {
+    input_file: String,
+    x: f64, // center of the HUD as a fraction of the output frame
+    y: f64,
+    size: f64, // text size in output pixels
+    color: RgbField,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    background: Option<RgbField>,
+    background_opacity: f64,
+    timeout: i32, // ms the HUD stays after the last key before fading out
+    #[serde(flatten)]
+    filter: KeystrokeFilter,
+}
+
+impl Default for KeystrokeInfo {
+    fn default() -> Self {
+        KeystrokeInfo {
+            input_file: "keyEvents.json".to_string(),
+            x: 0.5,
+            y: 0.9,
+            size: 36.0,
+            color: default_overlay_color(),
+            background: Some(RgbField {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            }),
+            background_opacity: default_text_background_opacity(),
+            timeout: 1500,
+            filter: KeystrokeFilter::default(),
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -251,6 +806,22 @@ struct Config {
     output_file: String,
     zoom_info: Vec<ZoomInfo>,
     background_info: Vec<BackgroundInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    webcam: Option<WebcamInfo>,
+    #[serde(default)]
+    frame: FrameStyle,
+    #[serde(default)]
+    canvas: Canvas,
+    #[serde(default)]
+    reframe: Reframe,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    overlays: Vec<Overlay>,
+    #[serde(default)]
+    bumpers: Bumpers,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    redactions: Vec<Redaction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keystrokes: Option<KeystrokeInfo>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -260,6 +831,15 @@ struct MouseEvents {
     timestamp: i32,
 }
 
+#[derive(Deserialize, Serialize, Debug)]
+struct SourceBounds {
+    timestamp: i32,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 struct SourceFile {
     x: i32,
@@ -267,1854 +847,6108 @@ struct SourceFile {
     width: i32,
     height: i32,
     scale_factor: f64,
+    // where the window was over time, sampled by the mouse tracker when it moves or resizes
+    #[serde(default)]
+    bounds: Vec<SourceBounds>,
 }
 
-#[derive(Deserialize, Serialize, Clone)]
-struct FramePixel {
-    color_y: u8,
-    color_u: u8,
-    color_v: u8,
-    x: usize,
-    y: usize,
+impl SourceFile {
+    // mouse positions are recorded in the same desktop space as the source bounds (physical
+    // pixels on windows / x11, logical on wayland), so going through the source's own size
+    // rather than a single dpi keeps mixed-DPI monitors and scaled captures lined up
+    fn mouse_to_frame(
+        &self,
+        mouse_x: f64,
+        mouse_y: f64,
+        timestamp: i32,
+        frame_width: u32,
+        frame_height: u32,
+    ) -> (f64, f64) {
+        // capture scales a resized window back to the frame size it started with, so the
+        // window's size at the time matters as much as where it was
+        let (origin_x, origin_y, width, height) = self
+            .bounds
+            .iter()
+            .take_while(|bounds| bounds.timestamp <= timestamp)
+            .last()
+            .map(|bounds| (bounds.x, bounds.y, bounds.width, bounds.height))
+            .unwrap_or((self.x, self.y, self.width, self.height));
+
+        let (ratio_x, ratio_y) = if width > 0 && height > 0 {
+            (
+                frame_width as f64 / width as f64,
+                frame_height as f64 / height as f64,
+            )
+        } else {
+            // bounds unknown (portal didn't report them), trust the detected dpi
+            (self.scale_factor, self.scale_factor)
+        };
+
+        (
+            (mouse_x - origin_x as f64) * ratio_x,
+            (mouse_y - origin_y as f64) * ratio_y,
+        )
+    }
 }
 
-fn do_transform_video(configPath: String) -> Result<String, String> {
-    // let start1 = Instant::now();
+fn resolve_config_path(config_dir: &Path, path: &str) -> String {
+    let path = Path::new(path);
 
-    // for debugging purposes
-    match env::current_dir() {
-        Ok(path) => println!("Current directory is: {:?}", path),
-        Err(e) => println!("Failed to get current directory: {}", e),
+    if !path.is_absolute() {
+        return config_dir.join(path).to_string_lossy().to_string();
     }
 
-    println!("Loading configuration... {}", configPath);
+    // older configs baked in absolute paths, if the project has since moved
+    // fall back to the file of the same name next to the config
+    if !path.exists() {
+        if let Some(file_name) = path.file_name() {
+            let relocated = config_dir.join(file_name);
+            if relocated.exists() {
+                return relocated.to_string_lossy().to_string();
+            }
+        }
+    }
 
-    // Load and parse the JSON configuration
-    let config: Config = match fs::read_to_string(&configPath) {
-        Ok(json_str) => serde_json::from_str(&json_str)
-            .map_err(|e| e.to_string())
-            .expect("Couldn't transform json string"),
-        Err(e) => return Err(format!("Failed to read config file: {}", e)),
-    };
+    path.to_string_lossy().to_string()
+}
 
-    println!("Configuration loaded successfully.");
-    println!("Duration: {}", config.duration);
-    println!("Positions file: {}", config.positions_file);
-    println!("Source file: {}", config.source_file);
-    println!("Input file: {}", config.input_file);
-    println!("Output file: {}", config.output_file);
+// every file path a config holds. do_transform_video resolves these and bundles relocate
+// them, so a new path field only needs adding here
+fn config_paths_mut(config: &mut Config) -> Vec<&mut String> {
+    let mut paths = vec![
+        &mut config.positions_file,
+        &mut config.source_file,
+        &mut config.input_file,
+        &mut config.output_file,
+    ];
+
+    if let Some(webcam) = config.webcam.as_mut() {
+        paths.push(&mut webcam.input_file);
+    }
 
-    println!("Opening Mouse Events...");
+    if let Some(keystrokes) = config.keystrokes.as_mut() {
+        paths.push(&mut keystrokes.input_file);
+    }
 
-    let mouse_events: Vec<MouseEvents> = match File::open(&config.positions_file) {
-        Ok(file) => {
-            let reader = BufReader::new(file);
-            serde_json::from_reader(reader)
-                .map_err(|e| format!("Failed to parse mouse events JSON: {}", e))?
+    for background in config.background_info.iter_mut() {
+        if let BackgroundKind::Background(Background::Image { path, .. }) = &mut background.kind {
+            paths.push(path);
         }
-        Err(e) => return Err(format!("Could not open mouse events file: {}", e)),
-    };
-
-    println!("Mouse events loaded successfully.");
-
-    println!("Opening Window Data...");
+    }
 
-    let window_data: SourceFile = match File::open(&config.source_file) {
-        Ok(file) => {
-            let reader = BufReader::new(file);
-            serde_json::from_reader(reader)
-                .map_err(|e| format!("Failed to parse window data JSON: {}", e))
-                .expect("Couldn't transform json data")
+    for bumper in [&mut config.bumpers.intro, &mut config.bumpers.outro] {
+        if let Some(Bumper::Clip { path }) = bumper {
+            paths.push(path);
         }
-        Err(e) => return Err(format!("Could not open window data file: {}", e)),
-    };
+    }
 
-    println!("Window data loaded successfully.");
+    for overlay in config.overlays.iter_mut() {
+        if let OverlayShape::Text {
+            font_file: Some(font_file),
+            ..
+        } = &mut overlay.shape
+        {
+            paths.push(font_file);
+        }
+    }
 
-    println!("Initializing FFmpeg...");
+    paths
+}
 
-    // Initialize FFmpeg
-    ffmpeg::init().map_err(|e| format!("Failed to initialize FFmpeg: {}", e))?;
+// *** Backgrounds *** //
 
-    // *** decode video ***
-    let input_filename = config.input_file;
-    let output_filename = config.output_file;
-    let fps_int = 60;
+// color at t (0 to 1) along gradient stops, held flat past the first and last stop
+fn sample_gradient(stops: &[GradientStop], t: f64) -> (f64, f64, f64) {
+    let color = |stop: &GradientStop| (stop.color.r, stop.color.g, stop.color.b);
 
-    println!("Opening input file...");
+    let Some(first) = stops.first() else {
+        return (0.0, 0.0, 0.0);
+    };
 
-    let mut input_context = ffmpeg::format::input(&input_filename)
-        .map_err(|e| format!("Could not open file: {}", e))?;
+    if t <= first.position {
+        return color(first);
+    }
 
-    println!("Finding Video Info...");
+    for pair in stops.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
 
-    // input_context.dump();
+        if t <= to.position {
+            let span = (to.position - from.position).max(f64::EPSILON);
+            let amount = (t - from.position) / span;
+            let (from, to) = (color(from), color(to));
 
-    println!("Finding Video Stream...");
+            return (
+                from.0 + (to.0 - from.0) * amount,
+                from.1 + (to.1 - from.1) * amount,
+                from.2 + (to.2 - from.2) * amount,
+            );
+        }
+    }
 
-    let video_stream = input_context
-        .streams()
-        .best(ffmpeg::media::Type::Video)
-        .ok_or("No video stream found")?;
+    color(stops.last().unwrap())
+}
 
-    let video_stream_index = video_stream.index();
+// a yuv420p frame filled with black
+fn blank_frame(width: u32, height: u32) -> Video {
+    let mut frame = Video::new(Pixel::YUV420P, width, height);
 
-    println!(
-        "Video Stream found. Num Streams: {}, Num Frames: {}",
-        input_context.streams().count(),
-        video_stream.frames()
-    );
+    frame.data_mut(0).fill(0);
+    frame.data_mut(1).fill(128);
+    frame.data_mut(2).fill(128);
 
-    println!("Setting up decoder...");
+    frame
+}
 
-    let mut decoder = ffmpeg::codec::context::Context::from_parameters(video_stream.parameters())
-        .map_err(|e| format!("Failed to create decoder context: {}", e))?
-        .decoder()
-        .video()
-        .map_err(|e| format!("Failed to create video decoder: {}", e))?;
+// draws color(x, y) for every pixel of a width x height frame, converted to yuv420p
+fn paint_frame<F>(width: u32, height: u32, color: F) -> Result<Video, String>
+where
+    F: Fn(f64, f64) -> (f64, f64, f64) + Sync,
+{
+    let mut rgb_frame = Video::new(Pixel::RGB24, width, height);
+    let stride = rgb_frame.stride(0);
+
+    rgb_frame
+        .data_mut(0)
+        .par_chunks_mut(stride)
+        .take(height as usize)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for x in 0..width as usize {
+                let (r, g, b) = color(x as f64 + 0.5, y as f64 + 0.5);
+                row[x * 3] = r.clamp(0.0, 255.0) as u8;
+                row[x * 3 + 1] = g.clamp(0.0, 255.0) as u8;
+                row[x * 3 + 2] = b.clamp(0.0, 255.0) as u8;
+            }
+        });
+
+    let mut frame = Video::empty();
+    Context::get(
+        Pixel::RGB24,
+        width,
+        height,
+        Pixel::YUV420P,
+        width,
+        height,
+        Flags::BILINEAR,
+    )
+    .and_then(|mut context| context.run(&rgb_frame, &mut frame))
+    .map_err(|e| format!("Failed to convert background: {}", e))?;
 
-    // note: open() should be called automatically with from_paramaters()
-    // decoder
-    //     .open()
-    //     .map_err(|e| format!("Failed to open decoder: {}", e))?;
+    Ok(frame)
+}
 
-    println!(
-        "Found Decoder: {}",
-        decoder
-            .codec()
-            .map(|c| c.name().to_string())
-            .unwrap_or_else(|| "Unknown".to_string())
-    );
-    println!("Decoder Pixel Format: {:?}", decoder.format());
+// scales source to cover or fit inside width x height, times zoom, centered on a black
+// yuv420p frame. Anything hanging over the edges is cropped
+fn fit_frame(
+    source: &Video,
+    width: u32,
+    height: u32,
+    fit: ImageFit,
+    zoom: f64,
+) -> Result<Video, String> {
+    let scale_x = width as f64 / source.width() as f64;
+    let scale_y = height as f64 / source.height() as f64;
+    let scale = match fit {
+        ImageFit::Cover => scale_x.max(scale_y),
+        ImageFit::Contain => scale_x.min(scale_y),
+    } * zoom;
+
+    let scaled_width = make_even(((source.width() as f64 * scale).round() as u32).max(2));
+    let scaled_height = make_even(((source.height() as f64 * scale).round() as u32).max(2));
+
+    let mut scaled = Video::empty();
+    Context::get(
+        source.format(),
+        source.width(),
+        source.height(),
+        Pixel::YUV420P,
+        scaled_width,
+        scaled_height,
+        Flags::BICUBIC,
+    )
+    .and_then(|mut context| context.run(source, &mut scaled))
+    .map_err(|e| format!("Failed to scale background: {}", e))?;
 
-    // *** prep encoding ***
-    println!("Setting up encoder...");
+    let mut frame = blank_frame(width, height);
 
-    let mut output_context = ffmpeg::format::output(&output_filename)
-        .map_err(|e| format!("Could not create output context: {}", e))?;
+    // even offsets keep the chroma planes lined up
+    let offset_x = make_even(width.abs_diff(scaled_width) / 2) as usize;
+    let offset_y = make_even(height.abs_diff(scaled_height) / 2) as usize;
+    let (src_x, dst_x) = if scaled_width > width {
+        (offset_x, 0)
+    } else {
+        (0, offset_x)
+    };
+    let (src_y, dst_y) = if scaled_height > height {
+        (offset_y, 0)
+    } else {
+        (0, offset_y)
+    };
+    let copy_width = scaled_width.min(width) as usize;
+    let copy_height = scaled_height.min(height) as usize;
+
+    for plane in 0..3 {
+        let shift = if plane == 0 { 0 } else { 1 };
+        let src_stride = scaled.stride(plane);
+        let dst_stride = frame.stride(plane);
+        let src = scaled.data(plane);
+        let dst = frame.data_mut(plane);
+        let row_width = copy_width >> shift;
+
+        for row in 0..copy_height >> shift {
+            let src_start = ((src_y >> shift) + row) * src_stride + (src_x >> shift);
+            let dst_start = ((dst_y >> shift) + row) * dst_stride + (dst_x >> shift);
+
+            dst[dst_start..dst_start + row_width]
+                .copy_from_slice(&src[src_start..src_start + row_width]);
+        }
+    }
 
-    let global_header = output_context
-        .format()
-        .flags()
-        .contains(ffmpeg::format::Flags::GLOBAL_HEADER);
+    Ok(frame)
+}
 
-    // let codec = ffmpeg::encoder::find_by_name("libx264").ok_or("Could not find libx264 encoder")?;
-    let codec =
-        ffmpeg::encoder::find(ffmpeg::codec::Id::H264).ok_or("Could not find libx264 encoder")?;
+// first frame of an image (or video) file
+fn load_image(path: &str) -> Result<Video, String> {
+    let mut input = ffmpeg::format::input(&path)
+        .map_err(|e| format!("Could not open background image {}: {}", path, e))?;
 
-    let mut output_stream = output_context
-        .add_stream(codec)
-        .map_err(|e| format!("Failed to add output stream: {}", e))?;
+    let (stream_index, mut decoder) = {
+        let stream = input
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or("Background image has no picture")?;
 
-    // let mut encoder = ffmpeg::codec::context::Context::new()
-    //     .encoder()
-    //     .video()
-    //     .map_err(|e| format!("Failed to create video encoder: {}", e))?;
+        let decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())
+            .and_then(|context| context.decoder().video())
+            .map_err(|e| format!("Failed to create image decoder: {}", e))?;
 
-    let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
-        .encoder()
-        .video()
-        .map_err(|e| format!("Failed to create video encoder: {}", e))?;
+        (stream.index(), decoder)
+    };
 
-    // output_stream.set_parameters(encoder.parameters());
+    let mut frame = Video::empty();
 
-    // encoder.set_codec(&codec);
+    for (stream, packet) in input.packets() {
+        if stream.index() == stream_index {
+            let _ = decoder.send_packet(&packet);
 
-    println!("Setting up codec context...");
-    println!("Bit Rate: {}", decoder.bit_rate());
-    println!("Codec name: {}", codec.name());
+            if decoder.receive_frame(&mut frame).is_ok() {
+                return Ok(frame);
+            }
+        }
+    }
 
-    let fps_int = 60; // Assuming 60 FPS, adjust as needed
+    let _ = decoder.send_eof();
+    decoder
+        .receive_frame(&mut frame)
+        .map(|_| frame)
+        .map_err(|e| format!("Could not decode background image {}: {}", path, e))
+}
 
-    encoder.set_bit_rate(decoder.bit_rate());
-    encoder.set_width(decoder.width());
-    encoder.set_height(decoder.height());
-    // encoder.set_time_base((1, fps_int));
-    encoder.set_time_base(ffmpeg::Rational(1, fps_int));
-    encoder.set_frame_rate(Some(ffmpeg::Rational(fps_int, 1)));
-    encoder.set_gop(10);
-    encoder.set_max_b_frames(1);
-    encoder.set_format(ffmpeg::util::format::Pixel::YUV420P);
-    // encoder.set_quality(50); // "-qscale is ignored, -crf is recommended."
-    // encoder.set_compression(Some(23));
+// turns the hue of a yuv420p frame, a rotation of the chroma planes around grey
+fn rotate_hue(frame: &mut Video, degrees: f64) {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let u_plane = frame.data(1).to_vec();
+    let v_plane = frame.data(2).to_vec();
+
+    let rotate = |u: u8, v: u8| {
+        let (u, v) = (u as f64 - 128.0, v as f64 - 128.0);
+        (
+            (u * cos - v * sin + 128.0).clamp(0.0, 255.0) as u8,
+            (u * sin + v * cos + 128.0).clamp(0.0, 255.0) as u8,
+        )
+    };
 
-    println!("Continuing 1");
+    for (i, value) in frame.data_mut(1).iter_mut().enumerate() {
+        *value = rotate(u_plane[i], v_plane[i]).0;
+    }
+    for (i, value) in frame.data_mut(2).iter_mut().enumerate() {
+        *value = rotate(u_plane[i], v_plane[i]).1;
+    }
+}
 
-    // Create a Dictionary to hold the encoder parameters
-    let mut parameters = Dictionary::new();
-    parameters.set("preset", "faster");
-    // parameters.set("x264-params", "level=4.0");
-    // parameters.set("tune", "zerolatency"); // "good for streaming scenarios"??
-    parameters.set("crf", "27");
+// mixes from into frame, amount 0 keeps all of from and 1 all of frame
+fn crossfade_frames(frame: &mut Video, from: &Video, amount: f64) {
+    for plane in 0..3 {
+        frame
+            .data_mut(plane)
+            .par_iter_mut()
+            .zip(from.data(plane).par_iter())
+            .for_each(|(to, from)| {
+                *to = (*from as f64 + (*to as f64 - *from as f64) * amount).round() as u8;
+            });
+    }
+}
 
-    println!("Continuing 2");
+// draws backgrounds at the size of the output frame. Backgrounds that look the same on
+// every frame are drawn once and copied
+struct BackgroundRenderer {
+    background: Background,
+    animation: BackgroundAnimation,
+    width: u32,
+    height: u32,
+    cached: Option<Video>,
+}
 
-    let mut encoder = encoder
-        .open_with(parameters)
-        .expect("Couldn't open encoder");
+impl BackgroundRenderer {
+    fn new(
+        background: Background,
+        animation: BackgroundAnimation,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, String> {
+        let mut renderer = Self {
+            background,
+            animation,
+            width,
+            height,
+            cached: None,
+        };
 
-    println!("Continuing 3");
+        let is_static = !renderer.is_moving()
+            && !matches!(renderer.background, Background::BlurredRecording { .. });
 
-    output_stream.set_time_base((1, fps_int));
+        if is_static {
+            renderer.cached = Some(renderer.draw(None, 0)?);
+        }
 
-    // Copy encoder parameters to output stream
-    // output_stream.set_parameters(encoder.parameters());
-    output_stream.set_parameters(video_stream.parameters());
+        Ok(renderer)
+    }
 
-    // Open output file
-    println!("Opening output file...");
-    output_context
-        .write_header()
-        .map_err(|e| format!("Error occurred when opening output file: {}", e))?;
+    // a gradient that shifts over time. Hue rotation is applied on top, so a turning hue
+    // alone still draws from the cache
+    fn is_moving(&self) -> bool {
+        match self.background {
+            Background::LinearGradient { .. } | Background::RadialGradient { .. } => {
+                self.animation.shift != 0.0
+            }
+            _ => false,
+        }
+    }
 
-    let mut y = 0;
-    let mut zoom = 1.0;
+    fn render(&self, recording: &Video, time_ms: i32) -> Result<Video, String> {
+        let mut frame = match &self.cached {
+            Some(frame) => frame.clone(),
+            None => self.draw(Some(recording), time_ms)?,
+        };
 
-    println!("Starting read frames...");
+        if self.animation.hue_rotation != 0.0 {
+            rotate_hue(
+                &mut frame,
+                self.animation.hue_rotation * time_ms as f64 / 1000.0,
+            );
+        }
 
-    let mut current_multiplier = 1.0;
-    let mut velocity = 0.0;
+        Ok(frame)
+    }
 
-    let total_frames = (config.duration / 1000) * fps_int;
-    let mut frame_index = 0;
-    let mut successful_frame_index = 0;
+    fn draw(&self, recording: Option<&Video>, time_ms: i32) -> Result<Video, String> {
+        let (width, height) = (self.width, self.height);
+        let shift = self.animation.shift * time_ms as f64 / 1000.0;
 
-    let start_time = Instant::now();
+        // gradients redrawn every frame are painted at a quarter size and scaled up,
+        // they are smooth enough that it doesn't show
+        let (paint_width, paint_height) = if self.is_moving() {
+            (
+                make_even((width / 4).max(2)),
+                make_even((height / 4).max(2)),
+            )
+        } else {
+            (width, height)
+        };
+        let (w, h) = (paint_width as f64, paint_height as f64);
+        let paint_gradient =
+            |color: &(dyn Fn(f64, f64) -> (f64, f64, f64) + Sync)| -> Result<Video, String> {
+                let frame = paint_frame(paint_width, paint_height, color)?;
+
+                if paint_width == width && paint_height == height {
+                    Ok(frame)
+                } else {
+                    fit_frame(&frame, width, height, ImageFit::Cover, 1.0)
+                }
+            };
 
-    let mut mouse_x = 0.0;
-    let mut mouse_y = 0.0;
-    let mut current_mouse_x = decoder.width() as f64 / 2.0;
-    let mut current_mouse_y = decoder.height() as f64 / 2.0;
-    let mut velocity_mouse_x: f64 = 0.0;
-    let mut velocity_mouse_y: f64 = 0.0;
-    let mut zoom_top = 0;
-    let mut zoom_left = 0;
-    let mut zooming_in = false;
-    let mut zooming_in2 = false;
-    let mut velocity_width = 0.0;
-    let mut velocity_height = 0.0;
-    let auto_zoom = false;
+        match &self.background {
+            Background::Solid { color } => {
+                paint_frame(width, height, |_, _| (color.r, color.g, color.b))
+            }
+            Background::LinearGradient { stops, angle } => {
+                // css gradient line: through the center, long enough to reach the corners
+                let radians = (angle + shift).to_radians();
+                let (dx, dy) = (radians.sin(), -radians.cos());
+                let length = (w * dx).abs() + (h * dy).abs();
+
+                paint_gradient(&|x, y| {
+                    let t = 0.5 + ((x - w / 2.0) * dx + (y - h / 2.0) * dy) / length;
+                    sample_gradient(stops, t)
+                })
+            }
+            Background::RadialGradient {
+                stops,
+                center_x,
+                center_y,
+            } => {
+                // shifting walks the center around a small circle
+                let (sin, cos) = shift.to_radians().sin_cos();
+                let (cx, cy) = (center_x * w, center_y * h);
+                let (cx, cy) = if shift != 0.0 {
+                    (cx + cos * w * 0.1, cy + sin * h * 0.1)
+                } else {
+                    (cx, cy)
+                };
+                let radius = cx.max(w - cx).hypot(cy.max(h - cy));
 
-    let friction1 = 2.5;
-    let friction2 = 4.0;
-    let friction3 = 5.0;
-    let easing_factor = 1.0;
+                paint_gradient(&|x, y| sample_gradient(stops, (x - cx).hypot(y - cy) / radius))
+            }
+            Background::Image { path, fit } => {
+                fit_frame(&load_image(path)?, width, height, *fit, 1.0)
+            }
+            Background::BlurredRecording { blur, zoom } => {
+                let recording = recording.ok_or("The blurred background needs a frame")?;
+                let blur = (*blur).max(1);
+
+                // shrinking and scaling back up smooths it out about as much as a blur would
+                let mut small = Video::empty();
+                Context::get(
+                    recording.format(),
+                    recording.width(),
+                    recording.height(),
+                    Pixel::YUV420P,
+                    make_even((recording.width() / blur).max(2)),
+                    make_even((recording.height() / blur).max(2)),
+                    Flags::AREA,
+                )
+                .and_then(|mut context| context.run(recording, &mut small))
+                .map_err(|e| format!("Failed to blur background: {}", e))?;
+
+                fit_frame(&small, width, height, ImageFit::Cover, zoom.max(1.0))
+            }
+        }
+    }
+}
 
-    let mut direction_x = 0.0;
-    let mut direction_y = 0.0;
+struct BackgroundLayer {
+    start_time: i32,
+    end_time: i32,
+    renderer: BackgroundRenderer,
+}
 
-    let mut prev_zoom_top = 0.0;
-    let mut prev_zoom_left = 0.0;
-    let mut smooth_zoom_top = 0.0;
-    let mut smooth_zoom_left = 0.0;
-    let mut used_zoom_top = 0.0;
-    let mut used_zoom_left = 0.0;
+// all of config.background_info, picking the background for each frame time and
+// crossfading into it from the one it replaced
+struct BackgroundTimeline {
+    layers: Vec<BackgroundLayer>,
+}
 
-    let mut smooth_width = 0.0;
-    let mut smooth_height = 0.0;
-    let mut used_width = 0.0;
-    let mut used_height = 0.0;
+impl BackgroundTimeline {
+    fn new(background_info: &[BackgroundInfo], width: u32, height: u32) -> Result<Self, String> {
+        let mut layers = Vec::new();
+
+        for info in background_info {
+            layers.push(BackgroundLayer {
+                start_time: info.start_time.unwrap_or(i32::MIN),
+                end_time: info.end_time.unwrap_or(i32::MAX),
+                renderer: BackgroundRenderer::new(
+                    info.background(),
+                    info.animation.clone(),
+                    width,
+                    height,
+                )?,
+            });
+        }
 
-    let animation_duration = 5000;
+        if layers.is_empty() {
+            let black = RgbField {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            };
+
+            layers.push(BackgroundLayer {
+                start_time: i32::MIN,
+                end_time: i32::MAX,
+                renderer: BackgroundRenderer::new(
+                    Background::Solid { color: black },
+                    BackgroundAnimation::default(),
+                    width,
+                    height,
+                )?,
+            });
+        }
 
-    // frictional_animation causes gradual slowdown, but smoothing is supposed to improved shakiness
-    let enable_dimension_smoothing = false;
-    let enable_coord_smoothing = false;
+        Ok(Self { layers })
+    }
 
-    let mut frame_index = 0;
-    let mut successful_frame_index = 0;
+    // later entries win where ranges overlap, before the first range the first entry shows
+    fn layer_at(&self, time_ms: i32) -> usize {
+        self.layers
+            .iter()
+            .rposition(|layer| layer.start_time <= time_ms && time_ms < layer.end_time)
+            .unwrap_or(0)
+    }
 
-    let mut target_multiplier = 1.0; // Default value when no zoom effect is active
-    let mut zooming_in = false;
-    let mut zooming_out = false;
+    fn render(&self, recording: &Video, time_ms: i32) -> Result<Video, String> {
+        let current = self.layer_at(time_ms);
+        let layer = &self.layers[current];
+        let mut frame = layer.renderer.render(recording, time_ms)?;
 
-    let mut speed = f64::INFINITY; // Initialize with a high value
+        // fading in over whatever showed before the layer started
+        let fade_time = time_ms.saturating_sub(layer.start_time);
 
-    // Get a packet iterator
-    let mut packet_iter = input_context.packets();
+        if fade_time < BACKGROUND_CROSSFADE_MS {
+            let previous = self.layer_at(layer.start_time.saturating_sub(1));
 
-    // Parameters for gradient colors
-    let start_color = (
-        config.background_info[0].start.r,
-        config.background_info[0].start.g,
-        config.background_info[0].start.b,
-    );
-    let end_color = (
-        config.background_info[0].end.r,
-        config.background_info[0].end.g,
-        config.background_info[0].end.b,
-    );
+            if previous != current {
+                let from = self.layers[previous].renderer.render(recording, time_ms)?;
+                crossfade_frames(
+                    &mut frame,
+                    &from,
+                    fade_time as f64 / BACKGROUND_CROSSFADE_MS as f64,
+                );
+            }
+        }
 
-    let precalculated_gradient =
-        precalculate_gradient(decoder.width() as usize, start_color, end_color);
+        // and out to whatever shows once it ends. A layer starting right there fades itself in
+        let fade_time = layer.end_time.saturating_sub(time_ms);
 
-    // Color shift
-    let color_shift = 128.0 as f64;
+        if fade_time <= BACKGROUND_CROSSFADE_MS {
+            let next = self.layer_at(layer.end_time);
 
-    let precalculated_yuv: Vec<(u8, u8, u8)> = precalculated_gradient
-        .iter()
-        .map(|&(r, g, b)| {
-            let y = calculate_y(r, g, b) as u8;
-            let u = (calculate_u(r, g, b) + color_shift) as u8;
-            let v = (calculate_v(r, g, b) + color_shift) as u8;
-            (y, u, v)
-        })
-        .collect();
+            if next != current && self.layers[next].start_time < layer.end_time {
+                let mut to = self.layers[next].renderer.render(recording, time_ms)?;
+                crossfade_frames(
+                    &mut to,
+                    &frame,
+                    1.0 - fade_time as f64 / BACKGROUND_CROSSFADE_MS as f64,
+                );
+                frame = to;
+            }
+        }
 
-    let decoderHeight = decoder.height();
-    let decoderWidth = decoder.width();
-
-    let precalculated_data: Vec<FramePixel> = (0..decoderHeight)
-        .into_par_iter() // Parallel iterator
-        .flat_map(|y| {
-            let precalculated_gradient = precalculated_gradient.clone();
-
-            (0..decoderWidth).into_par_iter().map(move |x| {
-                let (color_r, color_g, color_b) = precalculated_gradient[x as usize];
-                let color_y = calculate_y(color_r, color_g, color_b);
-                let color_u = calculate_u(color_r, color_g, color_b) + 128.0;
-                let color_v = calculate_v(color_r, color_g, color_b) + 128.0;
-
-                // Return the calculated data
-                FramePixel {
-                    color_y: color_y as u8,
-                    color_u: color_u as u8,
-                    color_v: color_v as u8,
-                    x: x.try_into().unwrap(),
-                    y: y.try_into().unwrap(),
-                }
-            })
-        })
-        .collect(); // Collect all the results
+        Ok(frame)
+    }
+}
 
-    // let duration1 = start1.elapsed();
-    // println!(
-    //     "start1 Time elapsed: {:?}, {:?}",
-    //     duration1,
-    //     precalculated_data.len()
-    // );
+// *** Webcam Overlay *** //
+
+const WEBCAM_ZOOM_RANGE: f64 = 0.2; // zoom (1.0 - multiplier) at which the overlay fully reacts
+const WEBCAM_ZOOM_SHRINK: f64 = 0.5; // overlay scale when fully zoomed, for the shrink behavior
+const WEBCAM_SHADOW_OPACITY: f64 = 0.45;
+
+// decodes webcam.mp4 alongside the capture, both start at the same recording clock zero
+struct WebcamReader {
+    input: ffmpeg::format::context::Input,
+    stream_index: usize,
+    decoder: ffmpeg::decoder::Video,
+    time_base: f64, // seconds per pts tick
+    current: Option<Video>,
+    next: Option<Video>,
+    finished: bool,
+}
 
-    let mut smoothed_velocity_width = decoder.width() as f64;
-    let mut smoothed_velocity_height = decoder.width() as f64;
+impl WebcamReader {
+    fn open(path: &str) -> Result<Self, String> {
+        let input = ffmpeg::format::input(&path)
+            .map_err(|e| format!("Could not open webcam file: {}", e))?;
 
-    // 1. Upscale the background frame
-    let upscale_factor = 4; // Choose an appropriate factor
-    let upscaled_width = decoder.width() * upscale_factor;
-    let upscaled_height = decoder.height() * upscale_factor;
+        let (stream_index, time_base, decoder) = {
+            let stream = input
+                .streams()
+                .best(ffmpeg::media::Type::Video)
+                .ok_or("No video stream in webcam file")?;
 
-    // These should be declared outside the loop and updated each iteration
-    let mut current_width = (decoder.width() as f64) * upscale_factor as f64;
-    let mut current_height = (decoder.height() as f64) * upscale_factor as f64;
+            let decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())
+                .and_then(|context| context.decoder().video())
+                .map_err(|e| format!("Failed to create webcam decoder: {}", e))?;
 
-    // Main loop
-    'main_loop: loop {
-        match packet_iter.next() {
-            Some((stream, packet)) => {
-                if stream.index() == video_stream_index {
-                    // Process video packets
-                    // let start2 = Instant::now();
+            (stream.index(), f64::from(stream.time_base()), decoder)
+        };
 
-                    decoder
-                        .send_packet(&packet)
-                        .map_err(|e| format!("Error sending packet for decoding: {}", e))?;
+        Ok(Self {
+            input,
+            stream_index,
+            decoder,
+            time_base,
+            current: None,
+            next: None,
+            finished: false,
+        })
+    }
 
-                    'decode_loop: loop {
-                        let mut decoded_frame = ffmpeg::frame::Video::empty();
-                        match decoder.receive_frame(&mut decoded_frame) {
-                            Ok(_) => {
-                                // *** Frame transformation logic ***
+    fn decode_next(&mut self) -> Option<Video> {
+        loop {
+            let mut frame = Video::empty();
 
-                                // Create a new frame for the background
-                                let mut bg_frame = ffmpeg::frame::Video::new(
-                                    encoder.format(),
-                                    encoder.width(),
-                                    encoder.height(),
-                                );
-                                bg_frame.set_pts(Some(frame_index as i64));
+            if self.decoder.receive_frame(&mut frame).is_ok() {
+                return Some(frame);
+            }
 
-                                let precalculated_data = precalculated_data.clone();
+            if self.finished {
+                return None;
+            }
 
-                                // let start3 = Instant::now();
+            match self.input.packets().next() {
+                Some((stream, packet)) => {
+                    if stream.index() == self.stream_index {
+                        let _ = self.decoder.send_packet(&packet);
+                    }
+                }
+                None => {
+                    let _ = self.decoder.send_eof();
+                    self.finished = true;
+                }
+            }
+        }
+    }
 
-                                // Get the strides first
-                                let y_stride = bg_frame.stride(0) as usize;
-                                let uv_stride = bg_frame.stride(1) as usize;
+    // the last webcam frame at or before time_ms, held once the webcam runs out
+    fn frame_at(&mut self, time_ms: i32) -> Option<&Video> {
+        let time_base = self.time_base;
+        let frame_ms = |frame: &Video| {
+            frame.timestamp().or(frame.pts()).unwrap_or(0) as f64 * time_base * 1000.0
+        };
 
-                                // Process the frame plane by plane
-                                {
-                                    let y_plane = bg_frame.data_mut(0);
-                                    for pixel in &precalculated_data {
-                                        let y_index = pixel.y * y_stride + pixel.x;
-                                        y_plane[y_index] = pixel.color_y;
-                                    }
-                                }
+        loop {
+            if self.next.is_none() {
+                self.next = self.decode_next();
+            }
 
-                                {
-                                    let u_plane = bg_frame.data_mut(1);
-                                    for pixel in precalculated_data
-                                        .iter()
-                                        .filter(|p| p.y % 2 == 0 && p.x % 2 == 0)
-                                    {
-                                        let uv_index = (pixel.y / 2) * uv_stride + (pixel.x / 2);
-                                        u_plane[uv_index] = pixel.color_u;
-                                    }
-                                }
+            let due = match &self.next {
+                Some(next) => frame_ms(next) <= time_ms as f64,
+                None => false,
+            };
 
-                                {
-                                    let v_plane = bg_frame.data_mut(2);
-                                    for pixel in precalculated_data
-                                        .iter()
-                                        .filter(|p| p.y % 2 == 0 && p.x % 2 == 0)
-                                    {
-                                        let uv_index = (pixel.y / 2) * uv_stride + (pixel.x / 2);
-                                        v_plane[uv_index] = pixel.color_v;
-                                    }
-                                }
+            if !due {
+                break;
+            }
 
-                                // let duration3 = start3.elapsed();
-                                // println!("start3 Time elapsed: {:?}", duration3);
+            self.current = self.next.take();
+        }
 
-                                // let start10 = Instant::now();
+        self.current.as_ref()
+    }
+}
 
-                                // *** Inset Video *** //
+// left, top, width, height of the overlay in the output frame. zoom_progress runs from 0 at
+// the full frame to 1 once zoomed in by WEBCAM_ZOOM_RANGE, cursor_x is in output pixels
+fn webcam_rect(
+    info: &WebcamInfo,
+    frame_width: u32,
+    frame_height: u32,
+    webcam_aspect: f64,
+    zoom_progress: f64,
+    cursor_x: f64,
+) -> (i32, i32, u32, u32) {
+    let mut width = info.size * frame_width as f64;
+
+    if info.zoom_behavior == WebcamZoomBehavior::Shrink {
+        width *= 1.0 - (1.0 - WEBCAM_ZOOM_SHRINK) * zoom_progress;
+    }
 
-                                // Scale down the frame using libswscale
-                                let scale_multiple = 0.8;
-
-                                let scaled_width =
-                                    (decoded_frame.width() as f64 * scale_multiple) as u32;
-                                let scaled_height =
-                                    (decoded_frame.height() as f64 * scale_multiple) as u32;
-
-                                // Create a new scaling context
-                                let mut sws_context = Context::get(
-                                    decoded_frame.format(),
-                                    decoded_frame.width(),
-                                    decoded_frame.height(),
-                                    decoded_frame.format(),
-                                    scaled_width,
-                                    scaled_height,
-                                    Flags::BILINEAR,
-                                )
-                                .map_err(|e| format!("Failed to create scaling context: {}", e))?;
+    let height = match info.shape {
+        WebcamShape::Circle => width,
+        _ => width / webcam_aspect,
+    };
 
-                                let mut scaled_frame = frame::Video::new(
-                                    Pixel::from(decoded_frame.format()),
-                                    scaled_width,
-                                    scaled_height,
-                                );
+    let width = make_even((width as u32).clamp(2, frame_width));
+    let height = make_even((height as u32).clamp(2, frame_height));
 
-                                // Perform the scaling
-                                sws_context
-                                    .run(&decoded_frame, &mut scaled_frame)
-                                    .map_err(|e| format!("Failed to scale frame: {}", e))?;
+    let margin = info.margin as f64;
+    let near_x = margin;
+    let far_x = frame_width as f64 - margin - width as f64;
 
-                                let offset_x = (bg_frame.width() - scaled_frame.width()) / 2;
-                                let offset_y = (bg_frame.height() - scaled_frame.height()) / 2;
+    let (mut x, y) = match info.corner {
+        WebcamCorner::TopLeft => (near_x, margin),
+        WebcamCorner::TopRight => (far_x, margin),
+        WebcamCorner::BottomLeft => (near_x, frame_height as f64 - margin - height as f64),
+        WebcamCorner::BottomRight => (far_x, frame_height as f64 - margin - height as f64),
+    };
 
-                                let bg_stride = bg_frame.stride(0);
+    // dodge the cursor by sliding over to the other side
+    if info.zoom_behavior == WebcamZoomBehavior::Move {
+        let on_left = matches!(
+            info.corner,
+            WebcamCorner::TopLeft | WebcamCorner::BottomLeft
+        );
+        let cursor_on_left = cursor_x < frame_width as f64 / 2.0;
 
-                                // Y plane
-                                {
-                                    let bg_y = bg_frame.data_mut(0);
-                                    let scaled_y = scaled_frame.data(0);
-                                    let scaled_stride = scaled_frame.stride(0);
-
-                                    for y in 0..scaled_frame.height() {
-                                        let bg_row_start =
-                                            (y + offset_y) as usize * bg_stride + offset_x as usize;
-                                        let scaled_row_start = y as usize * scaled_stride;
-                                        let row_width = scaled_frame.width() as usize;
-
-                                        bg_y[bg_row_start..bg_row_start + row_width]
-                                            .copy_from_slice(
-                                                &scaled_y[scaled_row_start
-                                                    ..scaled_row_start + row_width],
-                                            );
-                                    }
-                                }
+        if on_left == cursor_on_left {
+            let other_x = if on_left { far_x } else { near_x };
+            x += (other_x - x) * zoom_progress;
+        }
+    }
 
-                                let bg_stride = bg_frame.stride(1);
+    (x as i32, y as i32, width, height)
+}
 
-                                // U plane
-                                {
-                                    let bg_u = bg_frame.data_mut(1);
-                                    let scaled_u = scaled_frame.data(1);
-                                    let scaled_stride = scaled_frame.stride(1);
-
-                                    for y in (0..scaled_frame.height()).step_by(2) {
-                                        let bg_row_start = ((y + offset_y) / 2) as usize
-                                            * bg_stride
-                                            + (offset_x / 2) as usize;
-                                        let scaled_row_start = (y / 2) as usize * scaled_stride;
-                                        let row_width = (scaled_frame.width() / 2) as usize;
-
-                                        bg_u[bg_row_start..bg_row_start + row_width]
-                                            .copy_from_slice(
-                                                &scaled_u[scaled_row_start
-                                                    ..scaled_row_start + row_width],
-                                            );
-                                    }
-                                }
+// signed distance from a pixel to the edge of a width x height rect with rounded corners,
+// negative inside
+fn rounded_rect_distance(x: f64, y: f64, width: f64, height: f64, radius: f64) -> f64 {
+    let qx = (x - width / 2.0).abs() - (width / 2.0 - radius);
+    let qy = (y - height / 2.0).abs() - (height / 2.0 - radius);
 
-                                let bg_stride = bg_frame.stride(2);
+    qx.max(0.0).hypot(qy.max(0.0)) + qx.max(qy).min(0.0) - radius
+}
 
-                                // V plane
-                                {
-                                    let bg_v = bg_frame.data_mut(2);
-                                    let scaled_v = scaled_frame.data(2);
-                                    let scaled_stride = scaled_frame.stride(2);
-
-                                    for y in (0..scaled_frame.height()).step_by(2) {
-                                        let bg_row_start = ((y + offset_y) / 2) as usize
-                                            * bg_stride
-                                            + (offset_x / 2) as usize;
-                                        let scaled_row_start = (y / 2) as usize * scaled_stride;
-                                        let row_width = (scaled_frame.width() / 2) as usize;
-
-                                        bg_v[bg_row_start..bg_row_start + row_width]
-                                            .copy_from_slice(
-                                                &scaled_v[scaled_row_start
-                                                    ..scaled_row_start + row_width],
-                                            );
-                                    }
-                                }
+// alpha blends the webcam into a yuv420p frame, scaled to fill width x height and masked to
+// the configured shape, with the border drawn inside the mask and the shadow below it
+fn composite_webcam(
+    frame: &mut Video,
+    webcam: &Video,
+    info: &WebcamInfo,
+    left: i32,
+    top: i32,
+    width: u32,
+    height: u32,
+) -> Result<(), String> {
+    let (w, h) = (width as f64, height as f64);
+    let radius = match info.shape {
+        WebcamShape::Rectangle => 0.0,
+        WebcamShape::Rounded => info.corner_radius as f64,
+        WebcamShape::Circle => w.min(h) / 2.0,
+    };
+    let shadow = info.shadow as f64;
+
+    let look = FrameLook {
+        radius,
+        border: (info.border_width > 0).then(|| FrameBorder {
+            width: info.border_width as f64,
+            color: info.border_color.clone(),
+        }),
+        shadow: (shadow > 0.0).then(|| FrameShadow {
+            offset_x: 0.0,
+            offset_y: shadow / 3.0,
+            blur: shadow,
+            opacity: WEBCAM_SHADOW_OPACITY,
+            ..FrameShadow::default()
+        }),
+    };
 
-                                // *** Zoom *** //
-                                let time_elapsed = frame_index * 1000 / fps_int;
+    composite_framed(frame, webcam, &look, left, top, width, height)
+}
 
-                                // let duration10 = start10.elapsed();
-                                // println!("start10 Time elapsed: {:?}", duration10);
+// *** Framing *** //
 
-                                println!("Time Elapsed: {}", time_elapsed);
+// the mask, border and shadow a picture gets when pasted onto the frame
+struct FrameLook {
+    radius: f64,
+    border: Option<FrameBorder>,
+    shadow: Option<FrameShadow>,
+}
 
-                                // let start4 = Instant::now();
+// left, top, width, height of what's left of the frame inside the padding
+fn padded_area(style: &FrameStyle, frame_width: u32, frame_height: u32) -> (f64, f64, f64, f64) {
+    let padding = &style.padding;
+    let left = padding.left.to_px(frame_width);
+    let right = padding.right.to_px(frame_width);
+    let top = padding.top.to_px(frame_height);
+    let bottom = padding.bottom.to_px(frame_height);
+
+    (
+        left,
+        top,
+        (frame_width as f64 - left - right).max(2.0),
+        (frame_height as f64 - top - bottom).max(2.0),
+    )
+}
 
-                                let mut target_width = upscaled_width as f64;
-                                let mut target_height = upscaled_width as f64;
+// where the recording goes in a frame_width x frame_height frame: inside the padding,
+// fitted to keep the source aspect and centered. Even values so chroma lines up
+fn frame_rect(
+    style: &FrameStyle,
+    frame_width: u32,
+    frame_height: u32,
+    source_width: u32,
+    source_height: u32,
+) -> (i32, i32, u32, u32) {
+    let (left, top, area_width, area_height) = padded_area(style, frame_width, frame_height);
+    let scale = (area_width / source_width as f64).min(area_height / source_height as f64);
+
+    let width = make_even(((source_width as f64 * scale) as u32).clamp(2, frame_width));
+    let height = make_even(((source_height as f64 * scale) as u32).clamp(2, frame_height));
+    let x = left + (area_width - width as f64) / 2.0;
+    let y = top + (area_height - height as f64) / 2.0;
+
+    (
+        make_even(x.max(0.0) as u32) as i32,
+        make_even(y.max(0.0) as u32) as i32,
+        width,
+        height,
+    )
+}
 
-                                // Search for the current zoom level
-                                let mut t = 1.0;
+// *** Redaction *** //
 
-                                for zoom in &config.zoom_info {
-                                    let start = zoom.start as i32;
-                                    let end = zoom.end as i32;
-                                    let zoom_factor = zoom.zoom;
+// copies a yuv420p source over a yuv420p frame at left, top (even)
+fn paste_frame(frame: &mut Video, source: &Video, left: u32, top: u32) {
+    for plane in 0..3 {
+        let shift = if plane == 0 { 0 } else { 1 };
+        let stride = frame.stride(plane);
+        let source_stride = source.stride(plane);
+        let row_width = (source.width() >> shift) as usize;
+        let source_plane = source.data(plane);
+        let frame_plane = frame.data_mut(plane);
 
-                                    // Process each zoom info...
-                                    if time_elapsed >= start && time_elapsed < end {
-                                        if !zooming_in {
-                                            velocity = 0.0;
-                                            velocity_mouse_x = 0.0;
-                                            velocity_mouse_y = 0.0;
-                                            velocity_width = 0.0;
-                                            velocity_height = 0.0;
-                                            zooming_in = true;
-                                            zooming_out = false;
-                                            println!("Zooming In");
-                                            target_multiplier = zoom_factor;
-                                        }
+        for row in 0..(source.height() >> shift) as usize {
+            let start = ((top >> shift) as usize + row) * stride + (left >> shift) as usize;
+            let source_start = row * source_stride;
 
-                                        // Calculate the interpolation factor t based on the animation progress
-                                        // t = time_elapsed as f64
-                                        //     / (start + animation_duration) as f64;
-                                    } else if time_elapsed >= end
-                                        && time_elapsed < end + animation_duration
-                                    {
-                                        if zooming_in {
-                                            velocity = 0.0;
-                                            velocity_mouse_x = 0.0;
-                                            velocity_mouse_y = 0.0;
-                                            velocity_width = 0.0;
-                                            velocity_height = 0.0;
-                                            zooming_in = false;
-                                            zooming_out = true;
-                                            println!("Zooming Out");
-                                            target_multiplier = 1.0;
-                                        }
-                                    } else if (time_elapsed >= end + animation_duration) {
-                                        if zooming_out {
-                                            zooming_out = true;
-                                        }
-                                    }
-                                }
+            frame_plane[start..start + row_width]
+                .copy_from_slice(&source_plane[source_start..source_start + row_width]);
+        }
+    }
+}
 
-                                current_multiplier = target_multiplier;
+fn scale_yuv420p(source: &Video, width: u32, height: u32, flags: Flags) -> Result<Video, String> {
+    let mut scaled = Video::empty();
+
+    Context::get(
+        source.format(),
+        source.width(),
+        source.height(),
+        Pixel::YUV420P,
+        width,
+        height,
+        flags,
+    )
+    .and_then(|mut context| context.run(source, &mut scaled))
+    .map_err(|e| format!("Failed to scale redaction: {}", e))?;
 
-                                // (ex. 1.0 is 100% while 0.8 is ~120%)
-                                // println!("currentMultiplier {}", current_multiplier);
+    Ok(scaled)
+}
 
-                                target_width = upscaled_width as f64 * current_multiplier;
-                                target_height = upscaled_height as f64 * current_multiplier;
+// the frame with every redaction active at time_ms applied, untouched when there are none
+fn apply_redactions(
+    frame: Video,
+    redactions: &[Redaction],
+    time_ms: i32,
+    source: &SourceFile,
+) -> Result<Video, String> {
+    let active: Vec<&Redaction> = redactions
+        .iter()
+        .filter(|redaction| {
+            redaction.start_time.unwrap_or(i32::MIN) <= time_ms
+                && time_ms < redaction.end_time.unwrap_or(i32::MAX)
+        })
+        .collect();
 
-                                // // These should be declared outside the loop and updated each iteration
-                                // let mut current_width = bg_frame.width() as f64;
-                                // let mut current_height = bg_frame.height() as f64;
+    if active.is_empty() {
+        return Ok(frame);
+    }
 
-                                println!("target and current {} {}", target_width, current_width);
+    let mut frame = if frame.format() == Pixel::YUV420P {
+        frame
+    } else {
+        convert_to_yuv420p(&frame)?
+    };
+    let (frame_width, frame_height) = (frame.width(), frame.height());
+
+    for redaction in active {
+        let (x1, y1, x2, y2) = match redaction.space {
+            RegionSpace::Source => (
+                redaction.x,
+                redaction.y,
+                redaction.x + redaction.width,
+                redaction.y + redaction.height,
+            ),
+            RegionSpace::Desktop => {
+                let to_frame =
+                    |x, y| source.mouse_to_frame(x, y, time_ms, frame_width, frame_height);
+                let (x1, y1) = to_frame(redaction.x, redaction.y);
+                let (x2, y2) = to_frame(
+                    redaction.x + redaction.width,
+                    redaction.y + redaction.height,
+                );
+
+                (x1, y1, x2, y2)
+            }
+        };
 
-                                // let mut smooth_scale = SmoothAnimation::new();
-                                // let original_width = bg_frame.width() as f64;
-                                // let original_height = bg_frame.height() as f64;
+        // grown out to even pixels so chroma is covered too
+        let left = make_even(x1.max(0.0) as u32).min(frame_width);
+        let top = make_even(y1.max(0.0) as u32).min(frame_height);
+        let right = make_even(x2.max(0.0).ceil() as u32 + 1).min(frame_width);
+        let bottom = make_even(y2.max(0.0).ceil() as u32 + 1).min(frame_height);
 
-                                // if zooming_in || zooming_out {
-                                //     let current_scale = current_width / original_width;
-                                //     let target_scale = target_width / original_width;
-                                //     let velocity_scale = smooth_scale.ema_velocity / original_width;
+        if right <= left || bottom <= top {
+            continue;
+        }
 
-                                //     let scale_change = ease_out_frictional_animation(
-                                //         target_scale,
-                                //         current_scale,
-                                //         velocity_scale,
-                                //         friction2,
-                                //         0.5,
-                                //     );
+        let (width, height) = (right - left, bottom - top);
+        let strength = redaction.strength.max(1);
 
-                                //     // let width_change = scale_change * original_width;
-                                //     // let smoothed_velocity =
-                                //     //     smooth_scale.smooth_velocity(width_change);
-                                //     // current_width += smoothed_velocity;
-                                //     // current_width = smooth_scale.update(current_width);
+        let replacement = match redaction.style {
+            RedactionStyle::Fill => {
+                let color = yuv_color(&redaction.color);
+                let mut fill = Video::new(Pixel::YUV420P, width, height);
 
-                                //     let width_change = scale_change * original_width;
-                                //     speed = smooth_scale.smooth_velocity(width_change);
+                for (plane, value) in color.iter().enumerate() {
+                    fill.data_mut(plane).fill(value.clamp(0.0, 255.0) as u8);
+                }
 
-                                //     if zooming_out || (speed.abs() > MIN_SPEED_THRESHOLD) {
-                                //         // zoom out seems to have less shakiness so its fine
-                                //         current_width += speed;
-                                //         current_width = smooth_scale.update(current_width);
-                                //     } else {
-                                //         if (current_width > target_width - MIN_SPEED_THRESHOLD) {
-                                //             speed = -(MIN_SPEED_THRESHOLD);
-                                //             current_width += speed;
-                                //             current_width = smooth_scale.update(current_width);
-                                //         }
-                                //     }
-                                //     // else {
-                                //     //     current_width = target_width; // Snap to target if speed is below threshold
-                                //     // }
+                fill
+            }
+            // shrinking averages blocks together, point scaling back up keeps them square
+            RedactionStyle::Pixelate | RedactionStyle::Blur => {
+                let region = crop_frame(&frame, left, top, width, height)?;
+                let small = scale_yuv420p(
+                    &region,
+                    (width / strength).max(1),
+                    (height / strength).max(1),
+                    Flags::AREA,
+                )?;
+                let flags = if redaction.style == RedactionStyle::Pixelate {
+                    Flags::POINT
+                } else {
+                    Flags::BICUBIC
+                };
 
-                                //     current_height =
-                                //         original_height * (current_width / original_width);
+                scale_yuv420p(&small, width, height, flags)?
+            }
+        };
 
-                                //     println!(
-                                //         "Width: {:.2}, Height: {:.2}, Scale: {:.4}, Shaky: {}",
-                                //         current_width,
-                                //         current_height,
-                                //         current_width / original_width,
-                                //         smooth_scale.is_shaky()
-                                //     );
-                                // }
+        paste_frame(&mut frame, &replacement, left, top);
+    }
 
-                                let mut upscaled_frame = Video::empty();
-                                let mut upscale_context = Context::get(
-                                    Pixel::YUV420P,
-                                    bg_frame.width(),
-                                    bg_frame.height(),
-                                    Pixel::YUV420P,
-                                    upscaled_width,
-                                    upscaled_height,
-                                    Flags::BILINEAR,
-                                )
-                                .expect("Failed to create scaling context");
+    Ok(frame)
+}
 
-                                upscale_context
-                                    .run(&bg_frame, &mut upscaled_frame)
-                                    .map_err(|e| format!("Failed to scale frame: {}", e))?;
+// *** Reframing *** //
 
-                                if zooming_in || zooming_out {
-                                    let displacement_width = frictional_animation(
-                                        target_width as f64,
-                                        current_width as f64,
-                                        velocity_width as f64,
-                                        friction2,
-                                    );
-                                    let displacement_height = frictional_animation(
-                                        target_height as f64,
-                                        current_height as f64,
-                                        velocity_height as f64,
-                                        friction2,
-                                    );
+const REFRAME_DEAD_ZONE: f64 = 0.25; // fraction of the crop the cursor moves in freely
 
-                                    current_width += displacement_width as f64;
-                                    current_height += displacement_height as f64;
-                                    // velocity_width += displacement_width as f64;
-                                    // velocity_height += displacement_height as f64;
-                                }
+// how the recording is fitted into the canvas
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+enum Reframe {
+    #[default]
+    Fit, // all of the recording, letterboxed on the background
+    // a crop in the canvas' aspect ratio that pans after the cursor, for vertical
+    // exports of landscape recordings
+    FollowCursor,
+}
 
-                                // if zooming_in || zooming_out {
-                                //     let displacement_width = frictional_animation(
-                                //         target_width,
-                                //         current_width,
-                                //         velocity_width,
-                                //         friction2,
-                                //         // 0.5,
-                                //     );
-                                //     let displacement_height = frictional_animation(
-                                //         target_height,
-                                //         current_height,
-                                //         velocity_height,
-                                //         friction2,
-                                //         // 0.5,
-                                //     );
+// pans a crop_width x crop_height window over the source to keep the cursor in it. The
+// window only moves once the cursor leaves the middle of it, and eases over through
+// SmoothAnimation so small movements don't shake the picture
+struct CursorFollower {
+    source_width: f64,
+    source_height: f64,
+    crop_width: u32,
+    crop_height: u32,
+    target_x: f64,
+    target_y: f64,
+    smooth_x: SmoothAnimation,
+    smooth_y: SmoothAnimation,
+}
 
-                                //     if zooming_out
-                                //         || (displacement_width.abs() > MIN_SPEED_THRESHOLD)
-                                //     {
-                                //         current_width += displacement_width;
-                                //         current_height += displacement_height;
-                                //         velocity_width += displacement_width;
-                                //         velocity_height += displacement_height;
-                                //     } else {
-                                //         if (current_width > target_width - MIN_SPEED_THRESHOLD) {
-                                //             let displacement_width = -(MIN_SPEED_THRESHOLD);
-                                //             let displacement_height = displacement_width
-                                //                 * (bg_frame.width() as f64
-                                //                     / bg_frame.height() as f64);
-                                //             current_width += displacement_width;
-                                //             current_height += displacement_height;
-                                //             velocity_width += displacement_width;
-                                //             velocity_height += displacement_height;
-                                //         }
-                                //     }
-                                // }
+impl CursorFollower {
+    // the biggest window in the area's aspect ratio that fits the source
+    fn new(source_width: u32, source_height: u32, area_width: f64, area_height: f64) -> Self {
+        let aspect = area_width / area_height;
+        let (crop_width, crop_height) = if source_width as f64 / source_height as f64 > aspect {
+            (source_height as f64 * aspect, source_height as f64)
+        } else {
+            (source_width as f64, source_width as f64 / aspect)
+        };
 
-                                // println!("zooming_in {}", zooming_in);
-                                if zooming_in {
-                                    // when zooming in, the target_width should be LESS than the current_width
-                                    // want to prevent current_width from being less than target_width
-                                    current_width = current_width.max(target_width);
-                                    current_height = current_height.max(target_height);
-                                } else {
-                                    current_width = current_width.min(target_width);
-                                    current_height = current_height.min(target_height);
-                                }
+        CursorFollower {
+            source_width: source_width as f64,
+            source_height: source_height as f64,
+            crop_width: make_even((crop_width as u32).clamp(2, source_width)),
+            crop_height: make_even((crop_height as u32).clamp(2, source_height)),
+            target_x: source_width as f64 / 2.0,
+            target_y: source_height as f64 / 2.0,
+            smooth_x: SmoothAnimation::new(),
+            smooth_y: SmoothAnimation::new(),
+        }
+    }
 
-                                // println!(
-                                //     "Dimensions: {} {} {} {}",
-                                //     target_width, target_height, current_width, current_height
-                                // );
+    // cursor in source pixels, returns the left and top of the window
+    fn update(&mut self, cursor_x: f64, cursor_y: f64) -> (u32, u32) {
+        let (width, height) = (self.crop_width as f64, self.crop_height as f64);
 
-                                // velocity_width = velocity_width.clamp(-10000.0, 10000.0);
-                                // velocity_height = velocity_height.clamp(-10000.0, 10000.0);
+        let follow = |target: f64, cursor: f64, size: f64| {
+            let slack = size * REFRAME_DEAD_ZONE;
+            target + (cursor - target).signum() * ((cursor - target).abs() - slack).max(0.0)
+        };
 
-                                let (used_width, used_height) = if (enable_dimension_smoothing
-                                    && (zooming_in || zooming_out))
-                                {
-                                    let smoothing_factor1 = 0.95;
-                                    let (smooth_width, smooth_height) = if successful_frame_index
-                                        == 0
-                                    {
-                                        (
-                                            current_width
-                                                + (smoothing_factor1 * current_width
-                                                    + (1.0 - smoothing_factor1) * smooth_width),
-                                            current_height
-                                                + (smoothing_factor1 * current_height
-                                                    + (1.0 - smoothing_factor1) * smooth_height),
-                                        )
-                                    } else {
-                                        (
-                                            smoothing_factor1 * current_width
-                                                + (1.0 - smoothing_factor1) * smooth_width,
-                                            smoothing_factor1 * current_height
-                                                + (1.0 - smoothing_factor1) * smooth_height,
-                                        )
-                                    };
+        self.target_x = follow(self.target_x, cursor_x, width);
+        self.target_y = follow(self.target_y, cursor_y, height);
 
-                                    // Ensure dimensions are within frame size
-                                    let smooth_width =
-                                        smooth_width.clamp(1.0, bg_frame.width() as f64);
-                                    let smooth_height =
-                                        smooth_height.clamp(1.0, bg_frame.height() as f64);
+        let center_x = self.smooth_x.update(self.target_x);
+        let center_y = self.smooth_y.update(self.target_y);
 
-                                    // println!("Smooth Dimensions: {} x {} and {} x {}", smooth_width, smooth_height, target_width, target_height);
+        let left = (center_x - width / 2.0).clamp(0.0, self.source_width - width);
+        let top = (center_y - height / 2.0).clamp(0.0, self.source_height - height);
 
-                                    (smooth_width, smooth_height)
-                                } else {
-                                    (current_width, current_height)
-                                };
+        (make_even(left as u32), make_even(top as u32))
+    }
+}
 
-                                // // Double-check even numbers (though this should be redundant now)
-                                // let used_width = (used_width / 2.0).floor() * 2.0;
-                                // let used_height = (used_height / 2.0).floor() * 2.0;
+// same size, for code that works on the planes directly
+fn convert_to_yuv420p(source: &Video) -> Result<Video, String> {
+    let mut frame = Video::empty();
+
+    Context::get(
+        source.format(),
+        source.width(),
+        source.height(),
+        Pixel::YUV420P,
+        source.width(),
+        source.height(),
+        Flags::BILINEAR,
+    )
+    .and_then(|mut context| context.run(source, &mut frame))
+    .map_err(|e| format!("Failed to convert frame: {}", e))?;
 
-                                println!("used dimensions: {} {}", used_width, used_height);
+    Ok(frame)
+}
 
-                                // Make sure the dimensions are integers and within the frame size.
-                                let zoom_width = (used_width.round() as u32)
-                                    .clamp(1, upscaled_frame.width() as u32);
-                                let zoom_height = (used_height.round() as u32)
-                                    .clamp(1, upscaled_frame.height() as u32);
+// the left, top, width x height part of a frame as yuv420p, all even
+fn crop_frame(
+    source: &Video,
+    left: u32,
+    top: u32,
+    width: u32,
+    height: u32,
+) -> Result<Video, String> {
+    let converted;
+    let source = if source.format() == Pixel::YUV420P {
+        source
+    } else {
+        converted = convert_to_yuv420p(source)?;
+        &converted
+    };
 
-                                for zoom in &config.zoom_info {
-                                    let start = zoom.start;
-                                    let end = zoom.end;
-                                    let zoom_factor = zoom.zoom;
+    let mut cropped = Video::new(Pixel::YUV420P, width, height);
 
-                                    // Process each zoom info...
-                                    if time_elapsed >= start
-                                        && time_elapsed < start + animation_duration
-                                    {
-                                        if !zooming_in2 {
-                                            // Set mouse coords to the first mouse event after the start timestamp
-                                            let mouse_event = mouse_events
-                                                .iter()
-                                                .find(|event| event.timestamp >= time_elapsed);
+    for plane in 0..3 {
+        let shift = if plane == 0 { 0 } else { 1 };
+        let source_stride = source.stride(plane);
+        let cropped_stride = cropped.stride(plane);
+        let row_width = (width >> shift) as usize;
+        let source_plane = source.data(plane);
+        let cropped_plane = cropped.data_mut(plane);
 
-                                            if let Some(event) = mouse_event {
-                                                mouse_x = event.x as f64;
-                                                mouse_y = event.y as f64;
-                                            }
+        for row in 0..(height >> shift) as usize {
+            let source_start =
+                ((top >> shift) as usize + row) * source_stride + (left >> shift) as usize;
+            let cropped_start = row * cropped_stride;
 
-                                            zooming_in2 = true;
+            cropped_plane[cropped_start..cropped_start + row_width]
+                .copy_from_slice(&source_plane[source_start..source_start + row_width]);
+        }
+    }
+
+    Ok(cropped)
+}
+
+// alpha blends source into a yuv420p frame, scaled to fill width x height and masked to a
+// rounded rect with anti-aliased edges. The border is drawn inside the mask and the shadow
+// below it
+fn composite_framed(
+    frame: &mut Video,
+    source: &Video,
+    look: &FrameLook,
+    left: i32,
+    top: i32,
+    width: u32,
+    height: u32,
+) -> Result<(), String> {
+    // scale to cover the rect, then take the middle of it
+    let cover = (width as f64 / source.width() as f64).max(height as f64 / source.height() as f64);
+    let cover_width = make_even((source.width() as f64 * cover).ceil() as u32).max(width);
+    let cover_height = make_even((source.height() as f64 * cover).ceil() as u32).max(height);
+
+    let mut scaled = Video::empty();
+    Context::get(
+        source.format(),
+        source.width(),
+        source.height(),
+        Pixel::YUV420P,
+        cover_width,
+        cover_height,
+        Flags::BILINEAR,
+    )
+    .and_then(|mut context| context.run(source, &mut scaled))
+    .map_err(|e| format!("Failed to scale frame: {}", e))?;
+
+    let crop_x = make_even((cover_width - width) / 2) as usize;
+    let crop_y = make_even((cover_height - height) / 2) as usize;
+
+    let (w, h) = (width as f64, height as f64);
+    let radius = look.radius.clamp(0.0, w.min(h) / 2.0);
+    let border = look.border.as_ref().map_or(0.0, |border| border.width);
+    let border_color = look.border.as_ref().map(|border| yuv_color(&border.color));
+
+    let shadow = look.shadow.clone().unwrap_or(FrameShadow {
+        opacity: 0.0,
+        ..FrameShadow::default()
+    });
+    let shadow_color = yuv_color(&shadow.color);
+
+    // shadow alpha, mask coverage and whether it's border, for a pixel of the frame
+    let sample = |px: i32, py: i32| -> (f64, f64, bool) {
+        let x = (px - left) as f64 + 0.5;
+        let y = (py - top) as f64 + 0.5;
+        let distance = rounded_rect_distance(x, y, w, h, radius);
+        let coverage = (0.5 - distance).clamp(0.0, 1.0);
+
+        let shadow_alpha = if shadow.opacity > 0.0 {
+            let shadow_distance =
+                rounded_rect_distance(x - shadow.offset_x, y - shadow.offset_y, w, h, radius);
+
+            if shadow.blur > 0.0 {
+                let falloff = 1.0 - (shadow_distance / shadow.blur).clamp(0.0, 1.0);
+                shadow.opacity * falloff * falloff
+            } else {
+                shadow.opacity * (0.5 - shadow_distance).clamp(0.0, 1.0)
+            }
+        } else {
+            0.0
+        };
+
+        (shadow_alpha, coverage, distance > -border)
+    };
+
+    let spread = if shadow.opacity > 0.0 {
+        shadow.blur.ceil() as i32 + shadow.offset_x.abs().max(shadow.offset_y.abs()).ceil() as i32
+    } else {
+        0
+    };
+    let x_range = (left - spread).max(0)..(left + width as i32 + spread).min(frame.width() as i32);
+    let y_range = (top - spread).max(0)..(top + height as i32 + spread).min(frame.height() as i32);
+
+    let blend = |background: u8, color: f64, shadow_alpha: f64, coverage: f64, shade: f64| {
+        let shadowed = background as f64 + (shade - background as f64) * shadow_alpha;
+        (shadowed * (1.0 - coverage) + color * coverage).round() as u8
+    };
+
+    // Y at full size, U and V with one sample per 2x2 block
+    for plane in 0..3 {
+        let shift = if plane == 0 { 0 } else { 1 };
+        let stride = frame.stride(plane);
+        let source_stride = scaled.stride(plane);
+        let source_plane = scaled.data(plane);
+        let plane_border = border_color.map(|color| color[plane]);
+        let plane_shadow = shadow_color[plane];
+
+        let rows = (y_range.start >> shift) as usize..((y_range.end + shift) >> shift) as usize;
+
+        frame
+            .data_mut(plane)
+            .par_chunks_mut(stride)
+            .enumerate()
+            .filter(|(row, _)| rows.contains(row))
+            .for_each(|(row, frame_row)| {
+                let py = (row << shift) as i32;
+
+                for px in x_range.clone().filter(|px| px % (1 << shift) == 0) {
+                    let (shadow_alpha, coverage, is_border) = sample(px, py);
+
+                    if shadow_alpha <= 0.0 && coverage <= 0.0 {
+                        continue;
+                    }
+
+                    let color = match plane_border {
+                        Some(border_color) if is_border => border_color,
+                        _ => {
+                            let sx = (px - left).clamp(0, width as i32 - 1) as usize + crop_x;
+                            let sy = (py - top).clamp(0, height as i32 - 1) as usize + crop_y;
+                            source_plane[(sy >> shift) * source_stride + (sx >> shift)] as f64
+                        }
+                    };
+
+                    let index = (px >> shift) as usize;
+                    frame_row[index] = blend(
+                        frame_row[index],
+                        color,
+                        shadow_alpha,
+                        coverage,
+                        plane_shadow,
+                    );
+                }
+            });
+    }
+
+    Ok(())
+}
+
+// *** Overlays *** //
+
+static INTER_REGULAR: &[u8] = include_bytes!("../../public/fonts/Inter-Regular.ttf");
+static INTER_BOLD: &[u8] = include_bytes!("../../public/fonts/Inter-Bold.ttf");
+
+const TEXT_PADDING: f64 = 0.4; // pill padding, times the text size
+const ARROW_HEAD: f64 = 4.0; // arrow head length, times the line width
+
+// alpha blends color into a yuv420p frame over left..right, top..bottom. coverage gives
+// the alpha at an output pixel center, chroma takes the center of each 2x2 block
+fn fill_coverage<F>(frame: &mut Video, bounds: (i32, i32, i32, i32), color: [f64; 3], coverage: F)
+where
+    F: Fn(f64, f64) -> f64 + Sync,
+{
+    let (left, top, right, bottom) = bounds;
+    let x_range = left.max(0)..right.min(frame.width() as i32);
+    let y_range = top.max(0)..bottom.min(frame.height() as i32);
+
+    for plane in 0..3 {
+        let shift = if plane == 0 { 0 } else { 1 };
+        let stride = frame.stride(plane);
+        let rows = (y_range.start >> shift) as usize..((y_range.end + shift) >> shift) as usize;
+        let offset = shift as f64 * 0.5 + 0.5;
+
+        frame
+            .data_mut(plane)
+            .par_chunks_mut(stride)
+            .enumerate()
+            .filter(|(row, _)| rows.contains(row))
+            .for_each(|(row, frame_row)| {
+                let py = (row << shift) as i32;
+
+                for px in x_range.clone().filter(|px| px % (1 << shift) == 0) {
+                    let alpha = coverage(px as f64 + offset, py as f64 + offset).clamp(0.0, 1.0);
+
+                    if alpha <= 0.0 {
+                        continue;
+                    }
+
+                    let index = (px >> shift) as usize;
+                    let value = frame_row[index] as f64;
+                    frame_row[index] = (value + (color[plane] - value) * alpha).round() as u8;
+                }
+            });
+    }
+}
+
+// distance from p to the segment a b
+fn segment_distance(px: f64, py: f64, ax: f64, ay: f64, bx: f64, by: f64) -> f64 {
+    let (dx, dy) = (bx - ax, by - ay);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        (((px - ax) * dx + (py - ay) * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    (px - ax - dx * t).hypot(py - ay - dy * t)
+}
+
+// text drawn once into an alpha mask, it doesn't change between frames
+struct TextBitmap {
+    width: usize,
+    height: usize,
+    alpha: Vec<f32>,
+}
+
+fn rasterize_text(font: &FontArc, text: &str, size: f32) -> TextBitmap {
+    let scale = PxScale::from(size);
+    let scaled = font.as_scaled(scale);
+    let line_height = scaled.height() + scaled.line_gap();
+
+    let mut glyphs = Vec::new();
+    let mut width = 0.0f32;
+    let mut line_count = 0;
+
+    for (line_index, line) in text.lines().enumerate() {
+        let mut caret = point(0.0, scaled.ascent() + line_index as f32 * line_height);
+        let mut previous = None;
+
+        for c in line.chars() {
+            let id = scaled.glyph_id(c);
+
+            if let Some(previous) = previous {
+                caret.x += scaled.kern(previous, id);
+            }
+
+            glyphs.push(id.with_scale_and_position(scale, caret));
+            caret.x += scaled.h_advance(id);
+            previous = Some(id);
+        }
+
+        width = width.max(caret.x);
+        line_count += 1;
+    }
+
+    let width = width.ceil().max(1.0) as usize;
+    let height = (line_height * line_count.max(1) as f32).ceil() as usize;
+    let mut alpha = vec![0.0; width * height];
+
+    for glyph in glyphs {
+        if let Some(outlined) = font.outline_glyph(glyph) {
+            let bounds = outlined.px_bounds();
+
+            outlined.draw(|x, y, coverage| {
+                let px = bounds.min.x as i32 + x as i32;
+                let py = bounds.min.y as i32 + y as i32;
+
+                if px >= 0 && py >= 0 && (px as usize) < width && (py as usize) < height {
+                    let index = py as usize * width + px as usize;
+                    alpha[index] = alpha[index].max(coverage);
+                }
+            });
+        }
+    }
+
+    TextBitmap {
+        width,
+        height,
+        alpha,
+    }
+}
+
+struct OverlayRenderer {
+    overlays: Vec<(Overlay, Option<TextBitmap>)>,
+}
+
+impl OverlayRenderer {
+    fn new(overlays: &[Overlay]) -> Result<Self, String> {
+        let mut prepared = Vec::new();
+
+        for overlay in overlays {
+            let bitmap = match &overlay.shape {
+                OverlayShape::Text {
+                    text,
+                    bold,
+                    font_file,
+                    size,
+                    ..
+                } => {
+                    let font = match font_file {
+                        Some(path) => fs::read(path)
+                            .map_err(|e| format!("Could not read font {}: {}", path, e))
+                            .and_then(|data| {
+                                FontArc::try_from_vec(data).map_err(|e| e.to_string())
+                            })?,
+                        None if *bold => {
+                            FontArc::try_from_slice(INTER_BOLD).map_err(|e| e.to_string())?
+                        }
+                        None => {
+                            FontArc::try_from_slice(INTER_REGULAR).map_err(|e| e.to_string())?
+                        }
+                    };
+
+                    Some(rasterize_text(&font, text, *size as f32))
+                }
+                _ => None,
+            };
+
+            prepared.push((overlay.clone(), bitmap));
+        }
+
+        Ok(Self { overlays: prepared })
+    }
+
+    fn render(&self, frame: &mut Video, time_ms: i32) {
+        let (w, h) = (frame.width() as f64, frame.height() as f64);
+
+        for (overlay, bitmap) in &self.overlays {
+            if time_ms < overlay.start || time_ms >= overlay.end {
+                continue;
+            }
+
+            let fade = |elapsed: i32, duration: i32| {
+                if duration > 0 {
+                    (elapsed as f64 / duration as f64).min(1.0)
+                } else {
+                    1.0
+                }
+            };
+            let opacity = fade(time_ms - overlay.start, overlay.fade_in)
+                .min(fade(overlay.end - time_ms, overlay.fade_out));
+
+            match &overlay.shape {
+                OverlayShape::Text {
+                    size,
+                    color,
+                    background,
+                    background_opacity,
+                    x,
+                    y,
+                    ..
+                } => {
+                    let Some(bitmap) = bitmap else {
+                        continue;
+                    };
+
+                    let text_left = (x * w - bitmap.width as f64 / 2.0).round();
+                    let text_top = (y * h - bitmap.height as f64 / 2.0).round();
+
+                    if let Some(background) = background {
+                        let padding = size * TEXT_PADDING;
+                        let pill_width = bitmap.width as f64 + padding * 2.0;
+                        let pill_height = bitmap.height as f64 + padding;
+                        let pill_left = text_left - padding;
+                        let pill_top = text_top - padding / 2.0;
+
+                        fill_coverage(
+                            frame,
+                            (
+                                pill_left.floor() as i32,
+                                pill_top.floor() as i32,
+                                (pill_left + pill_width).ceil() as i32,
+                                (pill_top + pill_height).ceil() as i32,
+                            ),
+                            yuv_color(background),
+                            |px, py| {
+                                let distance = rounded_rect_distance(
+                                    px - pill_left,
+                                    py - pill_top,
+                                    pill_width,
+                                    pill_height,
+                                    pill_height / 2.0,
+                                );
+                                (0.5 - distance).clamp(0.0, 1.0) * background_opacity * opacity
+                            },
+                        );
+                    }
+
+                    fill_coverage(
+                        frame,
+                        (
+                            text_left as i32,
+                            text_top as i32,
+                            text_left as i32 + bitmap.width as i32,
+                            text_top as i32 + bitmap.height as i32,
+                        ),
+                        yuv_color(color),
+                        |px, py| {
+                            let bx = (px - text_left) as usize;
+                            let by = (py - text_top) as usize;
+
+                            if bx < bitmap.width && by < bitmap.height {
+                                bitmap.alpha[by * bitmap.width + bx] as f64 * opacity
+                            } else {
+                                0.0
+                            }
+                        },
+                    );
+                }
+                OverlayShape::Arrow {
+                    from_x,
+                    from_y,
+                    to_x,
+                    to_y,
+                    color,
+                    width,
+                } => {
+                    let (ax, ay, bx, by) = (from_x * w, from_y * h, to_x * w, to_y * h);
+                    let length = (bx - ax).hypot(by - ay).max(1.0);
+                    let (dx, dy) = ((bx - ax) / length, (by - ay) / length);
+
+                    // the shaft stops where the head starts
+                    let head = (width * ARROW_HEAD).min(length);
+                    let (neck_x, neck_y) = (bx - dx * head, by - dy * head);
+                    let corners = [
+                        (bx, by),
+                        (neck_x - dy * head * 0.6, neck_y + dx * head * 0.6),
+                        (neck_x + dy * head * 0.6, neck_y - dx * head * 0.6),
+                    ];
+
+                    let spread = head + width;
+                    let bounds = (
+                        (ax.min(bx) - spread).floor() as i32,
+                        (ay.min(by) - spread).floor() as i32,
+                        (ax.max(bx) + spread).ceil() as i32,
+                        (ay.max(by) + spread).ceil() as i32,
+                    );
+
+                    fill_coverage(frame, bounds, yuv_color(color), |px, py| {
+                        let shaft =
+                            width / 2.0 - segment_distance(px, py, ax, ay, neck_x, neck_y) + 0.5;
+
+                        // signed distance to the head triangle, the furthest of its edges.
+                        // Both windings are tried since it depends on the arrow's direction
+                        let (mut clockwise, mut counter_clockwise) = (f64::MIN, f64::MIN);
+                        for (i, &(x1, y1)) in corners.iter().enumerate() {
+                            let (x2, y2) = corners[(i + 1) % 3];
+                            let edge_length = (x2 - x1).hypot(y2 - y1).max(f64::EPSILON);
+                            let distance =
+                                ((x2 - x1) * (py - y1) - (y2 - y1) * (px - x1)) / edge_length;
+                            clockwise = clockwise.max(distance);
+                            counter_clockwise = counter_clockwise.max(-distance);
+                        }
+                        let edge_distance = clockwise.min(counter_clockwise);
+
+                        shaft.max(0.5 - edge_distance).clamp(0.0, 1.0) * opacity
+                    });
+                }
+                OverlayShape::Rectangle {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                    line_width,
+                } => {
+                    let (left, top, rect_width, rect_height) =
+                        (x * w, y * h, width * w, height * h);
+
+                    fill_coverage(
+                        frame,
+                        (
+                            left.floor() as i32,
+                            top.floor() as i32,
+                            (left + rect_width).ceil() as i32,
+                            (top + rect_height).ceil() as i32,
+                        ),
+                        yuv_color(color),
+                        |px, py| {
+                            // the stroke runs along the inside of the edge
+                            let distance = rounded_rect_distance(
+                                px - left,
+                                py - top,
+                                rect_width,
+                                rect_height,
+                                0.0,
+                            );
+                            (0.5 - distance).clamp(0.0, 1.0)
+                                * (distance + line_width + 0.5).clamp(0.0, 1.0)
+                                * opacity
+                        },
+                    );
+                }
+                OverlayShape::Highlight {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                    opacity: fill_opacity,
+                } => {
+                    let (left, top, rect_width, rect_height) =
+                        (x * w, y * h, width * w, height * h);
+
+                    fill_coverage(
+                        frame,
+                        (
+                            left.floor() as i32,
+                            top.floor() as i32,
+                            (left + rect_width).ceil() as i32,
+                            (top + rect_height).ceil() as i32,
+                        ),
+                        yuv_color(color),
+                        |px, py| {
+                            let distance = rounded_rect_distance(
+                                px - left,
+                                py - top,
+                                rect_width,
+                                rect_height,
+                                0.0,
+                            );
+                            (0.5 - distance).clamp(0.0, 1.0) * fill_opacity * opacity
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+// *** Keystrokes *** //
+
+const KEYSTROKE_FADE: i32 = 200; // ms
+const KEYSTROKE_HISTORY: usize = 3; // combos shown side by side
+
+// a key going down while recording, with everything held at that moment
+#[derive(Deserialize, Serialize, Debug)]
+struct KeyEvent {
+    timestamp: i32,
+    keys: Vec<String>,
+}
+
+fn is_modifier_key(name: &str) -> bool {
+    matches!(
+        name,
+        "LControl"
+            | "RControl"
+            | "LShift"
+            | "RShift"
+            | "LAlt"
+            | "RAlt"
+            | "LOption"
+            | "ROption"
+            | "LMeta"
+            | "RMeta"
+            | "Command"
+            | "RCommand"
+    )
+}
+
+fn key_label(name: &str) -> String {
+    let label = match name {
+        "LControl" | "RControl" => "Ctrl",
+        "LShift" | "RShift" => "Shift",
+        "LAlt" | "RAlt" | "LOption" | "ROption" => "Alt",
+        "LMeta" | "RMeta" | "Command" | "RCommand" => "Meta",
+        "Escape" => "Esc",
+        "Up" => "↑",
+        "Down" => "↓",
+        "Left" => "←",
+        "Right" => "→",
+        "Minus" => "-",
+        "Equal" => "=",
+        "Comma" => ",",
+        "Dot" => ".",
+        "Slash" => "/",
+        "BackSlash" => "\\",
+        "Semicolon" => ";",
+        "Apostrophe" => "'",
+        "LeftBracket" => "[",
+        "RightBracket" => "]",
+        "Grave" => "`",
+        _ => name.strip_prefix("Key").unwrap_or(name),
+    };
+
+    label.to_string()
+}
+
+// "Ctrl+Shift+P" for the keys held, None when the press isn't shown
+fn combo_label(keys: &[String], filter: &KeystrokeFilter) -> Option<String> {
+    let mut modifiers = Vec::new();
+    let mut pressed = Vec::new();
+
+    for key in keys {
+        let label = key_label(key);
+
+        if !is_modifier_key(key) {
+            pressed.push(label);
+        } else if !modifiers.contains(&label) {
+            modifiers.push(label);
+        }
+    }
+
+    if pressed.is_empty() {
+        return None;
+    }
+
+    // shift with a letter is still typing
+    let is_typing = pressed
+        .iter()
+        .all(|label| label.chars().count() == 1 || label == "Space");
+
+    if filter.only_shortcuts && is_typing && modifiers.iter().all(|label| label == "Shift") {
+        return None;
+    }
+
+    let order = ["Ctrl", "Alt", "Shift", "Meta"];
+    modifiers.sort_by_key(|label| order.iter().position(|o| o == label));
+
+    let combo = modifiers
+        .into_iter()
+        .chain(pressed.iter().cloned())
+        .collect::<Vec<_>>()
+        .join("+");
+
+    let ignored = filter.ignore.iter().any(|ignored| {
+        ignored.eq_ignore_ascii_case(&combo)
+            || pressed
+                .iter()
+                .any(|label| ignored.eq_ignore_ascii_case(label))
+    });
+
+    (!ignored).then_some(combo)
+}
+
+// the HUD as timed text overlays, one for every change of what it shows. Presses within
+// the timeout of each other share the HUD, repeats of a combo are counted
+fn keystroke_overlays(info: &KeystrokeInfo, events: &[KeyEvent]) -> Vec<Overlay> {
+    let mut overlays: Vec<Overlay> = Vec::new();
+    let mut combos: Vec<(String, u32)> = Vec::new();
+
+    for event in events {
+        let Some(combo) = combo_label(&event.keys, &info.filter) else {
+            continue;
+        };
+
+        let continues = overlays
+            .last()
+            .map(|last| event.timestamp < last.end)
+            .unwrap_or(false);
+
+        if continues {
+            let last = overlays.last_mut().unwrap();
+            last.end = event.timestamp;
+            last.fade_out = 0;
+        } else {
+            combos.clear();
+        }
+
+        match combos.last_mut() {
+            Some((last, count)) if *last == combo => *count += 1,
+            _ => combos.push((combo, 1)),
+        }
+
+        if combos.len() > KEYSTROKE_HISTORY {
+            combos.remove(0);
+        }
+
+        let text = combos
+            .iter()
+            .map(|(combo, count)| match count {
+                1 => combo.clone(),
+                _ => format!("{} ×{}", combo, count),
+            })
+            .collect::<Vec<_>>()
+            .join("   ");
+
+        overlays.push(Overlay {
+            start: event.timestamp,
+            end: event.timestamp + info.timeout + KEYSTROKE_FADE,
+            fade_in: if continues { 0 } else { KEYSTROKE_FADE },
+            fade_out: KEYSTROKE_FADE,
+            shape: OverlayShape::Text {
+                text,
+                bold: true,
+                font_file: None,
+                size: info.size,
+                color: info.color.clone(),
+                background: info.background.clone(),
+                background_opacity: info.background_opacity,
+                x: info.x,
+                y: info.y,
+            },
+        });
+    }
+
+    overlays
+}
+
+fn load_keystroke_overlays(info: &KeystrokeInfo) -> Result<Vec<Overlay>, String> {
+    let file = File::open(&info.input_file)
+        .map_err(|e| format!("Could not open key events file: {}", e))?;
+    let events: Vec<KeyEvent> = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| format!("Failed to parse key events JSON: {}", e))?;
+
+    Ok(keystroke_overlays(info, &events))
+}
+
+fn do_transform_video(configPath: String) -> Result<String, String> {
+    // let start1 = Instant::now();
+
+    // Load and parse the JSON configuration
+    let mut config: Config = match fs::read_to_string(&configPath) {
+        Ok(json_str) => serde_json::from_str(&json_str)
+            .map_err(|e| e.to_string())
+            .expect("Couldn't transform json string"),
+        Err(e) => return Err(format!("Failed to read config file: {}", e)),
+    };
+
+    // paths in the config are relative to the config's own folder
+    let config_dir = Path::new(&configPath)
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+
+    for path in config_paths_mut(&mut config) {
+        *path = resolve_config_path(config_dir, path);
+    }
+
+    let mouse_events: Vec<MouseEvents> = match File::open(&config.positions_file) {
+        Ok(file) => {
+            let reader = BufReader::new(file);
+            serde_json::from_reader(reader)
+                .map_err(|e| format!("Failed to parse mouse events JSON: {}", e))?
+        }
+        Err(e) => return Err(format!("Could not open mouse events file: {}", e)),
+    };
+
+    let window_data: SourceFile = match File::open(&config.source_file) {
+        Ok(file) => {
+            let reader = BufReader::new(file);
+            serde_json::from_reader(reader)
+                .map_err(|e| format!("Failed to parse window data JSON: {}", e))
+                .expect("Couldn't transform json data")
+        }
+        Err(e) => return Err(format!("Could not open window data file: {}", e)),
+    };
+
+    // Initialize FFmpeg
+    ffmpeg::init().map_err(|e| format!("Failed to initialize FFmpeg: {}", e))?;
+
+    // *** decode video ***
+    let input_filename = config.input_file;
+    let output_filename = config.output_file;
+    let fps_int = 60;
+
+    let mut input_context = ffmpeg::format::input(&input_filename)
+        .map_err(|e| format!("Could not open file: {}", e))?;
+
+    // input_context.dump();
+
+    let video_stream = input_context
+        .streams()
+        .best(ffmpeg::media::Type::Video)
+        .ok_or("No video stream found")?;
+
+    let video_stream_index = video_stream.index();
+
+    let mut decoder = ffmpeg::codec::context::Context::from_parameters(video_stream.parameters())
+        .map_err(|e| format!("Failed to create decoder context: {}", e))?
+        .decoder()
+        .video()
+        .map_err(|e| format!("Failed to create video decoder: {}", e))?;
+
+    // note: open() should be called automatically with from_paramaters()
+    // decoder
+    //     .open()
+    //     .map_err(|e| format!("Failed to open decoder: {}", e))?;
+
+    // *** prep encoding ***
+    let mut output_context = ffmpeg::format::output(&output_filename)
+        .map_err(|e| format!("Could not create output context: {}", e))?;
+
+    let global_header = output_context
+        .format()
+        .flags()
+        .contains(ffmpeg::format::Flags::GLOBAL_HEADER);
+
+    // let codec = ffmpeg::encoder::find_by_name("libx264").ok_or("Could not find libx264 encoder")?;
+    let codec =
+        ffmpeg::encoder::find(ffmpeg::codec::Id::H264).ok_or("Could not find libx264 encoder")?;
+
+    let mut output_stream = output_context
+        .add_stream(codec)
+        .map_err(|e| format!("Failed to add output stream: {}", e))?;
+
+    // let mut encoder = ffmpeg::codec::context::Context::new()
+    //     .encoder()
+    //     .video()
+    //     .map_err(|e| format!("Failed to create video encoder: {}", e))?;
+
+    let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+        .encoder()
+        .video()
+        .map_err(|e| format!("Failed to create video encoder: {}", e))?;
+
+    // output_stream.set_parameters(encoder.parameters());
+
+    // encoder.set_codec(&codec);
+
+    let fps_int = 60; // Assuming 60 FPS, adjust as needed
+
+    encoder.set_bit_rate(decoder.bit_rate());
+    let (canvas_width, canvas_height) = config.canvas.size(decoder.width(), decoder.height());
+
+    encoder.set_width(canvas_width);
+    encoder.set_height(canvas_height);
+    // encoder.set_time_base((1, fps_int));
+    encoder.set_time_base(ffmpeg::Rational(1, fps_int));
+    encoder.set_frame_rate(Some(ffmpeg::Rational(fps_int, 1)));
+    encoder.set_gop(10);
+    encoder.set_max_b_frames(1);
+    encoder.set_format(ffmpeg::util::format::Pixel::YUV420P);
+    // encoder.set_quality(50); // "-qscale is ignored, -crf is recommended."
+    // encoder.set_compression(Some(23));
+
+    // Create a Dictionary to hold the encoder parameters
+    let mut parameters = Dictionary::new();
+    parameters.set("preset", "faster");
+    // parameters.set("x264-params", "level=4.0");
+    // parameters.set("tune", "zerolatency"); // "good for streaming scenarios"??
+    parameters.set("crf", "27");
+
+    let mut encoder = encoder
+        .open_with(parameters)
+        .expect("Couldn't open encoder");
+
+    output_stream.set_time_base((1, fps_int));
+
+    // Copy encoder parameters to output stream, the canvas may not match the input
+    output_stream.set_parameters(&encoder);
+
+    // Open output file
+    output_context
+        .write_header()
+        .map_err(|e| format!("Error occurred when opening output file: {}", e))?;
+
+    let mut y = 0;
+    let mut zoom = 1.0;
+
+    let mut current_multiplier = 1.0;
+    let mut velocity = 0.0;
+
+    let total_frames = (config.duration / 1000) * fps_int;
+    let mut frame_index = 0;
+    let mut successful_frame_index = 0;
+
+    let start_time = Instant::now();
+
+    let mut mouse_x = 0.0;
+    let mut mouse_y = 0.0;
+    let mut current_mouse_x = canvas_width as f64 / 2.0;
+    let mut current_mouse_y = canvas_height as f64 / 2.0;
+    let mut velocity_mouse_x: f64 = 0.0;
+    let mut velocity_mouse_y: f64 = 0.0;
+    let mut zoom_top = 0;
+    let mut zoom_left = 0;
+    let mut zooming_in = false;
+    let mut zooming_in2 = false;
+    let mut velocity_width = 0.0;
+    let mut velocity_height = 0.0;
+    let auto_zoom = false;
+
+    let friction1 = 2.5;
+    let friction2 = 4.0;
+    let friction3 = 5.0;
+    let easing_factor = 1.0;
+
+    let mut direction_x = 0.0;
+    let mut direction_y = 0.0;
+
+    let mut prev_zoom_top = 0.0;
+    let mut prev_zoom_left = 0.0;
+    let mut smooth_zoom_top = 0.0;
+    let mut smooth_zoom_left = 0.0;
+    let mut used_zoom_top = 0.0;
+    let mut used_zoom_left = 0.0;
+
+    let mut smooth_width = 0.0;
+    let mut smooth_height = 0.0;
+    let mut used_width = 0.0;
+    let mut used_height = 0.0;
+
+    let animation_duration = 5000;
+
+    // frictional_animation causes gradual slowdown, but smoothing is supposed to improved shakiness
+    let enable_dimension_smoothing = false;
+    let enable_coord_smoothing = false;
+
+    let mut frame_index = 0;
+    let mut successful_frame_index = 0;
+
+    let mut target_multiplier = 1.0; // Default value when no zoom effect is active
+    let mut zooming_in = false;
+    let mut zooming_out = false;
+
+    let mut speed = f64::INFINITY; // Initialize with a high value
+
+    // a missing or broken webcam.mp4 only costs the overlay
+    let mut webcam_reader =
+        config
+            .webcam
+            .as_ref()
+            .and_then(|webcam| match WebcamReader::open(&webcam.input_file) {
+                Ok(reader) => Some(reader),
+                Err(e) => {
+                    eprintln!("Skipping webcam overlay: {}", e);
+                    None
+                }
+            });
+
+    // Get a packet iterator
+    let mut packet_iter = input_context.packets();
+
+    let backgrounds =
+        BackgroundTimeline::new(&config.background_info, encoder.width(), encoder.height())?;
+
+    // following the cursor, a crop of the recording fills the padded area instead
+    let mut cursor_follower = match config.reframe {
+        Reframe::Fit => None,
+        Reframe::FollowCursor => {
+            let (_, _, area_width, area_height) =
+                padded_area(&config.frame, encoder.width(), encoder.height());
+
+            Some(CursorFollower::new(
+                decoder.width(),
+                decoder.height(),
+                area_width,
+                area_height,
+            ))
+        }
+    };
+    let (crop_width, crop_height) = cursor_follower
+        .as_ref()
+        .map_or((decoder.width(), decoder.height()), |follower| {
+            (follower.crop_width, follower.crop_height)
+        });
+    let (mut crop_left, mut crop_top) = (0, 0);
+
+    let (inset_left, inset_top, inset_width, inset_height) = frame_rect(
+        &config.frame,
+        encoder.width(),
+        encoder.height(),
+        crop_width,
+        crop_height,
+    );
+
+    // the keystroke HUD is drawn with the other overlays
+    let mut overlay_list = config.overlays.clone();
+    if let Some(keystrokes) = &config.keystrokes {
+        match load_keystroke_overlays(keystrokes) {
+            Ok(hud) => overlay_list.extend(hud),
+            Err(e) => eprintln!("Skipping keystrokes: {}", e),
+        }
+    }
+    let overlays = OverlayRenderer::new(&overlay_list)?;
+
+    let frame_look = FrameLook {
+        radius: config.frame.corner_radius,
+        border: config.frame.border.clone(),
+        shadow: config.frame.shadow.clone(),
+    };
+
+    let mut smoothed_velocity_width = canvas_width as f64;
+    let mut smoothed_velocity_height = canvas_width as f64;
+
+    // 1. Upscale the background frame
+    let upscale_factor = 4; // Choose an appropriate factor
+    let upscaled_width = canvas_width * upscale_factor;
+    let upscaled_height = canvas_height * upscale_factor;
+
+    // These should be declared outside the loop and updated each iteration
+    let mut current_width = (canvas_width as f64) * upscale_factor as f64;
+    let mut current_height = (canvas_height as f64) * upscale_factor as f64;
+
+    // Main loop
+    'main_loop: loop {
+        match packet_iter.next() {
+            Some((stream, packet)) => {
+                if stream.index() == video_stream_index {
+                    // Process video packets
+                    // let start2 = Instant::now();
+
+                    decoder
+                        .send_packet(&packet)
+                        .map_err(|e| format!("Error sending packet for decoding: {}", e))?;
+
+                    'decode_loop: loop {
+                        let mut decoded_frame = ffmpeg::frame::Video::empty();
+                        match decoder.receive_frame(&mut decoded_frame) {
+                            Ok(_) => {
+                                // *** Frame transformation logic ***
+
+                                // before anything is scaled, so regions stay put when zoomed
+                                let decoded_frame = apply_redactions(
+                                    decoded_frame,
+                                    &config.redactions,
+                                    frame_index * 1000 / fps_int,
+                                    &window_data,
+                                )?;
+
+                                // Create a new frame for the background
+                                let mut bg_frame = backgrounds
+                                    .render(&decoded_frame, frame_index * 1000 / fps_int)?;
+                                bg_frame.set_pts(Some(frame_index as i64));
+
+                                // let duration3 = start3.elapsed();
+                                // println!("start3 Time elapsed: {:?}", duration3);
+
+                                // let start10 = Instant::now();
+
+                                // *** Inset Video *** //
+
+                                let cropped_frame;
+                                let inset_source = match cursor_follower.as_mut() {
+                                    Some(follower) => {
+                                        let time_ms = frame_index * 1000 / fps_int;
+                                        let seen = mouse_events
+                                            .partition_point(|event| event.timestamp <= time_ms);
+                                        let cursor = mouse_events.get(seen.saturating_sub(1));
+
+                                        if let Some(event) = cursor {
+                                            let (cursor_x, cursor_y) = window_data.mouse_to_frame(
+                                                event.x as f64,
+                                                event.y as f64,
+                                                event.timestamp,
+                                                decoder.width(),
+                                                decoder.height(),
+                                            );
+
+                                            (crop_left, crop_top) =
+                                                follower.update(cursor_x, cursor_y);
+                                        }
+
+                                        cropped_frame = crop_frame(
+                                            &decoded_frame,
+                                            crop_left,
+                                            crop_top,
+                                            crop_width,
+                                            crop_height,
+                                        )?;
+                                        &cropped_frame
+                                    }
+                                    None => &decoded_frame,
+                                };
+
+                                composite_framed(
+                                    &mut bg_frame,
+                                    inset_source,
+                                    &frame_look,
+                                    inset_left,
+                                    inset_top,
+                                    inset_width,
+                                    inset_height,
+                                )?;
+
+                                // *** Zoom *** //
+                                let time_elapsed = frame_index * 1000 / fps_int;
+
+                                // let duration10 = start10.elapsed();
+                                // println!("start10 Time elapsed: {:?}", duration10);
+
+                                // let start4 = Instant::now();
+
+                                let mut target_width = upscaled_width as f64;
+                                let mut target_height = upscaled_width as f64;
+
+                                // Search for the current zoom level
+                                let mut t = 1.0;
+
+                                for zoom in &config.zoom_info {
+                                    let start = zoom.start as i32;
+                                    let end = zoom.end as i32;
+                                    let zoom_factor = zoom.zoom;
+
+                                    // Process each zoom info...
+                                    if time_elapsed >= start && time_elapsed < end {
+                                        if !zooming_in {
+                                            velocity = 0.0;
+                                            velocity_mouse_x = 0.0;
+                                            velocity_mouse_y = 0.0;
+                                            velocity_width = 0.0;
+                                            velocity_height = 0.0;
+                                            zooming_in = true;
+                                            zooming_out = false;
+                                            target_multiplier = zoom_factor;
+                                        }
+
+                                        // Calculate the interpolation factor t based on the animation progress
+                                        // t = time_elapsed as f64
+                                        //     / (start + animation_duration) as f64;
+                                    } else if time_elapsed >= end
+                                        && time_elapsed < end + animation_duration
+                                    {
+                                        if zooming_in {
+                                            velocity = 0.0;
+                                            velocity_mouse_x = 0.0;
+                                            velocity_mouse_y = 0.0;
+                                            velocity_width = 0.0;
+                                            velocity_height = 0.0;
+                                            zooming_in = false;
+                                            zooming_out = true;
+                                            target_multiplier = 1.0;
+                                        }
+                                    } else if (time_elapsed >= end + animation_duration) {
+                                        if zooming_out {
+                                            zooming_out = true;
+                                        }
+                                    }
+                                }
+
+                                current_multiplier = target_multiplier;
+
+                                // (ex. 1.0 is 100% while 0.8 is ~120%)
+                                // println!("currentMultiplier {}", current_multiplier);
+
+                                target_width = upscaled_width as f64 * current_multiplier;
+                                target_height = upscaled_height as f64 * current_multiplier;
+
+                                // // These should be declared outside the loop and updated each iteration
+                                // let mut current_width = bg_frame.width() as f64;
+                                // let mut current_height = bg_frame.height() as f64;
+
+                                // let mut smooth_scale = SmoothAnimation::new();
+                                // let original_width = bg_frame.width() as f64;
+                                // let original_height = bg_frame.height() as f64;
+
+                                // if zooming_in || zooming_out {
+                                //     let current_scale = current_width / original_width;
+                                //     let target_scale = target_width / original_width;
+                                //     let velocity_scale = smooth_scale.ema_velocity / original_width;
+
+                                //     let scale_change = ease_out_frictional_animation(
+                                //         target_scale,
+                                //         current_scale,
+                                //         velocity_scale,
+                                //         friction2,
+                                //         0.5,
+                                //     );
+
+                                //     // let width_change = scale_change * original_width;
+                                //     // let smoothed_velocity =
+                                //     //     smooth_scale.smooth_velocity(width_change);
+                                //     // current_width += smoothed_velocity;
+                                //     // current_width = smooth_scale.update(current_width);
+
+                                //     let width_change = scale_change * original_width;
+                                //     speed = smooth_scale.smooth_velocity(width_change);
+
+                                //     if zooming_out || (speed.abs() > MIN_SPEED_THRESHOLD) {
+                                //         // zoom out seems to have less shakiness so its fine
+                                //         current_width += speed;
+                                //         current_width = smooth_scale.update(current_width);
+                                //     } else {
+                                //         if (current_width > target_width - MIN_SPEED_THRESHOLD) {
+                                //             speed = -(MIN_SPEED_THRESHOLD);
+                                //             current_width += speed;
+                                //             current_width = smooth_scale.update(current_width);
+                                //         }
+                                //     }
+                                //     // else {
+                                //     //     current_width = target_width; // Snap to target if speed is below threshold
+                                //     // }
+
+                                //     current_height =
+                                //         original_height * (current_width / original_width);
+
+                                //     println!(
+                                //         "Width: {:.2}, Height: {:.2}, Scale: {:.4}, Shaky: {}",
+                                //         current_width,
+                                //         current_height,
+                                //         current_width / original_width,
+                                //         smooth_scale.is_shaky()
+                                //     );
+                                // }
+
+                                let mut upscaled_frame = Video::empty();
+                                let mut upscale_context = Context::get(
+                                    Pixel::YUV420P,
+                                    bg_frame.width(),
+                                    bg_frame.height(),
+                                    Pixel::YUV420P,
+                                    upscaled_width,
+                                    upscaled_height,
+                                    Flags::BILINEAR,
+                                )
+                                .expect("Failed to create scaling context");
+
+                                upscale_context
+                                    .run(&bg_frame, &mut upscaled_frame)
+                                    .map_err(|e| format!("Failed to scale frame: {}", e))?;
+
+                                if zooming_in || zooming_out {
+                                    let displacement_width = frictional_animation(
+                                        target_width as f64,
+                                        current_width as f64,
+                                        velocity_width as f64,
+                                        friction2,
+                                    );
+                                    let displacement_height = frictional_animation(
+                                        target_height as f64,
+                                        current_height as f64,
+                                        velocity_height as f64,
+                                        friction2,
+                                    );
+
+                                    current_width += displacement_width as f64;
+                                    current_height += displacement_height as f64;
+                                    // velocity_width += displacement_width as f64;
+                                    // velocity_height += displacement_height as f64;
+                                }
+
+                                // if zooming_in || zooming_out {
+                                //     let displacement_width = frictional_animation(
+                                //         target_width,
+                                //         current_width,
+                                //         velocity_width,
+                                //         friction2,
+                                //         // 0.5,
+                                //     );
+                                //     let displacement_height = frictional_animation(
+                                //         target_height,
+                                //         current_height,
+                                //         velocity_height,
+                                //         friction2,
+                                //         // 0.5,
+                                //     );
+
+                                //     if zooming_out
+                                //         || (displacement_width.abs() > MIN_SPEED_THRESHOLD)
+                                //     {
+                                //         current_width += displacement_width;
+                                //         current_height += displacement_height;
+                                //         velocity_width += displacement_width;
+                                //         velocity_height += displacement_height;
+                                //     } else {
+                                //         if (current_width > target_width - MIN_SPEED_THRESHOLD) {
+                                //             let displacement_width = -(MIN_SPEED_THRESHOLD);
+                                //             let displacement_height = displacement_width
+                                //                 * (bg_frame.width() as f64
+                                //                     / bg_frame.height() as f64);
+                                //             current_width += displacement_width;
+                                //             current_height += displacement_height;
+                                //             velocity_width += displacement_width;
+                                //             velocity_height += displacement_height;
+                                //         }
+                                //     }
+                                // }
+
+                                // println!("zooming_in {}", zooming_in);
+                                if zooming_in {
+                                    // when zooming in, the target_width should be LESS than the current_width
+                                    // want to prevent current_width from being less than target_width
+                                    current_width = current_width.max(target_width);
+                                    current_height = current_height.max(target_height);
+                                } else {
+                                    current_width = current_width.min(target_width);
+                                    current_height = current_height.min(target_height);
+                                }
+
+                                // println!(
+                                //     "Dimensions: {} {} {} {}",
+                                //     target_width, target_height, current_width, current_height
+                                // );
+
+                                // velocity_width = velocity_width.clamp(-10000.0, 10000.0);
+                                // velocity_height = velocity_height.clamp(-10000.0, 10000.0);
+
+                                let (used_width, used_height) = if (enable_dimension_smoothing
+                                    && (zooming_in || zooming_out))
+                                {
+                                    let smoothing_factor1 = 0.95;
+                                    let (smooth_width, smooth_height) = if successful_frame_index
+                                        == 0
+                                    {
+                                        (
+                                            current_width
+                                                + (smoothing_factor1 * current_width
+                                                    + (1.0 - smoothing_factor1) * smooth_width),
+                                            current_height
+                                                + (smoothing_factor1 * current_height
+                                                    + (1.0 - smoothing_factor1) * smooth_height),
+                                        )
+                                    } else {
+                                        (
+                                            smoothing_factor1 * current_width
+                                                + (1.0 - smoothing_factor1) * smooth_width,
+                                            smoothing_factor1 * current_height
+                                                + (1.0 - smoothing_factor1) * smooth_height,
+                                        )
+                                    };
+
+                                    // Ensure dimensions are within frame size
+                                    let smooth_width =
+                                        smooth_width.clamp(1.0, bg_frame.width() as f64);
+                                    let smooth_height =
+                                        smooth_height.clamp(1.0, bg_frame.height() as f64);
+
+                                    // println!("Smooth Dimensions: {} x {} and {} x {}", smooth_width, smooth_height, target_width, target_height);
+
+                                    (smooth_width, smooth_height)
+                                } else {
+                                    (current_width, current_height)
+                                };
+
+                                // // Double-check even numbers (though this should be redundant now)
+                                // let used_width = (used_width / 2.0).floor() * 2.0;
+                                // let used_height = (used_height / 2.0).floor() * 2.0;
+
+                                // Make sure the dimensions are integers and within the frame size.
+                                let zoom_width = (used_width.round() as u32)
+                                    .clamp(1, upscaled_frame.width() as u32);
+                                let zoom_height = (used_height.round() as u32)
+                                    .clamp(1, upscaled_frame.height() as u32);
+
+                                for zoom in &config.zoom_info {
+                                    let start = zoom.start;
+                                    let end = zoom.end;
+                                    let zoom_factor = zoom.zoom;
+
+                                    // Process each zoom info...
+                                    if time_elapsed >= start
+                                        && time_elapsed < start + animation_duration
+                                    {
+                                        if !zooming_in2 {
+                                            // Set mouse coords to the first mouse event after the start timestamp
+                                            let mouse_event = mouse_events
+                                                .iter()
+                                                .find(|event| event.timestamp >= time_elapsed);
+
+                                            if let Some(event) = mouse_event {
+                                                mouse_x = event.x as f64;
+                                                mouse_y = event.y as f64;
+                                            }
+
+                                            zooming_in2 = true;
+
+                                            // println!(
+                                            //     "setting mouse {} {} {} {}",
+                                            //     mouse_x,
+                                            //     current_width,
+                                            //     window_data.x
+                                            // );
+
+                                            // desktop coordinates to source frame pixels (DPI scaling, windowOffset)
+                                            let (frame_mouse_x, frame_mouse_y) = window_data
+                                                .mouse_to_frame(
+                                                    mouse_x,
+                                                    mouse_y,
+                                                    time_elapsed,
+                                                    decoder.width(),
+                                                    decoder.height(),
+                                                );
+
+                                            mouse_x = frame_mouse_x * upscale_factor as f64;
+                                            mouse_y = frame_mouse_y * upscale_factor as f64;
+
+                                            // source pixels to where the recording sits in the frame
+                                            mouse_x = (mouse_x
+                                                - (crop_left * upscale_factor) as f64)
+                                                * inset_width as f64
+                                                / crop_width as f64
+                                                + (inset_left * upscale_factor as i32) as f64;
+                                            mouse_y = (mouse_y
+                                                - (crop_top * upscale_factor) as f64)
+                                                * inset_height as f64
+                                                / crop_height as f64
+                                                + (inset_top * upscale_factor as i32) as f64;
+
+                                            // println!("Mouse {} {}\n", mouse_x, mouse_y);
+
+                                            // for testing
+                                            // mouse_x = zoom_width as f64 / 2.0;
+                                            // mouse_y = zoom_height as f64 / 2.0;
+
+                                            if !auto_zoom {
+                                                current_mouse_x = mouse_x;
+                                                current_mouse_y = mouse_y;
+                                            }
+
+                                            direction_x = mouse_x - current_mouse_x;
+                                            direction_y = mouse_y - current_mouse_y;
+                                        }
+                                    } else if time_elapsed >= end
+                                        && time_elapsed < end + animation_duration
+                                    {
+                                        if zooming_in2 {
+                                            zooming_in2 = false;
+
+                                            // current_mouse_x = zoom_width as f64 / 2.0;
+                                            // current_mouse_y = zoom_height as f64 / 2.0;
+                                        }
+                                    }
+                                }
+
+                                // clamp max
+                                let frame_width = upscaled_frame.width() as f64; // TODO: frame or bg_frame?
+                                let frame_height = upscaled_frame.height() as f64;
+
+                                // alternative clamp
+                                current_mouse_x = current_mouse_x.clamp(0.0, frame_width);
+                                current_mouse_y = current_mouse_y.clamp(0.0, frame_height);
+
+                                velocity_mouse_x = velocity_mouse_x.clamp(-mouse_x, frame_width);
+                                velocity_mouse_y = velocity_mouse_y.clamp(-mouse_y, frame_height);
+
+                                // println!(
+                                //     "Mouse Positions: {}, {} and {}, {}",
+                                //     mouse_x, mouse_y, current_mouse_x, current_mouse_y
+                                // );
+                                // println!("Smooth Info: {}, {}", smooth_height, smooth_width);
+
+                                // Center the zoom on the current mouse position
+                                let zoom_top = (current_mouse_y - zoom_height as f64 / 2.0);
+                                let zoom_left = (current_mouse_x - zoom_width as f64 / 2.0);
+
+                                // // Center the zoom on the current mouse position
+                                // let zoom_top = ((bg_frame.height() as f64 - current_mouse_y) / 2.0);
+                                // let zoom_left = ((bg_frame.width() as f64 - current_mouse_x) / 2.0);
+
+                                let zoom_top = zoom_top
+                                    .clamp(0.0, upscaled_frame.height() as f64 - zoom_height as f64)
+                                    .max(0.0) as u32;
+
+                                let zoom_left = zoom_left
+                                    .clamp(0.0, upscaled_frame.width() as f64 - zoom_width as f64)
+                                    .max(0.0)
+                                    as u32;
+
+                                // let target_zoom_top = (current_mouse_y - target_height as f64 / 2.0)
+                                //     .clamp(
+                                //         0.0,
+                                //         upscaled_frame.height() as f64 - target_height as f64,
+                                //     )
+                                //     .max(0.0)
+                                //     as f64;
+
+                                // let target_zoom_left = (current_mouse_x - target_width as f64 / 2.0)
+                                //     .clamp(0.0, upscaled_frame.width() as f64 - target_width as f64)
+                                //     .max(0.0)
+                                //     as f64;
+
+                                // // max clamps
+                                // let zoom_top = if zoom_top + zoom_height > upscaled_frame.height() {
+                                //     upscaled_frame.height() - zoom_height
+                                // } else {
+                                //     zoom_top
+                                // };
+
+                                // let zoom_left = if zoom_left + zoom_width > upscaled_frame.width() {
+                                //     upscaled_frame.width() - zoom_width
+                                // } else {
+                                //     zoom_left
+                                // };
+
+                                // clamp zoom_top and zoom_left
+                                // let zoom_top = zoom_top.min(upscaled_frame.height());
+                                // let zoom_left = zoom_left.min(upscaled_frame.width());
+
+                                // println!("Zoom Info: {}, {}", zoom_top, zoom_left);
+
+                                if enable_coord_smoothing {
+                                    let prev_zoom_top = smooth_zoom_top;
+                                    let prev_zoom_left = smooth_zoom_left;
+
+                                    if frame_index == 0 {
+                                        smooth_zoom_top = zoom_top as f64;
+                                        smooth_zoom_left = zoom_left as f64;
+                                    }
+
+                                    let frame_proportion =
+                                        bg_frame.height() as f64 / bg_frame.width() as f64;
+
+                                    let smoothing_factor = 0.1; // Adjust this value to change the amount of smoothing (0-1)
+                                    let top_change = (1.0 - smoothing_factor) * smooth_zoom_top; // TODO: need not just on side change but also on dimensions?
+                                    smooth_zoom_top = zoom_top as f64 + top_change;
+                                    smooth_zoom_left =
+                                        zoom_left as f64 + (top_change * frame_proportion);
+
+                                    // Ensure non-negative values
+                                    smooth_zoom_top = smooth_zoom_top.max(0.0);
+                                    smooth_zoom_left = smooth_zoom_left.max(0.0);
+
+                                    // Round and ensure even numbers
+                                    smooth_zoom_top = (smooth_zoom_top.round() / 2.0).floor() * 2.0;
+                                    smooth_zoom_left =
+                                        (smooth_zoom_left.round() / 2.0).floor() * 2.0;
+
+                                    // Apply max clamps
+                                    smooth_zoom_top = smooth_zoom_top
+                                        .min(bg_frame.height() as f64 - zoom_height as f64);
+                                    smooth_zoom_left = smooth_zoom_left
+                                        .min(bg_frame.width() as f64 - zoom_width as f64);
+
+                                    // Double-check even numbers (though this should be redundant now)
+                                    smooth_zoom_top = (smooth_zoom_top / 2.0).floor() * 2.0;
+                                    smooth_zoom_left = (smooth_zoom_left / 2.0).floor() * 2.0;
+
+                                    used_zoom_top = smooth_zoom_top;
+                                    used_zoom_left = smooth_zoom_left;
+                                } else {
+                                    // Ensure even numbers for non-smoothed zoom
+                                    used_zoom_top = ((zoom_top as f64) / 2.0).floor() * 2.0;
+                                    used_zoom_left = ((zoom_left as f64) / 2.0).floor() * 2.0;
+                                }
+
+                                let zoom_width = make_even(zoom_width);
+                                let zoom_height = make_even(zoom_height);
+
+                                let scaled_pts =
+                                    frame_index.rescale(encoder.time_base(), stream.time_base());
+
+                                let mut data_frame = frame::Video::new(
+                                    Pixel::from(bg_frame.format()),
+                                    // zoom_width,
+                                    // zoom_height,
+                                    bg_frame.width(),
+                                    bg_frame.height(),
+                                );
+
+                                data_frame.set_pts(Some(scaled_pts));
+
+                                // Create a scaling context for zooming
+                                let mut sws_ctx_zoom = Context::get(
+                                    bg_frame.format(),
+                                    zoom_width,
+                                    zoom_height,
+                                    data_frame.format(),
+                                    bg_frame.width(),
+                                    bg_frame.height(),
+                                    // upscaled_frame.format(),
+                                    // upscaled_frame.width(),
+                                    // upscaled_frame.height(),
+                                    Flags::BILINEAR,
+                                )
+                                .expect("Failed to create scaling context");
+
+                                // Calculate zoom strides
+                                let zoom_y_stride = zoom_width as usize;
+                                let zoom_u_stride = zoom_width as usize / 2;
+                                let zoom_v_stride = zoom_width as usize / 2;
+
+                                // Prepare linesize (stride) array for the zoomed data
+                                let linesize: [c_int; 3] = [
+                                    zoom_y_stride as c_int,
+                                    zoom_u_stride as c_int,
+                                    zoom_v_stride as c_int,
+                                ];
+
+                                // Get strides
+                                let y_stride = bg_frame.stride(0);
+                                let u_stride = bg_frame.stride(1);
+                                let v_stride = bg_frame.stride(2);
+
+                                // Prepare linesize (stride) array
+                                let linesize2: [c_int; 3] =
+                                    [y_stride as c_int, u_stride as c_int, v_stride as c_int];
+
+                                // println!("linesizes {:?} {:?}", linesize, linesize2);
+
+                                // let y_stride = data_frame.stride(0); // For Y plane
+                                // let u_stride = data_frame.stride(1); // For U plane
+                                // let v_stride = data_frame.stride(2); // For V plane
+
+                                // let linesize: [c_int; 3] =
+                                //     [y_stride as c_int, u_stride as c_int, v_stride as c_int];
+
+                                // Get pointers to the zoomed portion in the background frame
+                                let used_zoom_top_int = used_zoom_top as usize;
+                                let used_zoom_left_int = used_zoom_left as usize;
+
+                                // let mut zoom_data: [*const u8; 3] = [std::ptr::null(); 3];
+
+                                // let start_y =
+                                //     used_zoom_top_int * bg_frame.stride(0) + used_zoom_left_int;
+
+                                // zoom_data[0] = bg_frame.data(0)[start_y..].as_ptr();
+
+                                // if used_zoom_top_int % 2 == 0 && used_zoom_left_int % 2 == 0 {
+                                //     let start_u = (used_zoom_top_int / 2) * bg_frame.stride(1)
+                                //         + (used_zoom_left_int / 2);
+                                //     zoom_data[1] = bg_frame.data(1)[start_u..].as_ptr();
+
+                                //     let start_v = (used_zoom_top_int / 2) * bg_frame.stride(2)
+                                //         + (used_zoom_left_int / 2);
+                                //     zoom_data[2] = bg_frame.data(2)[start_v..].as_ptr();
+                                // } else {
+                                //     zoom_data[1] = std::ptr::null();
+                                //     zoom_data[2] = std::ptr::null();
+                                // }
+
+                                let mut zoom_data: [*const u8; 3] = [std::ptr::null(); 3];
+
+                                // Convert float coordinates to integers, accounting for upscaling
+                                let used_zoom_top_int = used_zoom_top as usize;
+                                let used_zoom_left_int = used_zoom_left as usize;
+
+                                // Prepare Y plane
+                                let start_y = used_zoom_top_int * upscaled_frame.stride(0)
+                                    + used_zoom_left_int;
+                                zoom_data[0] = upscaled_frame.data(0)[start_y..].as_ptr();
+
+                                // Prepare U and V planes
+                                // Note: We need to ensure that these values are even after upscaling
+                                if used_zoom_top_int % 2 == 0 && used_zoom_left_int % 2 == 0 {
+                                    let start_u = (used_zoom_top_int / 2)
+                                        * upscaled_frame.stride(1)
+                                        + (used_zoom_left_int / 2);
+                                    zoom_data[1] = upscaled_frame.data(1)[start_u..].as_ptr();
+
+                                    let start_v = (used_zoom_top_int / 2)
+                                        * upscaled_frame.stride(2)
+                                        + (used_zoom_left_int / 2);
+                                    zoom_data[2] = upscaled_frame.data(2)[start_v..].as_ptr();
+                                } else {
+                                    // Handle the case where the upscaled coordinates are not even
+                                    // This might involve some form of interpolation or rounding
+                                    let adjusted_top = (used_zoom_top_int / 2) * 2;
+                                    let adjusted_left = (used_zoom_left_int / 2) * 2;
+
+                                    let start_u = (adjusted_top / 2) * upscaled_frame.stride(1)
+                                        + (adjusted_left / 2);
+                                    zoom_data[1] = upscaled_frame.data(1)[start_u..].as_ptr();
+
+                                    let start_v = (adjusted_top / 2) * upscaled_frame.stride(2)
+                                        + (adjusted_left / 2);
+                                    zoom_data[2] = upscaled_frame.data(2)[start_v..].as_ptr();
+                                }
+
+                                unsafe {
+                                    sws_scale(
+                                        sws_ctx_zoom.as_mut_ptr(),
+                                        zoom_data.as_ptr() as *const *const u8,
+                                        (*upscaled_frame.as_mut_ptr()).linesize.as_ptr(),
+                                        // linesize.as_ptr(),
+                                        0,
+                                        zoom_height as c_int,
+                                        (*data_frame.as_mut_ptr()).data.as_ptr() as *mut *mut u8,
+                                        (*data_frame.as_mut_ptr()).linesize.as_ptr(),
+                                    );
+                                }
+
+                                // *** Webcam Overlay *** //
+
+                                if let (Some(webcam_info), Some(webcam_reader)) =
+                                    (&config.webcam, webcam_reader.as_mut())
+                                {
+                                    if let Some(webcam_frame) = webcam_reader.frame_at(time_elapsed)
+                                    {
+                                        let zoom_progress = ((1.0
+                                            - zoom_width as f64 / upscaled_width as f64)
+                                            / WEBCAM_ZOOM_RANGE)
+                                            .clamp(0.0, 1.0);
+                                        let cursor_x = (current_mouse_x - used_zoom_left)
+                                            * data_frame.width() as f64
+                                            / zoom_width as f64;
+
+                                        let (left, top, width, height) = webcam_rect(
+                                            webcam_info,
+                                            data_frame.width(),
+                                            data_frame.height(),
+                                            webcam_frame.width() as f64
+                                                / webcam_frame.height() as f64,
+                                            zoom_progress,
+                                            cursor_x,
+                                        );
+
+                                        composite_webcam(
+                                            &mut data_frame,
+                                            webcam_frame,
+                                            webcam_info,
+                                            left,
+                                            top,
+                                            width,
+                                            height,
+                                        )?;
+                                    }
+                                }
+
+                                // *** Overlays *** //
+
+                                overlays.render(&mut data_frame, time_elapsed);
+
+                                // Send the zoom_frame to the encoder
+                                encoder.send_frame(&data_frame).map_err(|e| {
+                                    format!("Error sending frame for encoding: {}", e)
+                                })?;
+
+                                // Receive and write encoded packets
+                                'encode_loop: loop {
+                                    let mut output_packet = ffmpeg::Packet::empty();
+                                    match encoder.receive_packet(&mut output_packet) {
+                                        Ok(_) => {
+                                            output_packet.set_stream(0); // Set the stream index
+                                            output_packet
+                                                .write_interleaved(&mut output_context)
+                                                .map_err(|e| {
+                                                    format!("Error writing packet: {}", e)
+                                                })?;
+                                            successful_frame_index += 1;
+                                        }
+                                        Err(ffmpeg::Error::Other {
+                                            errno: ffmpeg::error::EAGAIN,
+                                        }) => {
+                                            // No more packets to receive, break the loop
+                                            break 'encode_loop;
+                                        }
+                                        Err(e) => {
+                                            return Err(format!(
+                                                "Error receiving encoded packet: {}",
+                                                e
+                                            ));
+                                        }
+                                    }
+                                }
+
+                                // The zoom_frame will be automatically dropped here when it goes out of scope
+                            }
+                            Err(ffmpeg::Error::Other {
+                                errno: ffmpeg::error::EAGAIN,
+                            }) => {
+                                break 'decode_loop;
+                            }
+                            Err(e) => return Err(format!("Error receiving decoded frame: {}", e)),
+                        }
+                    }
+
+                    // let duration2 = start2.elapsed();
+                    // println!("start2 Time elapsed: {:?}", duration2);
+                }
+            }
+            // Some(Err(e)) => return Err(format!("Error reading packet: {}", e)),
+            None => break 'main_loop, // End of stream
+        }
+
+        // ... (progress update code)
+        frame_index += 1;
+    }
+
+    // After the main loop
+    output_context
+        .write_trailer()
+        .map_err(|e| format!("Error occurred when writing trailer: {}", e))?;
+
+    // closed before the bumpers replace it
+    drop(output_context);
+
+    add_bumpers(&config, canvas_width, canvas_height)?;
+
+    Ok("Video transformation completed successfully".to_string())
+}
+
+// *** Intro / Outro *** //
+
+const CARD_FPS: i32 = 60; // same as the export
+const CARD_TITLE_SIZE: f64 = 96.0;
+const CARD_SUBTITLE_SIZE: f64 = 48.0;
+
+fn video_duration_ms(path: &str) -> Result<i32, String> {
+    let input = ffmpeg::format::input(&path)
+        .map_err(|e| format!("Could not open video {}: {}", path, e))?;
+
+    Ok((input.duration().max(0) * 1000 / ffmpeg::ffi::AV_TIME_BASE as i64) as i32)
+}
+
+// the planes of a yuv420p frame without their stride padding, as ffmpeg's rawvideo expects
+fn write_raw_frame(output: &mut impl Write, frame: &Video) -> Result<(), String> {
+    for plane in 0..3 {
+        let shift = if plane == 0 { 0 } else { 1 };
+        let stride = frame.stride(plane);
+        let width = (frame.width() >> shift) as usize;
+        let data = frame.data(plane);
+
+        for row in 0..(frame.height() >> shift) as usize {
+            output
+                .write_all(&data[row * stride..row * stride + width])
+                .map_err(|e| format!("Failed to write card frame: {}", e))?;
+        }
+    }
+
+    Ok(())
+}
+
+// the title over the export's first background, encoded to output_path by the ffmpeg cli
+fn render_title_card(
+    config: &Config,
+    title: &str,
+    subtitle: Option<&str>,
+    duration: i32,
+    size: (u32, u32),
+    output_path: &str,
+) -> Result<(), String> {
+    let (width, height) = size;
+    let backgrounds = BackgroundTimeline::new(&config.background_info, width, height)?;
+
+    let line = |text: &str, bold: bool, size: f64, y: f64| Overlay {
+        start: 0,
+        end: duration,
+        fade_in: 0,
+        fade_out: 0,
+        shape: OverlayShape::Text {
+            text: text.to_string(),
+            bold,
+            font_file: None,
+            size,
+            color: default_overlay_color(),
+            background: None,
+            background_opacity: default_text_background_opacity(),
+            x: 0.5,
+            y,
+        },
+    };
+
+    let texts = match subtitle {
+        Some(subtitle) => vec![
+            line(title, true, CARD_TITLE_SIZE, 0.45),
+            line(subtitle, false, CARD_SUBTITLE_SIZE, 0.58),
+        ],
+        None => vec![line(title, true, CARD_TITLE_SIZE, 0.5)],
+    };
+    let overlays = OverlayRenderer::new(&texts)?;
+
+    // there's no recording under a card, a blurred background just goes black
+    let blank = blank_frame(width, height);
+
+    let mut ffmpeg_process = Command::new("ffmpeg")
+        .args(["-y", "-f", "rawvideo", "-pix_fmt", "yuv420p", "-s"])
+        .arg(format!("{}x{}", width, height))
+        .args(["-r", &CARD_FPS.to_string(), "-i", "-"])
+        .args(["-c:v", "libx264", "-pix_fmt", "yuv420p", "-crf", "18"])
+        .arg(output_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+
+    let mut stdin = ffmpeg_process
+        .stdin
+        .take()
+        .ok_or("Could not open the ffmpeg input")?;
+
+    for frame_index in 0..duration * CARD_FPS / 1000 {
+        let time_ms = frame_index * 1000 / CARD_FPS;
+        let mut frame = backgrounds.render(&blank, time_ms)?;
+        overlays.render(&mut frame, time_ms);
+
+        write_raw_frame(&mut stdin, &frame)?;
+    }
+
+    drop(stdin);
+
+    let status = ffmpeg_process.wait().map_err(|e| e.to_string())?;
+
+    if !status.success() {
+        return Err(format!("ffmpeg could not encode the title card {}", title));
+    }
+
+    Ok(())
+}
+
+// joins the intro, the rendered output and the outro into config.output_file, crossfading
+// between them. Every part is fitted to the canvas at the export's frame rate
+fn add_bumpers(config: &Config, width: u32, height: u32) -> Result<(), String> {
+    let bumpers = &config.bumpers;
+
+    if bumpers.intro.is_none() && bumpers.outro.is_none() {
+        return Ok(());
+    }
+
+    let output_dir = Path::new(&config.output_file)
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+    let mut cards = Vec::new();
+
+    let mut prepare = |bumper: &Bumper, name: &str| -> Result<String, String> {
+        match bumper {
+            Bumper::Clip { path } => Ok(path.clone()),
+            Bumper::TitleCard {
+                title,
+                subtitle,
+                duration,
+            } => {
+                let card_path = output_dir
+                    .join(format!("{}_card.mp4", name))
+                    .to_string_lossy()
+                    .to_string();
+
+                render_title_card(
+                    config,
+                    title,
+                    subtitle.as_deref(),
+                    *duration,
+                    (width, height),
+                    &card_path,
+                )?;
+
+                cards.push(card_path.clone());
+                Ok(card_path)
+            }
+        }
+    };
+
+    let mut parts = Vec::new();
+
+    if let Some(intro) = &bumpers.intro {
+        parts.push(prepare(intro, "intro")?);
+    }
+
+    parts.push(config.output_file.clone());
+
+    if let Some(outro) = &bumpers.outro {
+        parts.push(prepare(outro, "outro")?);
+    }
+
+    let durations = parts
+        .iter()
+        .map(|part| video_duration_ms(part))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // a fade can't be longer than the parts it joins
+    let shortest = durations.iter().copied().min().unwrap_or(0);
+    let fade = bumpers.crossfade.clamp(0, shortest / 2);
+
+    let mut filter = String::new();
+
+    for index in 0..parts.len() {
+        filter.push_str(&format!(
+            "[{i}:v]scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={fps},format=yuv420p[v{i}];",
+            i = index,
+            w = width,
+            h = height,
+            fps = CARD_FPS
+        ));
+    }
+
+    let output_label = if fade > 0 {
+        let mut last = "v0".to_string();
+        let mut length = durations[0];
+
+        for (index, duration) in durations.iter().enumerate().skip(1) {
+            let label = format!("x{}", index);
+
+            filter.push_str(&format!(
+                "[{}][v{}]xfade=transition=fade:duration={}:offset={}[{}];",
+                last,
+                index,
+                fade as f64 / 1000.0,
+                (length - fade) as f64 / 1000.0,
+                label
+            ));
+
+            length += duration - fade;
+            last = label;
+        }
+
+        last
+    } else {
+        for index in 0..parts.len() {
+            filter.push_str(&format!("[v{}]", index));
+        }
+        filter.push_str(&format!("concat=n={}:v=1:a=0[joined];", parts.len()));
+
+        "joined".to_string()
+    };
+
+    // no trailing separator
+    filter.pop();
+
+    let joined_path = output_dir.join("output_joined.mp4");
+
+    let mut command = Command::new("ffmpeg");
+    command.arg("-y");
+
+    for part in &parts {
+        command.arg("-i").arg(part);
+    }
+
+    let status = command
+        .arg("-filter_complex")
+        .arg(&filter)
+        .args(["-map", &format!("[{}]", output_label), "-an"])
+        .args(["-c:v", "libx264", "-crf", "18"])
+        .arg(&joined_path)
+        .status()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+
+    for card in &cards {
+        let _ = fs::remove_file(card);
+    }
+
+    if !status.success() {
+        return Err("ffmpeg could not join the intro and outro".to_string());
+    }
+
+    fs::rename(&joined_path, &config.output_file).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn transform_video(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    settings: ExportSettings,
+) -> Result<String, String> {
+    let save_path = app_handle.path_resolver().app_data_dir().unwrap();
+    let project_path = save_path.join("projects").join(&project_id);
+
+    let output_path = project_path.join("output.mp4");
+    let compressed_path = project_path.join("output_compressed.mp4");
+
+    let config_path = project_path.join("config.json");
+
+    let config_path_str = config_path.to_string_lossy().to_string();
+    let output_path_str = output_path.to_string_lossy().to_string();
+    let compressed_path_str = compressed_path.to_string_lossy().to_string();
+
+    let config = Config {
+        duration: settings.duration.ok_or("No duration to export")?,
+        // relative to config.json so the project folder can be moved or copied
+        positions_file: "mousePositions.json".to_string(),
+        source_file: "sourceData.json".to_string(),
+        input_file: "capture.mp4".to_string(),
+        output_file: "output.mp4".to_string(),
+        zoom_info: settings.zoom_info,
+        background_info: settings.background_info,
+        // only projects recorded with a webcam have one to overlay
+        webcam: settings
+            .webcam_info
+            .filter(|_| project_path.join("webcam.mp4").exists()),
+        frame: settings.frame_style.unwrap_or_default(),
+        canvas: settings.canvas.unwrap_or_default(),
+        reframe: settings.reframe.unwrap_or_default(),
+        overlays: settings.overlays.unwrap_or_default(),
+        bumpers: settings.bumpers.unwrap_or_default(),
+        redactions: settings.redactions.unwrap_or_default(),
+        // keyboard events are only recorded since the keystroke HUD was added
+        keystrokes: settings
+            .keystroke_info
+            .filter(|_| project_path.join("keyEvents.json").exists()),
+    };
+
+    fs::write(config_path, serde_json::to_string_pretty(&config).unwrap())
+        .map_err(|e| e.to_string())?;
+
+    thread::spawn(move || {
+        do_transform_video(config_path_str).expect("Couldn't transform video");
+
+        let status = Command::new("ffmpeg")
+            .arg("-i")
+            .arg(output_path_str)
+            .arg("-vcodec")
+            .arg("libx264") // or "libx265" for H.265/HEVC compression
+            .arg("-crf")
+            .arg("5")
+            .arg(compressed_path_str)
+            .status()
+            .expect("Failed to execute ffmpeg command");
+
+        if status.success() {
+            println!("Video compressed successfully!");
+            app_handle.emit_all("video-export", "success").unwrap();
+        } else {
+            eprintln!("Error compressing the video.");
+            app_handle.emit_all("video-export", "failure").unwrap();
+        }
+    });
+
+    Ok("Transformation has been initiated in thread".to_string())
+}
+
+#[tauri::command]
+fn create_project(app_handle: tauri::AppHandle) -> Result<serde_json::Value, String> {
+    let current_project_id = Uuid::new_v4().to_string();
+    let save_path = app_handle.path_resolver().app_data_dir().unwrap();
+    let project_dir = save_path.join("projects").join(&current_project_id);
+
+    fs::create_dir_all(&project_dir).map_err(|e| e.to_string())?;
+
+    let meta = ProjectMeta {
+        name: "Untitled Project".to_string(),
+        created_at: now_millis(),
+    };
+
+    write_project_meta(&project_dir, &meta)?;
+
+    Ok(json!({ "projectId": current_project_id }))
+}
+
+// *** Project Library *** //
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+struct ProjectMeta {
+    name: String,
+    created_at: u64, // ms since unix epoch
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct ProjectInfo {
+    project_id: String,
+    name: String,
+    created_at: u64,
+    duration: Option<i64>, // ms, None if there is no capture yet
+    size_on_disk: u64,
+    thumbnail: Option<String>, // sunshot:// url, see project_media_url
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn projects_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let save_path = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or("Failed to get app data directory")?;
+
+    Ok(save_path.join("projects"))
+}
+
+fn project_dir(app_handle: &tauri::AppHandle, project_id: &str) -> Result<PathBuf, String> {
+    // project ids are uuids, don't let anything else escape the projects folder
+    if project_id.is_empty() || project_id.contains(['/', '\\']) || project_id.contains("..") {
+        return Err(format!("Invalid project id: {}", project_id));
+    }
+
+    let project_path = projects_dir(app_handle)?.join(project_id);
+
+    if !project_path.is_dir() {
+        return Err(format!("Project not found: {}", project_id));
+    }
+
+    Ok(project_path)
+}
+
+fn read_project_meta(project_path: &Path) -> ProjectMeta {
+    let meta = fs::read_to_string(project_path.join("project.json"))
+        .ok()
+        .and_then(|json_str| serde_json::from_str::<ProjectMeta>(&json_str).ok());
+
+    match meta {
+        Some(meta) => meta,
+        None => {
+            // projects made before project.json existed
+            let created_at = fs::metadata(project_path)
+                .and_then(|m| m.created().or_else(|_| m.modified()))
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0);
+
+            ProjectMeta {
+                name: "Untitled Project".to_string(),
+                created_at,
+            }
+        }
+    }
+}
+
+fn write_project_meta(project_path: &Path, meta: &ProjectMeta) -> Result<(), String> {
+    fs::write(
+        project_path.join("project.json"),
+        serde_json::to_string_pretty(meta).unwrap(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let mut size = 0;
+
+    if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.flatten() {
+            match entry.metadata() {
+                Ok(metadata) if metadata.is_dir() => size += dir_size(&entry.path()),
+                Ok(metadata) => size += metadata.len(),
+                Err(_) => {}
+            }
+        }
+    }
+
+    size
+}
+
+fn copy_dir_all(src: &Path, dst: &Path) -> Result<(), String> {
+    fs::create_dir_all(dst).map_err(|e| e.to_string())?;
+
+    for entry in fs::read_dir(src).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let target = dst.join(entry.file_name());
+
+        if entry.file_type().map_err(|e| e.to_string())?.is_dir() {
+            copy_dir_all(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target).map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn capture_duration(capture_path: &Path) -> Option<i64> {
+    if !capture_path.exists() {
+        return None;
+    }
+
+    ffmpeg::init().ok()?;
+
+    let input_context = ffmpeg::format::input(&capture_path).ok()?;
+    let duration = input_context.duration();
+
+    if duration <= 0 {
+        return None;
+    }
+
+    // AV_TIME_BASE is microseconds
+    Some(duration.rescale(ffmpeg::rescale::TIME_BASE, Rational(1, 1000)))
+}
+
+// a still from the video as a jpeg, false if ffmpeg couldn't make one. Taken a second in,
+// or halfway through clips shorter than two seconds
+fn generate_thumbnail(video_path: &Path, thumbnail_path: &Path) -> bool {
+    let seek_ms = capture_duration(video_path)
+        .map(|duration| (duration / 2).min(1000))
+        .unwrap_or(0);
+
+    let status = Command::new("ffmpeg")
+        .arg("-nostdin")
+        .arg("-y")
+        .arg("-ss")
+        .arg(format!("{:.3}", seek_ms as f64 / 1000.0))
+        .arg("-i")
+        .arg(video_path)
+        .arg("-frames:v")
+        .arg("1")
+        .arg("-vf")
+        .arg("scale=320:-2")
+        .arg(thumbnail_path)
+        .status();
+
+    match status {
+        Ok(status) if status.success() && thumbnail_path.exists() => true,
+        _ => {
+            eprintln!("Error generating thumbnail for {:?}", video_path);
+            false
+        }
+    }
+}
+
+// modified time (ms) and size, enough to tell a video was replaced
+fn file_stamp(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_millis() as u64;
+
+    Some((modified, metadata.len()))
+}
+
+// latest modified time (ms) of a folder and the folders inside it, changes whenever a file
+// is added, removed or replaced anywhere in it
+fn folder_stamp(path: &Path) -> u64 {
+    let modified = file_stamp(path).map(|(modified, _)| modified).unwrap_or(0);
+
+    fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+        .map(|entry| folder_stamp(&entry.path()))
+        .fold(modified, u64::max)
+}
+
+// what the library shows of a project, kept in libraryCache.json so listing doesn't probe
+// and run ffmpeg on every project each time. The thumbnail and duration are worked out again
+// when capture.mp4 or output.mp4 change, the size when anything in the project does
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+struct LibraryCache {
+    capture: Option<(u64, u64)>,
+    output: Option<(u64, u64)>,
+    duration: Option<i64>,
+    has_thumbnail: bool,
+    folder: u64,
+    size_on_disk: u64,
+}
+
+fn library_cache(project_path: &Path) -> LibraryCache {
+    let capture_path = project_path.join("capture.mp4");
+    let output_path = project_path.join("output.mp4");
+    let thumbnail_path = project_path.join("thumbnail.jpg");
+    let cache_path = project_path.join("libraryCache.json");
+
+    let capture = file_stamp(&capture_path);
+    let output = file_stamp(&output_path);
+
+    let cached = fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|json_str| serde_json::from_str::<LibraryCache>(&json_str).ok());
+
+    let videos_cached = cached.as_ref().filter(|cache| {
+        cache.capture == capture
+            && cache.output == output
+            && (!cache.has_thumbnail || thumbnail_path.exists())
+    });
+
+    let (duration, has_thumbnail) = match videos_cached {
+        Some(cache) => (cache.duration, cache.has_thumbnail),
+        None => {
+            // the export shows the project as it was edited, the raw capture has to do until then
+            let thumbnail_source = match (output, capture) {
+                (Some(_), _) => Some(&output_path),
+                (None, Some(_)) => Some(&capture_path),
+                _ => None,
+            };
+
+            (
+                capture_duration(&capture_path),
+                thumbnail_source
+                    .map(|video_path| generate_thumbnail(video_path, &thumbnail_path))
+                    .unwrap_or(false),
+            )
+        }
+    };
+
+    // stamped after the thumbnail is written so it doesn't count as a change next time
+    let folder = folder_stamp(project_path);
+
+    let size_on_disk = match &cached {
+        Some(cache) if videos_cached.is_some() && cache.folder == folder => cache.size_on_disk,
+        _ => dir_size(project_path),
+    };
+
+    let cache = LibraryCache {
+        capture,
+        output,
+        duration,
+        has_thumbnail,
+        folder,
+        size_on_disk,
+    };
+
+    if cached.as_ref() != Some(&cache) {
+        if let Err(e) = fs::write(&cache_path, serde_json::to_string_pretty(&cache).unwrap()) {
+            eprintln!(
+                "Could not write library cache for {:?}: {}",
+                project_path, e
+            );
+        }
+    }
+
+    cache
+}
+
+// runs off the main thread, a library with new recordings has ffmpeg work to do
+#[tauri::command]
+async fn list_projects(app_handle: tauri::AppHandle) -> Result<Vec<ProjectInfo>, String> {
+    let projects_path = projects_dir(&app_handle)?;
+
+    tauri::async_runtime::spawn_blocking(move || read_projects(&projects_path))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn read_projects(projects_path: &Path) -> Result<Vec<ProjectInfo>, String> {
+    if !projects_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut projects: Vec<ProjectInfo> = Vec::new();
+
+    for entry in fs::read_dir(projects_path).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let project_path = entry.path();
+
+        if !project_path.is_dir() {
+            continue;
+        }
+
+        let project_id = entry.file_name().to_string_lossy().to_string();
+        let meta = read_project_meta(&project_path);
+        let cache = library_cache(&project_path);
+
+        // the query makes the webview load a regenerated thumbnail instead of its cached one
+        let thumbnail = cache.has_thumbnail.then(|| {
+            let version = cache.output.or(cache.capture).unwrap_or_default().0;
+            format!(
+                "{}?v={}",
+                project_media_url(&project_id, "thumbnail.jpg"),
+                version
+            )
+        });
+
+        projects.push(ProjectInfo {
+            project_id,
+            name: meta.name,
+            created_at: meta.created_at,
+            duration: cache.duration,
+            size_on_disk: cache.size_on_disk,
+            thumbnail,
+        });
+    }
+
+    // newest first
+    projects.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+
+    Ok(projects)
+}
+
+#[tauri::command]
+fn rename_project(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    name: String,
+) -> Result<bool, String> {
+    let project_path = project_dir(&app_handle, &project_id)?;
+
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Project name cannot be empty".to_string());
+    }
+
+    let mut meta = read_project_meta(&project_path);
+    meta.name = name.to_string();
+
+    write_project_meta(&project_path, &meta)?;
+
+    Ok(true)
+}
+
+#[tauri::command]
+fn duplicate_project(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+) -> Result<serde_json::Value, String> {
+    let project_path = project_dir(&app_handle, &project_id)?;
+
+    let new_project_id = Uuid::new_v4().to_string();
+    let new_project_path = projects_dir(&app_handle)?.join(&new_project_id);
+
+    if let Err(e) = copy_dir_all(&project_path, &new_project_path) {
+        // don't leave half a project behind
+        let _ = fs::remove_dir_all(&new_project_path);
+        return Err(format!("Failed to duplicate project: {}", e));
+    }
+
+    let meta = read_project_meta(&project_path);
+    let new_meta = ProjectMeta {
+        name: format!("{} (Copy)", meta.name),
+        created_at: now_millis(),
+    };
+
+    write_project_meta(&new_project_path, &new_meta)?;
+
+    Ok(json!({ "projectId": new_project_id }))
+}
+
+#[tauri::command]
+fn delete_project(app_handle: tauri::AppHandle, project_id: String) -> Result<bool, String> {
+    let project_path = project_dir(&app_handle, &project_id)?;
+
+    trash::delete(&project_path).map_err(|e| format!("Failed to move project to trash: {}", e))?;
+
+    Ok(true)
+}
+
+// *** Editor State *** //
+
+const MAX_HISTORY_SNAPSHOTS: usize = 50;
+
+// mirrors the frontend EditorContext tracks so they round trip untouched
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct ZoomTrackState {
+    id: String,
+    name: String,
+    start: i32,
+    end: i32,
+    zoom_factor: serde_json::Value,
+    easing: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct VideoTrackState {
+    id: String,
+    name: String,
+    start: i32,
+    end: i32,
+    gradient: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    webcam: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    background_animation: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    frame: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    canvas: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reframe: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    overlays: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bumpers: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    redactions: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keystrokes: Option<serde_json::Value>,
+}
+
+// the values last handed to transform_video
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+struct ExportSettings {
+    duration: Option<i32>,
+    zoom_info: Vec<ZoomInfo>,
+    background_info: Vec<BackgroundInfo>,
+    #[serde(default)]
+    webcam_info: Option<WebcamInfo>,
+    #[serde(default)]
+    frame_style: Option<FrameStyle>,
+    #[serde(default)]
+    canvas: Option<Canvas>,
+    #[serde(default)]
+    reframe: Option<Reframe>,
+    #[serde(default)]
+    overlays: Option<Vec<Overlay>>,
+    #[serde(default)]
+    bumpers: Option<Bumpers>,
+    #[serde(default)]
+    redactions: Option<Vec<Redaction>>,
+    #[serde(default)]
+    keystroke_info: Option<KeystrokeInfo>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct EditorState {
+    video_track: Option<VideoTrackState>,
+    zoom_tracks: Vec<ZoomTrackState>,
+    #[serde(default)]
+    export_settings: ExportSettings,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+struct EditorHistory {
+    snapshots: Vec<EditorState>,
+    cursor: usize, // index of the snapshot currently in editorState.json
+}
+
+impl EditorHistory {
+    // false when state is what the cursor already points at, autosave fires on every change
+    fn push(&mut self, state: EditorState) -> bool {
+        if self.snapshots.get(self.cursor) == Some(&state) {
+            return false;
+        }
+
+        // a new edit after undoing drops the redo branch
+        if !self.snapshots.is_empty() {
+            self.snapshots.truncate(self.cursor + 1);
+        }
+
+        self.snapshots.push(state);
+
+        if self.snapshots.len() > MAX_HISTORY_SNAPSHOTS {
+            let overflow = self.snapshots.len() - MAX_HISTORY_SNAPSHOTS;
+            self.snapshots.drain(0..overflow);
+        }
+
+        self.cursor = self.snapshots.len() - 1;
+
+        true
+    }
+
+    fn undo(&mut self) -> Option<EditorState> {
+        if self.cursor == 0 || self.snapshots.is_empty() {
+            return None;
+        }
+
+        self.cursor -= 1;
+        Some(self.snapshots[self.cursor].clone())
+    }
+
+    fn redo(&mut self) -> Option<EditorState> {
+        if self.cursor + 1 >= self.snapshots.len() {
+            return None;
+        }
+
+        self.cursor += 1;
+        Some(self.snapshots[self.cursor].clone())
+    }
+}
+
+fn read_editor_state(project_path: &Path) -> Result<Option<EditorState>, String> {
+    let state_path = project_path.join("editorState.json");
+
+    if !state_path.exists() {
+        return Ok(None);
+    }
+
+    let json_str = fs::read_to_string(state_path).map_err(|e| e.to_string())?;
+    let state = serde_json::from_str(&json_str)
+        .map_err(|e| format!("Failed to parse editor state: {}", e))?;
+
+    Ok(Some(state))
+}
+
+fn write_editor_state(project_path: &Path, state: &EditorState) -> Result<(), String> {
+    // write then rename so a crash mid-autosave can't leave a truncated file
+    let tmp_path = project_path.join("editorState.json.tmp");
+
+    fs::write(&tmp_path, serde_json::to_string_pretty(state).unwrap())
+        .map_err(|e| e.to_string())?;
+    fs::rename(&tmp_path, project_path.join("editorState.json")).map_err(|e| e.to_string())
+}
+
+fn read_editor_history(project_path: &Path) -> EditorHistory {
+    fs::read_to_string(project_path.join("editorHistory.json"))
+        .ok()
+        .and_then(|json_str| serde_json::from_str(&json_str).ok())
+        .unwrap_or_default()
+}
+
+fn write_editor_history(project_path: &Path, history: &EditorHistory) -> Result<(), String> {
+    fs::write(
+        project_path.join("editorHistory.json"),
+        serde_json::to_string(history).unwrap(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn save_editor_state(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    editor_state: EditorState,
+) -> Result<bool, String> {
+    let project_path = project_dir(&app_handle, &project_id)?;
+
+    let mut history = read_editor_history(&project_path);
+
+    if history.push(editor_state.clone()) {
+        write_editor_history(&project_path, &history)?;
+    }
+
+    write_editor_state(&project_path, &editor_state)?;
+
+    Ok(true)
+}
+
+#[tauri::command]
+fn load_editor_state(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+) -> Result<Option<EditorState>, String> {
+    let project_path = project_dir(&app_handle, &project_id)?;
+
+    read_editor_state(&project_path)
+}
+
+#[tauri::command]
+fn undo_editor_state(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+) -> Result<Option<EditorState>, String> {
+    let project_path = project_dir(&app_handle, &project_id)?;
+
+    let mut history = read_editor_history(&project_path);
+
+    let Some(state) = history.undo() else {
+        return Ok(None);
+    };
+
+    write_editor_history(&project_path, &history)?;
+    write_editor_state(&project_path, &state)?;
+
+    Ok(Some(state))
+}
+
+#[tauri::command]
+fn redo_editor_state(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+) -> Result<Option<EditorState>, String> {
+    let project_path = project_dir(&app_handle, &project_id)?;
+
+    let mut history = read_editor_history(&project_path);
+
+    let Some(state) = history.redo() else {
+        return Ok(None);
+    };
+
+    write_editor_history(&project_path, &history)?;
+    write_editor_state(&project_path, &state)?;
+
+    Ok(Some(state))
+}
+
+// *** Project Bundles *** //
+
+const BUNDLE_FORMAT_VERSION: u32 = 2; // 2 added files in subfolders like backgrounds/
+const BUNDLE_OUTPUT_FILES: [&str; 2] = ["output.mp4", "output_compressed.mp4"];
+const BUNDLE_SKIPPED_FILES: [&str; 3] = [
+    "capture_pre.mp4",
+    "editorState.json.tmp",
+    "libraryCache.json", // only valid for the files on this machine
+];
+// files the config uses from outside the project are copied in here
+const BUNDLE_ASSETS_DIR: &str = "assets";
+// recorded keys can be sensitive even after filtering, they only leave with an opt in
+const BUNDLE_KEYSTROKE_FILE: &str = "keyEvents.json";
+
+#[derive(Deserialize, Serialize, Debug)]
+struct BundleFile {
+    path: String,
+    size: u64,
+    sha256: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct BundleManifest {
+    format_version: u32,
+    project_id: String,
+    meta: ProjectMeta,
+    exported_at: u64,
+    files: Vec<BundleFile>,
+}
+
+// every file under dir with its bundle path, "/" separated and relative to the project
+fn collect_bundle_files(
+    dir: &Path,
+    prefix: &str,
+    files: &mut Vec<(String, PathBuf)>,
+) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())?.flatten() {
+        let path = entry.path();
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+
+        if path.is_dir() {
+            collect_bundle_files(&path, &format!("{}/", name), files)?;
+        } else if path.is_file() {
+            files.push((name, path));
+        }
+    }
+
+    Ok(())
+}
+
+// manifest paths are relative and "/" separated, subfolders like backgrounds/ are fine
+// but nothing may point outside the project
+fn is_valid_bundle_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.contains('\\')
+        && !path.contains(':')
+        && path
+            .split('/')
+            .all(|part| !part.is_empty() && part != "." && part != "..")
+}
+
+// copies everything from reader to writer, returning the byte count and sha256 hex digest
+fn copy_and_hash<R: Read, W: Write>(reader: &mut R, writer: &mut W) -> io::Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0;
+
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        writer.write_all(&buf[..n])?;
+        size += n as u64;
+    }
+
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+// config.json as it goes into a bundle: paths into the project become relative, files
+// from elsewhere (images, clips, fonts) are copied into assets/. Returns the config and
+// the assets as bundle path and source file
+fn bundle_config(
+    project_path: &Path,
+    taken: &[String],
+) -> Result<Option<(Vec<u8>, Vec<(String, PathBuf)>)>, String> {
+    let config_path = project_path.join("config.json");
+
+    if !config_path.exists() {
+        return Ok(None);
+    }
+
+    let json_str = fs::read_to_string(&config_path).map_err(|e| e.to_string())?;
+    let mut config: Config = serde_json::from_str(&json_str).map_err(|e| e.to_string())?;
+    let mut assets: Vec<(String, PathBuf)> = Vec::new();
+
+    for path in config_paths_mut(&mut config) {
+        let file = PathBuf::from(path.as_str());
+
+        if !file.is_absolute() {
+            continue;
+        }
+
+        if let Ok(relative) = file.strip_prefix(project_path) {
+            *path = relative.to_string_lossy().replace('\\', "/");
+            continue;
+        }
+
+        let Some(file_name) = file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+
+        if !file.is_file() {
+            continue;
+        }
+
+        if let Some((bundle_path, _)) = assets.iter().find(|(_, source)| *source == file) {
+            *path = bundle_path.clone();
+            continue;
+        }
+
+        let is_taken = |name: &String| {
+            taken.contains(name) || assets.iter().any(|(bundle_path, _)| bundle_path == name)
+        };
+        let mut bundle_path = format!("{}/{}", BUNDLE_ASSETS_DIR, file_name);
+        let mut index = 1;
+        while is_taken(&bundle_path) {
+            bundle_path = format!("{}/{}-{}", BUNDLE_ASSETS_DIR, index, file_name);
+            index += 1;
+        }
+
+        *path = bundle_path.clone();
+        assets.push((bundle_path, file));
+    }
+
+    Ok(Some((
+        serde_json::to_string_pretty(&config).unwrap().into_bytes(),
+        assets,
+    )))
+}
+
+// older bundles hold absolute paths into the exporting machine's app data, swap them for
+// the same file inside the imported project. Paths to files that aren't there are kept,
+// they may still be valid on this machine
+fn rewrite_config_paths(project_path: &Path) -> Result<(), String> {
+    let config_path = project_path.join("config.json");
+
+    if !config_path.exists() {
+        return Ok(());
+    }
+
+    let json_str = fs::read_to_string(&config_path).map_err(|e| e.to_string())?;
+    let mut config: Config = serde_json::from_str(&json_str).map_err(|e| e.to_string())?;
+
+    for path in config_paths_mut(&mut config) {
+        let file = PathBuf::from(path.as_str());
+
+        if !file.is_absolute() {
+            continue;
+        }
+
+        // the shortest trailing part of the path that exists in the project, so
+        // /old/projects/<id>/backgrounds/a.jpg becomes backgrounds/a.jpg
+        let parts: Vec<_> = file
+            .components()
+            .filter_map(|component| match component {
+                std::path::Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+                _ => None,
+            })
+            .collect();
+
+        let relocated = (0..parts.len())
+            .rev()
+            .map(|start| parts[start..].join("/"))
+            .find(|relative| project_path.join(relative).is_file());
+
+        if let Some(relative) = relocated {
+            *path = relative;
+        }
+    }
+
+    fs::write(config_path, serde_json::to_string_pretty(&config).unwrap())
+        .map_err(|e| e.to_string())
+}
+
+// writes the project into bundle_file, zip and sha256 work that belongs off the async runtime
+fn write_bundle(
+    project_path: &Path,
+    project_id: &str,
+    bundle_file: File,
+    include_outputs: bool,
+    include_keystrokes: bool,
+) -> Result<(), String> {
+    let mut zip = ZipWriter::new(bundle_file);
+
+    let stored = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(true);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut files: Vec<BundleFile> = Vec::new();
+
+    let mut entries = Vec::new();
+    collect_bundle_files(project_path, "", &mut entries)?;
+    entries.sort();
+
+    let taken: Vec<String> = entries.iter().map(|(name, _)| name.clone()).collect();
+    let config = bundle_config(project_path, &taken)?;
+
+    if let Some((_, assets)) = &config {
+        entries.extend(assets.iter().cloned());
+    }
+
+    for (file_name, path) in entries {
+        if BUNDLE_SKIPPED_FILES.contains(&file_name.as_str()) {
+            continue;
+        }
+        if !include_outputs && BUNDLE_OUTPUT_FILES.contains(&file_name.as_str()) {
+            continue;
+        }
+        if !include_keystrokes && file_name == BUNDLE_KEYSTROKE_FILE {
+            continue;
+        }
+
+        // video is already compressed, deflating it only costs time
+        let options = if file_name.ends_with(".json") {
+            deflated
+        } else {
+            stored
+        };
+
+        zip.start_file(format!("files/{}", file_name), options)
+            .map_err(|e| e.to_string())?;
+
+        let (size, sha256) = match &config {
+            Some((config_json, _)) if file_name == "config.json" => {
+                copy_and_hash(&mut config_json.as_slice(), &mut zip)
+            }
+            _ => File::open(path).and_then(|mut source| copy_and_hash(&mut source, &mut zip)),
+        }
+        .map_err(|e| format!("Failed to bundle {}: {}", file_name, e))?;
+
+        files.push(BundleFile {
+            path: file_name,
+            size,
+            sha256,
+        });
+    }
+
+    let manifest = BundleManifest {
+        format_version: BUNDLE_FORMAT_VERSION,
+        project_id: project_id.to_string(),
+        meta: read_project_meta(project_path),
+        exported_at: now_millis(),
+        files,
+    };
+
+    zip.start_file("manifest.json", deflated)
+        .map_err(|e| e.to_string())?;
+    zip.write_all(serde_json::to_string_pretty(&manifest).unwrap().as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    zip.finish().map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn export_project_bundle(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    destination: String,
+    include_outputs: bool,
+    include_keystrokes: Option<bool>,
+) -> Result<String, String> {
+    let project_path = project_dir(&app_handle, &project_id)?;
+
+    let mut destination = PathBuf::from(destination);
+    if destination.extension().is_none() {
+        destination.set_extension("sunshot");
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let bundle_file = File::create(&destination)
+            .map_err(|e| format!("Could not create bundle file: {}", e))?;
+
+        // a partial bundle would only fail its integrity check on import
+        if let Err(e) = write_bundle(
+            &project_path,
+            &project_id,
+            bundle_file,
+            include_outputs,
+            include_keystrokes.unwrap_or(false),
+        ) {
+            let _ = fs::remove_file(&destination);
+            return Err(e);
+        }
+
+        Ok(destination.to_string_lossy().to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn extract_bundle(bundle_path: &str, target_path: &Path) -> Result<BundleManifest, String> {
+    let bundle_file =
+        File::open(bundle_path).map_err(|e| format!("Could not open bundle: {}", e))?;
+    let mut archive =
+        ZipArchive::new(bundle_file).map_err(|e| format!("Not a valid bundle: {}", e))?;
+
+    let manifest: BundleManifest = {
+        let manifest_file = archive
+            .by_name("manifest.json")
+            .map_err(|_| "Bundle has no manifest".to_string())?;
+        serde_json::from_reader(manifest_file)
+            .map_err(|e| format!("Failed to parse bundle manifest: {}", e))?
+    };
+
+    if manifest.format_version > BUNDLE_FORMAT_VERSION {
+        return Err(format!(
+            "Bundle format {} is newer than this version of SunShot supports",
+            manifest.format_version
+        ));
+    }
+
+    fs::create_dir_all(target_path).map_err(|e| e.to_string())?;
+
+    for bundle_file in &manifest.files {
+        if !is_valid_bundle_path(&bundle_file.path) {
+            return Err(format!("Invalid file in bundle: {}", bundle_file.path));
+        }
+
+        let mut zip_file = archive
+            .by_name(&format!("files/{}", bundle_file.path))
+            .map_err(|_| format!("Bundle is missing {}", bundle_file.path))?;
+
+        let file_path = target_path.join(&bundle_file.path);
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        let mut target = File::create(file_path).map_err(|e| e.to_string())?;
+        let (size, sha256) = copy_and_hash(&mut zip_file, &mut target)
+            .map_err(|e| format!("Failed to extract {}: {}", bundle_file.path, e))?;
+
+        if size != bundle_file.size || sha256 != bundle_file.sha256 {
+            return Err(format!("Integrity check failed for {}", bundle_file.path));
+        }
+    }
+
+    Ok(manifest)
+}
+
+// extracts and fixes up the bundle in staging_path, only a complete project is moved into
+// projects_path. Returns the new project id
+fn import_bundle(
+    bundle_path: &str,
+    staging_path: &Path,
+    projects_path: &Path,
+) -> Result<String, String> {
+    let manifest = extract_bundle(bundle_path, staging_path)?;
+
+    // keep the original id so links between teammates still line up, unless it's taken
+    let project_id = match Uuid::parse_str(&manifest.project_id) {
+        Ok(id) if !projects_path.join(id.to_string()).exists() => id.to_string(),
+        _ => Uuid::new_v4().to_string(),
+    };
+
+    let mut meta = manifest.meta;
+    if project_id != manifest.project_id {
+        meta.name = format!("{} (Imported)", meta.name);
+    }
+    write_project_meta(staging_path, &meta)?;
+
+    rewrite_config_paths(staging_path)?;
+
+    fs::rename(staging_path, projects_path.join(&project_id)).map_err(|e| e.to_string())?;
+
+    Ok(project_id)
+}
+
+#[tauri::command]
+async fn import_project_bundle(
+    app_handle: tauri::AppHandle,
+    bundle_path: String,
+) -> Result<serde_json::Value, String> {
+    let projects_path = projects_dir(&app_handle)?;
+    fs::create_dir_all(&projects_path).map_err(|e| e.to_string())?;
+
+    // staged next to projects/ so list_projects never sees a half imported project, and the
+    // final rename stays on the same filesystem
+    let staging_path = projects_path
+        .with_file_name("imports")
+        .join(Uuid::new_v4().to_string());
+
+    let project_id = tauri::async_runtime::spawn_blocking(move || {
+        let result = import_bundle(&bundle_path, &staging_path, &projects_path);
+
+        if result.is_err() {
+            let _ = fs::remove_dir_all(&staging_path);
+        }
+
+        result
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    Ok(json!({ "projectId": project_id }))
+}
+
+use device_query::{DeviceQuery, DeviceState, MouseState};
+use percent_encoding::percent_decode_str;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::http::header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, RANGE};
+use tauri::http::status::StatusCode;
+use tauri::http::{HttpRange, Request as HttpRequest, Response as HttpResponse, ResponseBuilder};
+use tauri::{GlobalShortcutManager, Manager};
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use capture::{RectInfo, Source, SourceInfo};
+
+// thumbnails mean grabbing every source, so they are opt in
+#[tauri::command]
+async fn get_sources(
+    backend: tauri::State<'_, Box<dyn capture::CaptureBackend>>,
+    include_thumbnails: Option<bool>,
+) -> Result<Vec<SourceInfo>, String> {
+    capture::list_sources(&**backend, include_thumbnails.unwrap_or(false))
+}
+
+#[tauri::command]
+fn save_source_data(
+    app_handle: tauri::AppHandle,
+    backend: tauri::State<'_, Box<dyn capture::CaptureBackend>>,
+    source: Source,
+    current_project_id: String,
+) -> Result<serde_json::Value, String> {
+    let window_info = backend.source_info(&source)?;
+
+    let source_data = json!({
+        "source": source,
+        "name": window_info.title,
+        "width": window_info.rect.width,
+        "height": window_info.rect.height,
+        "x": window_info.rect.left,
+        "y": window_info.rect.top,
+        "scale_factor": window_info.scale_factor
+    });
+
+    let save_path = app_handle.path_resolver().app_data_dir().unwrap();
+    let file_path = save_path
+        .join("projects")
+        .join(&current_project_id)
+        .join("sourceData.json");
+
+    fs::write(
+        file_path,
+        serde_json::to_string_pretty(&source_data).unwrap(),
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(source_data)
+}
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const BOUNDS_SAMPLE_TICKS: u32 = 5; // source bounds are checked every 5th mouse sample
+const KEY_SAMPLE_INTERVAL: u64 = 10; // ms, keys are polled faster than the mouse to catch short taps
+
+// managed once for the whole app, reset at the start of every recording
+#[derive(Default)]
+struct MouseTrackingState {
+    mouse_positions: Arc<Mutex<Vec<serde_json::Value>>>,
+    key_events: Arc<Mutex<Vec<serde_json::Value>>>,
+    records_keys: Arc<AtomicBool>, // only when the keystroke HUD was asked for
+    source_bounds: Arc<Mutex<Vec<serde_json::Value>>>,
+    clock: capture::RecordingClock, // shared with the capture, stands still while paused
+    is_tracking: Arc<AtomicBool>,
+    is_recording: Arc<Mutex<bool>>,
+}
+
+// source is the window being recorded, its bounds are sampled so moves and resizes
+// mid recording can be followed by the renderer. Keys are only captured with a filter
+#[tauri::command]
+fn start_mouse_tracking(
+    app_handle: tauri::AppHandle,
+    source: Option<Source>,
+    keystrokes: Option<KeystrokeFilter>,
+) -> Result<bool, String> {
+    let state = app_handle.state::<MouseTrackingState>();
+
+    if state.is_tracking.swap(true, Ordering::Relaxed) {
+        return Err("Already tracking the mouse".to_string());
+    }
+
+    state.mouse_positions.lock().unwrap().clear();
+    state.key_events.lock().unwrap().clear();
+    state
+        .records_keys
+        .store(keystrokes.is_some(), Ordering::Relaxed);
+    state.source_bounds.lock().unwrap().clear();
+    state.clock.restart();
+
+    let mouse_positions = state.mouse_positions.clone();
+    let source_bounds = state.source_bounds.clone();
+    let clock = state.clock.clone();
+    let is_tracking = state.is_tracking.clone();
+    let key_events = state.key_events.clone();
+    let key_clock = state.clock.clone();
+    let keys_tracking = state.is_tracking.clone();
+    let tracked_window = source.filter(|source| matches!(source, Source::Window { .. }));
+    let tracking_handle = app_handle.clone();
+
+    thread::spawn(move || {
+        // positions have to be in the same space as the source bounds in sourceData.json
+        capture::use_physical_coordinates();
+
+        let backend = tracking_handle.state::<Box<dyn capture::CaptureBackend>>();
+        let mut last_rect: Option<RectInfo> = None;
+        let mut tick: u32 = 0;
+
+        let device_state = DeviceState::new();
+        while is_tracking.load(Ordering::Relaxed) {
+            if clock.is_paused() {
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+
+            let mouse: MouseState = device_state.get_mouse();
+            let timestamp = clock.elapsed().as_millis();
+
+            let position = json!({
+                "x": mouse.coords.0,
+                "y": mouse.coords.1,
+                "timestamp": timestamp
+            });
+
+            mouse_positions.lock().unwrap().push(position);
+
+            if let Some(window) = tracked_window
+                .as_ref()
+                .filter(|_| tick % BOUNDS_SAMPLE_TICKS == 0)
+            {
+                // a window that went away keeps its last known bounds
+                if let Ok(info) = backend.source_info(window) {
+                    let rect = info.rect;
+                    let changed = last_rect
+                        .as_ref()
+                        .map(|last| {
+                            (last.left, last.top, last.width, last.height)
+                                != (rect.left, rect.top, rect.width, rect.height)
+                        })
+                        .unwrap_or(true);
+
+                    if changed {
+                        source_bounds.lock().unwrap().push(json!({
+                            "timestamp": timestamp,
+                            "x": rect.left,
+                            "y": rect.top,
+                            "width": rect.width,
+                            "height": rect.height
+                        }));
+                        last_rect = Some(rect);
+                    }
+                }
+            }
+
+            tick = tick.wrapping_add(1);
+            thread::sleep(Duration::from_millis(100));
+        }
+    });
+
+    let Some(filter) = keystrokes else {
+        return Ok(true);
+    };
+
+    // a press is stored with all keys held at that moment, so modifiers and the key make
+    // one combo. Modifiers going down on their own and filtered presses aren't stored
+    thread::spawn(move || {
+        let device_state = DeviceState::new();
+        let mut held: Vec<String> = Vec::new();
+
+        while keys_tracking.load(Ordering::Relaxed) {
+            if key_clock.is_paused() {
+                held.clear();
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+
+            let keys: Vec<String> = device_state
+                .get_keys()
+                .iter()
+                .map(|key| format!("{:?}", key))
+                .collect();
+
+            let pressed = keys
+                .iter()
+                .any(|key| !is_modifier_key(key) && !held.contains(key));
+
+            if pressed && combo_label(&keys, &filter).is_some() {
+                key_events.lock().unwrap().push(json!({
+                    "timestamp": key_clock.elapsed().as_millis(),
+                    "keys": keys
+                }));
+            }
+
+            held = keys;
+            thread::sleep(Duration::from_millis(KEY_SAMPLE_INTERVAL));
+        }
+    });
+
+    Ok(true)
+}
 
-                                            // println!(
-                                            //     "setting mouse {} {} {} {}",
-                                            //     mouse_x,
-                                            //     scale_multiple,
-                                            //     current_width,
-                                            //     window_data.x
-                                            // );
+#[tauri::command]
+fn stop_mouse_tracking(app_handle: tauri::AppHandle, project_id: String) -> Result<bool, String> {
+    let state = app_handle.state::<MouseTrackingState>();
 
-                                            // DPI scaling
-                                            let scale_factor = window_data.scale_factor;
+    // Signal the tracking thread to stop
+    state.is_tracking.store(false, Ordering::Relaxed);
 
-                                            mouse_x *= scale_factor;
-                                            mouse_y *= scale_factor;
+    // Give the thread some time to finish
+    thread::sleep(Duration::from_millis(200));
 
-                                            mouse_x *= upscale_factor as f64;
-                                            mouse_y *= upscale_factor as f64;
+    let mouse_positions = state.mouse_positions.lock().unwrap().clone();
 
-                                            // add windowOffset
-                                            mouse_x -= window_data.x as f64;
-                                            mouse_y -= window_data.y as f64;
+    let save_path = app_handle.path_resolver().app_data_dir().unwrap();
+    let file_path = save_path
+        .join("projects")
+        .join(&project_id)
+        .join("mousePositions.json");
 
-                                            // scale mouse positions
-                                            mouse_x = mouse_x * scale_multiple
-                                                + upscaled_frame.width() as f64 * 0.1; // TODO: bg_frame or frame?
-                                            mouse_y = mouse_y * scale_multiple
-                                                + upscaled_frame.height() as f64 * 0.1;
+    fs::write(
+        file_path,
+        serde_json::to_string_pretty(&mouse_positions).unwrap(),
+    )
+    .map_err(|e| e.to_string())?;
 
-                                            // println!("Mouse {} {}\n", mouse_x, mouse_y);
+    if state.records_keys.load(Ordering::Relaxed) {
+        let key_events = state.key_events.lock().unwrap().clone();
 
-                                            // for testing
-                                            // mouse_x = zoom_width as f64 / 2.0;
-                                            // mouse_y = zoom_height as f64 / 2.0;
+        fs::write(
+            save_path
+                .join("projects")
+                .join(&project_id)
+                .join("keyEvents.json"),
+            serde_json::to_string_pretty(&key_events).unwrap(),
+        )
+        .map_err(|e| e.to_string())?;
+    }
 
-                                            if !auto_zoom {
-                                                current_mouse_x = mouse_x;
-                                                current_mouse_y = mouse_y;
-                                            }
+    let source_bounds = state.source_bounds.lock().unwrap().clone();
 
-                                            direction_x = mouse_x - current_mouse_x;
-                                            direction_y = mouse_y - current_mouse_y;
-                                            println!(
-                                                "Zooming In 2 {} {}",
-                                                current_mouse_x, current_mouse_y
-                                            );
-                                        }
-                                    } else if time_elapsed >= end
-                                        && time_elapsed < end + animation_duration
-                                    {
-                                        if zooming_in2 {
-                                            zooming_in2 = false;
-                                            println!("Zooming Out 2");
+    if !source_bounds.is_empty() {
+        update_source_data(
+            &save_path.join("projects").join(&project_id),
+            |source_data| {
+                source_data["bounds"] = json!(source_bounds);
+            },
+        )?;
+    }
 
-                                            // current_mouse_x = zoom_width as f64 / 2.0;
-                                            // current_mouse_y = zoom_height as f64 / 2.0;
-                                        }
-                                    }
-                                }
+    Ok(true)
+}
 
-                                // clamp max
-                                let frame_width = upscaled_frame.width() as f64; // TODO: frame or bg_frame?
-                                let frame_height = upscaled_frame.height() as f64;
+// images picked as backgrounds are copied into the project, so exports and bundles
+// don't depend on where they came from. Returns the path relative to the project
+#[tauri::command]
+fn save_background_image(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    file_name: String,
+    buffer: Vec<u8>,
+) -> Result<String, String> {
+    let project_path = project_dir(&app_handle, &project_id)?;
 
-                                // alternative clamp
-                                current_mouse_x = current_mouse_x.clamp(0.0, frame_width);
-                                current_mouse_y = current_mouse_y.clamp(0.0, frame_height);
+    let file_name = Path::new(&file_name)
+        .file_name()
+        .ok_or("Invalid image file name")?
+        .to_string_lossy()
+        .to_string();
+    let relative_path = format!("backgrounds/{}", file_name);
 
-                                velocity_mouse_x = velocity_mouse_x.clamp(-mouse_x, frame_width);
-                                velocity_mouse_y = velocity_mouse_y.clamp(-mouse_y, frame_height);
+    fs::create_dir_all(project_path.join("backgrounds")).map_err(|e| e.to_string())?;
+    fs::write(project_path.join(&relative_path), buffer).map_err(|e| e.to_string())?;
 
-                                // println!(
-                                //     "Mouse Positions: {}, {} and {}, {}",
-                                //     mouse_x, mouse_y, current_mouse_x, current_mouse_y
-                                // );
-                                println!(
-                                    "Spring Position: {}, {}",
-                                    current_mouse_x, current_mouse_y
-                                );
-                                // println!("Smooth Info: {}, {}", smooth_height, smooth_width);
+    Ok(relative_path)
+}
 
-                                // Center the zoom on the current mouse position
-                                let zoom_top = (current_mouse_y - zoom_height as f64 / 2.0);
-                                let zoom_left = (current_mouse_x - zoom_width as f64 / 2.0);
+#[tauri::command]
+fn save_video_blob(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    buffer: Vec<u8>,
+) -> Result<bool, String> {
+    let save_path = app_handle.path_resolver().app_data_dir().unwrap();
+    let file_path = save_path
+        .join("projects")
+        .join(&project_id)
+        .join("originalCapture.webm");
 
-                                // // Center the zoom on the current mouse position
-                                // let zoom_top = ((bg_frame.height() as f64 - current_mouse_y) / 2.0);
-                                // let zoom_left = ((bg_frame.width() as f64 - current_mouse_x) / 2.0);
+    fs::write(file_path, buffer).map_err(|e| e.to_string())?;
 
-                                println!("Mid Info: {}, {}", zoom_top, zoom_left);
-                                println!("More Info: {} {}", upscaled_frame.height(), zoom_height);
+    Ok(true)
+}
 
-                                let zoom_top = zoom_top
-                                    .clamp(0.0, upscaled_frame.height() as f64 - zoom_height as f64)
-                                    .max(0.0) as u32;
+#[tauri::command]
+fn get_project_data(
+    app_handle: tauri::AppHandle,
+    current_project_id: String,
+) -> Result<serde_json::Value, String> {
+    let save_path = app_handle.path_resolver().app_data_dir().unwrap();
+    let project_path = save_path.join("projects").join(&current_project_id);
 
-                                let zoom_left = zoom_left
-                                    .clamp(0.0, upscaled_frame.width() as f64 - zoom_width as f64)
-                                    .max(0.0)
-                                    as u32;
+    let mouse_positions: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(project_path.join("mousePositions.json")).map_err(|e| e.to_string())?,
+    )
+    .map_err(|e| e.to_string())?;
 
-                                // let target_zoom_top = (current_mouse_y - target_height as f64 / 2.0)
-                                //     .clamp(
-                                //         0.0,
-                                //         upscaled_frame.height() as f64 - target_height as f64,
-                                //     )
-                                //     .max(0.0)
-                                //     as f64;
+    let source_data: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(project_path.join("sourceData.json")).map_err(|e| e.to_string())?,
+    )
+    .map_err(|e| e.to_string())?;
 
-                                // let target_zoom_left = (current_mouse_x - target_width as f64 / 2.0)
-                                //     .clamp(0.0, upscaled_frame.width() as f64 - target_width as f64)
-                                //     .max(0.0)
-                                //     as f64;
+    // served through the sunshot:// protocol so the player can seek without loading the whole file
+    let original_capture = project_media_url(&current_project_id, "capture.mp4");
 
-                                // // max clamps
-                                // let zoom_top = if zoom_top + zoom_height > upscaled_frame.height() {
-                                //     upscaled_frame.height() - zoom_height
-                                // } else {
-                                //     zoom_top
-                                // };
+    let editor_state = read_editor_state(&project_path)?;
 
-                                // let zoom_left = if zoom_left + zoom_width > upscaled_frame.width() {
-                                //     upscaled_frame.width() - zoom_width
-                                // } else {
-                                //     zoom_left
-                                // };
+    // let screens = Screen::all().map_err(|e| e.to_string())?;
+    // let primary_screen = screens
+    //     .into_iter()
+    //     .find(|s| s.display_info.is_primary)
+    //     .unwrap();
+    // let width = primary_screen.display_info.width;
+    // let height = primary_screen.display_info.height;
 
-                                // clamp zoom_top and zoom_left
-                                // let zoom_top = zoom_top.min(upscaled_frame.height());
-                                // let zoom_left = zoom_left.min(upscaled_frame.width());
+    // let resolution = if width <= 1920 && height <= 1080 {
+    //     "hd"
+    // } else {
+    //     "4k"
+    // };
 
-                                // println!("Zoom Info: {}, {}", zoom_top, zoom_left);
+    Ok(json!({
+        "currentProjectId": current_project_id,
+        "mousePositions": mouse_positions,
+        "originalCapture": original_capture,
+        "hasWebcam": project_path.join("webcam.mp4").exists(),
+        "hasKeystrokes": project_path.join("keyEvents.json").exists(),
+        "sourceData": source_data,
+        "editorState": editor_state,
+        // "resolution": resolution,
+    }))
+}
 
-                                if enable_coord_smoothing {
-                                    let prev_zoom_top = smooth_zoom_top;
-                                    let prev_zoom_left = smooth_zoom_left;
+// *** Project Media Protocol *** //
 
-                                    if frame_index == 0 {
-                                        smooth_zoom_top = zoom_top as f64;
-                                        smooth_zoom_left = zoom_left as f64;
-                                    }
+const MEDIA_PROTOCOL: &str = "sunshot";
+const MAX_RANGE_CHUNK: u64 = 1024 * 1024; // bytes served per response
 
-                                    let frame_proportion =
-                                        bg_frame.height() as f64 / bg_frame.width() as f64;
+fn project_media_url(project_id: &str, file_name: &str) -> String {
+    // webview2 only allows custom protocols through its https://<scheme>.localhost form
+    if cfg!(target_os = "windows") {
+        format!(
+            "https://{}.localhost/{}/{}",
+            MEDIA_PROTOCOL, project_id, file_name
+        )
+    } else {
+        format!(
+            "{}://localhost/{}/{}",
+            MEDIA_PROTOCOL, project_id, file_name
+        )
+    }
+}
 
-                                    let smoothing_factor = 0.1; // Adjust this value to change the amount of smoothing (0-1)
-                                    let top_change = (1.0 - smoothing_factor) * smooth_zoom_top; // TODO: need not just on side change but also on dimensions?
-                                    smooth_zoom_top = zoom_top as f64 + top_change;
-                                    smooth_zoom_left =
-                                        zoom_left as f64 + (top_change * frame_proportion);
+fn media_mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("json") => "application/json",
+        _ => "application/octet-stream",
+    }
+}
 
-                                    println!(
-                                        "Smooth Info: {}, {}",
-                                        smooth_zoom_top, smooth_zoom_left
-                                    );
+// the inclusive byte range a response carries, None for the whole file. Bigger files go out
+// a chunk at a time even when no range was asked for, so a request never reads a whole
+// recording into memory. Err for a range outside the file
+fn media_byte_range(range_header: Option<&str>, len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let (start, length) = match range_header {
+        None if len <= MAX_RANGE_CHUNK => return Ok(None),
+        None => (0, len),
+        // only the first range of a multi-range request is served, players don't ask for more
+        Some(range_header) => match HttpRange::parse(range_header, len) {
+            Ok(ranges) if !ranges.is_empty() => (ranges[0].start, ranges[0].length),
+            _ => return Err(()),
+        },
+    };
 
-                                    // Ensure non-negative values
-                                    smooth_zoom_top = smooth_zoom_top.max(0.0);
-                                    smooth_zoom_left = smooth_zoom_left.max(0.0);
+    Ok(Some((start, start + length.min(MAX_RANGE_CHUNK) - 1)))
+}
 
-                                    // Round and ensure even numbers
-                                    smooth_zoom_top = (smooth_zoom_top.round() / 2.0).floor() * 2.0;
-                                    smooth_zoom_left =
-                                        (smooth_zoom_left.round() / 2.0).floor() * 2.0;
+fn project_media_protocol(
+    app_handle: &tauri::AppHandle,
+    request: &HttpRequest,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let uri = request.uri();
+
+    // sunshot://localhost/<project_id>/<file> or https://sunshot.localhost/<project_id>/<file>
+    let path = uri
+        .splitn(2, "localhost/")
+        .nth(1)
+        .unwrap_or("")
+        .split(['?', '#'])
+        .next()
+        .unwrap_or("");
+    let path = percent_decode_str(path).decode_utf8_lossy().to_string();
+
+    let mut parts = path.splitn(2, '/');
+    let project_id = parts.next().unwrap_or("");
+    let file_name = parts.next().unwrap_or("");
+
+    // files in project subfolders like backgrounds/ too, nothing outside the project
+    if !is_valid_bundle_path(file_name) {
+        return ResponseBuilder::new().status(403).body(Vec::new());
+    }
 
-                                    // Apply max clamps
-                                    smooth_zoom_top = smooth_zoom_top
-                                        .min(bg_frame.height() as f64 - zoom_height as f64);
-                                    smooth_zoom_left = smooth_zoom_left
-                                        .min(bg_frame.width() as f64 - zoom_width as f64);
+    let file_path = match project_dir(app_handle, project_id) {
+        Ok(project_path) => project_path.join(file_name),
+        Err(_) => return ResponseBuilder::new().status(404).body(Vec::new()),
+    };
 
-                                    // Double-check even numbers (though this should be redundant now)
-                                    smooth_zoom_top = (smooth_zoom_top / 2.0).floor() * 2.0;
-                                    smooth_zoom_left = (smooth_zoom_left / 2.0).floor() * 2.0;
+    let mut file = match File::open(&file_path) {
+        Ok(file) => file,
+        Err(_) => return ResponseBuilder::new().status(404).body(Vec::new()),
+    };
 
-                                    used_zoom_top = smooth_zoom_top;
-                                    used_zoom_left = smooth_zoom_left;
-                                } else {
-                                    // Ensure even numbers for non-smoothed zoom
-                                    used_zoom_top = ((zoom_top as f64) / 2.0).floor() * 2.0;
-                                    used_zoom_left = ((zoom_left as f64) / 2.0).floor() * 2.0;
-                                }
+    let len = file.metadata()?.len();
 
-                                println!("Used Info: {}, {}", used_zoom_top, used_zoom_left);
+    let response = ResponseBuilder::new()
+        .header(ACCEPT_RANGES, "bytes")
+        .mimetype(media_mime_type(&file_path));
 
-                                let zoom_width = make_even(zoom_width);
-                                let zoom_height = make_even(zoom_height);
+    let range_header = request.headers().get(RANGE).and_then(|r| r.to_str().ok());
 
-                                let scaled_pts =
-                                    frame_index.rescale(encoder.time_base(), stream.time_base());
+    let (start, end) = match media_byte_range(range_header, len) {
+        Ok(Some(range)) => range,
+        Ok(None) => {
+            let mut buf = Vec::with_capacity(len as usize);
+            file.read_to_end(&mut buf)?;
 
-                                let mut data_frame = frame::Video::new(
-                                    Pixel::from(bg_frame.format()),
-                                    // zoom_width,
-                                    // zoom_height,
-                                    bg_frame.width(),
-                                    bg_frame.height(),
-                                );
+            return response
+                .header(CONTENT_LENGTH, len)
+                .status(StatusCode::OK)
+                .body(buf);
+        }
+        Err(()) => {
+            return ResponseBuilder::new()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(CONTENT_RANGE, format!("bytes */{}", len))
+                .body(Vec::new());
+        }
+    };
 
-                                data_frame.set_pts(Some(scaled_pts));
+    let nbytes = end + 1 - start;
 
-                                // Create a scaling context for zooming
-                                let mut sws_ctx_zoom = Context::get(
-                                    bg_frame.format(),
-                                    zoom_width,
-                                    zoom_height,
-                                    data_frame.format(),
-                                    bg_frame.width(),
-                                    bg_frame.height(),
-                                    // upscaled_frame.format(),
-                                    // upscaled_frame.width(),
-                                    // upscaled_frame.height(),
-                                    Flags::BILINEAR,
-                                )
-                                .expect("Failed to create scaling context");
+    let mut buf = Vec::with_capacity(nbytes as usize);
+    file.seek(SeekFrom::Start(start))?;
+    file.take(nbytes).read_to_end(&mut buf)?;
 
-                                // Calculate zoom strides
-                                let zoom_y_stride = zoom_width as usize;
-                                let zoom_u_stride = zoom_width as usize / 2;
-                                let zoom_v_stride = zoom_width as usize / 2;
+    response
+        .header(CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len))
+        .header(CONTENT_LENGTH, nbytes)
+        .status(StatusCode::PARTIAL_CONTENT)
+        .body(buf)
+}
 
-                                // Prepare linesize (stride) array for the zoomed data
-                                let linesize: [c_int; 3] = [
-                                    zoom_y_stride as c_int,
-                                    zoom_u_stride as c_int,
-                                    zoom_v_stride as c_int,
-                                ];
+// a capture started directly is held in the session too, so the record shortcut stops it
+// rather than starting a second recording. Mouse tracking is left to the caller
+#[tauri::command]
+async fn start_video_capture(
+    app_handle: tauri::AppHandle,
+    source: Source,
+    width: u32,
+    height: u32,
+    project_id: String,
+    cursor_mode: Option<capture::CursorMode>,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+    webcam: Option<String>, // v4l2 device or video file, recorded into webcam.mp4
+) -> Result<(), String> {
+    let config = RecordingConfig {
+        source,
+        cursor_mode,
+        max_width,
+        max_height,
+        countdown: 0,
+        max_duration: None,
+        max_file_size: None,
+        webcam,
+        keystrokes: None,
+    };
 
-                                // Get strides
-                                let y_stride = bg_frame.stride(0);
-                                let u_stride = bg_frame.stride(1);
-                                let v_stride = bg_frame.stride(2);
+    let state = app_handle.state::<Mutex<RecordingSession>>();
+    {
+        let mut session = state.lock().unwrap();
 
-                                // Prepare linesize (stride) array
-                                let linesize2: [c_int; 3] =
-                                    [y_stride as c_int, u_stride as c_int, v_stride as c_int];
+        if session.phase != RecordingPhase::Idle {
+            return Err("Already recording".to_string());
+        }
 
-                                // println!("linesizes {:?} {:?}", linesize, linesize2);
+        session.generation += 1;
+        session.phase = RecordingPhase::Starting;
+        session.project_id = Some(project_id.clone());
+        session.pending_stop = None;
+        session.tracks_mouse = false;
+        emit_recording_state(&app_handle, &session, None);
+    }
 
-                                // let y_stride = data_frame.stride(0); // For Y plane
-                                // let u_stride = data_frame.stride(1); // For U plane
-                                // let v_stride = data_frame.stride(2); // For V plane
+    if let Err(e) = start_capture(&app_handle, &config, width, height, &project_id) {
+        let mut session = state.lock().unwrap();
+        session.phase = RecordingPhase::Idle;
+        emit_recording_event(
+            &app_handle,
+            RecordingStateEvent {
+                state: session.phase,
+                project_id: session.project_id.clone(),
+                countdown: None,
+                reason: None,
+                error: Some(e.clone()),
+            },
+        );
+        return Err(e);
+    }
 
-                                // let linesize: [c_int; 3] =
-                                //     [y_stride as c_int, u_stride as c_int, v_stride as c_int];
+    capture_started(&app_handle)
+}
 
-                                // Get pointers to the zoomed portion in the background frame
-                                let used_zoom_top_int = used_zoom_top as usize;
-                                let used_zoom_left_int = used_zoom_left as usize;
+// records config.source into the project's capture_pre.mp4, and the webcam alongside
+fn start_capture(
+    app_handle: &tauri::AppHandle,
+    config: &RecordingConfig,
+    width: u32,
+    height: u32,
+    project_id: &str,
+) -> Result<(), String> {
+    let backend = app_handle.state::<Box<dyn capture::CaptureBackend>>();
+    let project_path = project_dir(app_handle, project_id)?;
+    let output_path = project_path
+        .join("capture_pre.mp4")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let webcam_path = project_path
+        .join("webcam.mp4")
+        .to_string_lossy()
+        .to_string();
 
-                                // let mut zoom_data: [*const u8; 3] = [std::ptr::null(); 3];
+    let state = app_handle.state::<MouseTrackingState>();
+    let mut is_recording = state.is_recording.lock().unwrap();
 
-                                // let start_y =
-                                //     used_zoom_top_int * bg_frame.stride(0) + used_zoom_left_int;
+    if *is_recording {
+        return Err("Already recording".to_string());
+    }
 
-                                // zoom_data[0] = bg_frame.data(0)[start_y..].as_ptr();
+    *is_recording = true;
+    drop(is_recording);
 
-                                // if used_zoom_top_int % 2 == 0 && used_zoom_left_int % 2 == 0 {
-                                //     let start_u = (used_zoom_top_int / 2) * bg_frame.stride(1)
-                                //         + (used_zoom_left_int / 2);
-                                //     zoom_data[1] = bg_frame.data(1)[start_u..].as_ptr();
+    println!(
+        "Starting {} capture of {:?} ({}x{})",
+        backend.name(),
+        config.source,
+        width,
+        height
+    );
 
-                                //     let start_v = (used_zoom_top_int / 2) * bg_frame.stride(2)
-                                //         + (used_zoom_left_int / 2);
-                                //     zoom_data[2] = bg_frame.data(2)[start_v..].as_ptr();
-                                // } else {
-                                //     zoom_data[1] = std::ptr::null();
-                                //     zoom_data[2] = std::ptr::null();
-                                // }
+    let options = capture::CaptureOptions {
+        source: config.source.clone(),
+        width,
+        height,
+        output_path,
+        // either bound alone still limits that side
+        max_size: match (config.max_width, config.max_height) {
+            (None, None) => None,
+            (max_width, max_height) => Some((
+                max_width.unwrap_or(u32::MAX),
+                max_height.unwrap_or(u32::MAX),
+            )),
+        },
+        cursor_mode: config.cursor_mode.unwrap_or_default(),
+        is_recording: state.is_recording.clone(),
+        clock: state.clock.clone(),
+        on_frame: None,
+        on_source_bounds: Some(Box::new(move |rect| {
+            if let Err(e) = update_source_bounds(&project_path, rect) {
+                eprintln!("Failed to update source bounds: {}", e);
+            }
+        })),
+    };
 
-                                let mut zoom_data: [*const u8; 3] = [std::ptr::null(); 3];
+    if let Err(e) = backend.start(options) {
+        *state.is_recording.lock().unwrap() = false;
+        return Err(e);
+    }
 
-                                // Convert float coordinates to integers, accounting for upscaling
-                                let used_zoom_top_int = used_zoom_top as usize;
-                                let used_zoom_left_int = used_zoom_left as usize;
+    // the recording goes on without the overlay if the webcam can't be opened
+    if let Some(webcam) = config.webcam.as_ref().filter(|webcam| !webcam.is_empty()) {
+        if let Err(e) = app_handle.state::<capture::webcam::WebcamCapture>().start(
+            webcam,
+            &webcam_path,
+            state.is_recording.clone(),
+            state.clock.clone(),
+        ) {
+            eprintln!("Failed to start webcam: {}", e);
+        }
+    }
 
-                                // Prepare Y plane
-                                let start_y = used_zoom_top_int * upscaled_frame.stride(0)
-                                    + used_zoom_left_int;
-                                zoom_data[0] = upscaled_frame.data(0)[start_y..].as_ptr();
+    Ok(())
+}
 
-                                // Prepare U and V planes
-                                // Note: We need to ensure that these values are even after upscaling
-                                if used_zoom_top_int % 2 == 0 && used_zoom_left_int % 2 == 0 {
-                                    let start_u = (used_zoom_top_int / 2)
-                                        * upscaled_frame.stride(1)
-                                        + (used_zoom_left_int / 2);
-                                    zoom_data[1] = upscaled_frame.data(1)[start_u..].as_ptr();
+// paused spans are left out of both the capture and the mouse positions
+#[tauri::command]
+fn pause_recording(app_handle: tauri::AppHandle) -> Result<(), String> {
+    set_paused(&app_handle, true)
+}
 
-                                    let start_v = (used_zoom_top_int / 2)
-                                        * upscaled_frame.stride(2)
-                                        + (used_zoom_left_int / 2);
-                                    zoom_data[2] = upscaled_frame.data(2)[start_v..].as_ptr();
-                                } else {
-                                    // Handle the case where the upscaled coordinates are not even
-                                    // This might involve some form of interpolation or rounding
-                                    let adjusted_top = (used_zoom_top_int / 2) * 2;
-                                    let adjusted_left = (used_zoom_left_int / 2) * 2;
+#[tauri::command]
+fn resume_recording(app_handle: tauri::AppHandle) -> Result<(), String> {
+    set_paused(&app_handle, false)
+}
 
-                                    let start_u = (adjusted_top / 2) * upscaled_frame.stride(1)
-                                        + (adjusted_left / 2);
-                                    zoom_data[1] = upscaled_frame.data(1)[start_u..].as_ptr();
+fn set_paused(app_handle: &tauri::AppHandle, paused: bool) -> Result<(), String> {
+    let state = app_handle.state::<MouseTrackingState>();
 
-                                    let start_v = (adjusted_top / 2) * upscaled_frame.stride(2)
-                                        + (adjusted_left / 2);
-                                    zoom_data[2] = upscaled_frame.data(2)[start_v..].as_ptr();
-                                }
+    if !*state.is_recording.lock().unwrap() {
+        return Err("Not currently recording".to_string());
+    }
 
-                                unsafe {
-                                    sws_scale(
-                                        sws_ctx_zoom.as_mut_ptr(),
-                                        zoom_data.as_ptr() as *const *const u8,
-                                        (*upscaled_frame.as_mut_ptr()).linesize.as_ptr(),
-                                        // linesize.as_ptr(),
-                                        0,
-                                        zoom_height as c_int,
-                                        (*data_frame.as_mut_ptr()).data.as_ptr() as *mut *mut u8,
-                                        (*data_frame.as_mut_ptr()).linesize.as_ptr(),
-                                    );
-                                }
+    if paused {
+        state.clock.pause()?;
+    } else {
+        state.clock.resume()?;
+    }
 
-                                // Send the zoom_frame to the encoder
-                                encoder.send_frame(&data_frame).map_err(|e| {
-                                    format!("Error sending frame for encoding: {}", e)
-                                })?;
+    let session_state = app_handle.state::<Mutex<RecordingSession>>();
+    let mut session = session_state.lock().unwrap();
 
-                                // Receive and write encoded packets
-                                'encode_loop: loop {
-                                    let mut output_packet = ffmpeg::Packet::empty();
-                                    match encoder.receive_packet(&mut output_packet) {
-                                        Ok(_) => {
-                                            output_packet.set_stream(0); // Set the stream index
-                                            output_packet
-                                                .write_interleaved(&mut output_context)
-                                                .map_err(|e| {
-                                                    format!("Error writing packet: {}", e)
-                                                })?;
-                                            successful_frame_index += 1;
-                                        }
-                                        Err(ffmpeg::Error::Other {
-                                            errno: ffmpeg::error::EAGAIN,
-                                        }) => {
-                                            // No more packets to receive, break the loop
-                                            break 'encode_loop;
-                                        }
-                                        Err(e) => {
-                                            return Err(format!(
-                                                "Error receiving encoded packet: {}",
-                                                e
-                                            ));
-                                        }
-                                    }
-                                }
+    if matches!(
+        session.phase,
+        RecordingPhase::Recording | RecordingPhase::Paused
+    ) {
+        session.phase = if paused {
+            RecordingPhase::Paused
+        } else {
+            RecordingPhase::Recording
+        };
+        emit_recording_state(app_handle, &session, None);
+    }
 
-                                // The zoom_frame will be automatically dropped here when it goes out of scope
-                            }
-                            Err(ffmpeg::Error::Other {
-                                errno: ffmpeg::error::EAGAIN,
-                            }) => {
-                                break 'decode_loop;
-                            }
-                            Err(e) => return Err(format!("Error receiving decoded frame: {}", e)),
-                        }
-                    }
+    Ok(())
+}
 
-                    // let duration2 = start2.elapsed();
-                    // println!("start2 Time elapsed: {:?}", duration2);
-                }
-            }
-            // Some(Err(e)) => return Err(format!("Error reading packet: {}", e)),
-            None => break 'main_loop, // End of stream
-        }
+fn update_source_bounds(project_path: &Path, rect: &RectInfo) -> Result<(), String> {
+    update_source_data(project_path, |source_data| {
+        source_data["x"] = json!(rect.left);
+        source_data["y"] = json!(rect.top);
+        source_data["width"] = json!(rect.width);
+        source_data["height"] = json!(rect.height);
+    })
+}
 
-        // ... (progress update code)
-        frame_index += 1;
-    }
+fn update_source_data(
+    project_path: &Path,
+    update: impl FnOnce(&mut serde_json::Value),
+) -> Result<(), String> {
+    let source_path = project_path.join("sourceData.json");
 
-    // After the main loop
-    output_context
-        .write_trailer()
-        .map_err(|e| format!("Error occurred when writing trailer: {}", e))?;
+    let mut source_data: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&source_path).map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
 
-    Ok("Video transformation completed successfully".to_string())
+    update(&mut source_data);
+
+    fs::write(
+        source_path,
+        serde_json::to_string_pretty(&source_data).unwrap(),
+    )
+    .map_err(|e| e.to_string())
 }
 
+// how long a stopped capture gets to flush its encoder and write the file
+const CAPTURE_FINALIZE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// stops the recording of project_id through the session, however it was started
 #[tauri::command]
-fn transform_video(
+async fn stop_video_capture(
     app_handle: tauri::AppHandle,
     project_id: String,
-    duration: i32,
-    zoom_info: Vec<ZoomInfo>,
-    background_info: Vec<BackgroundInfo>,
-) -> Result<String, String> {
-    let save_path = app_handle.path_resolver().app_data_dir().unwrap();
-    let project_path = save_path.join("projects").join(&project_id);
+) -> Result<(), String> {
+    let session_project = app_handle
+        .state::<Mutex<RecordingSession>>()
+        .lock()
+        .unwrap()
+        .project_id
+        .clone();
 
-    let positions_path = project_path.join("mousePositions.json");
-    let source_path = project_path.join("sourceData.json");
-    let input_path = project_path.join("capture.mp4");
-    let output_path = project_path.join("output.mp4");
-    let compressed_path = project_path.join("output_compressed.mp4");
+    if session_project.as_deref() != Some(project_id.as_str()) {
+        return Err("Not currently recording".to_string());
+    }
 
-    let config_path = project_path.join("config.json");
+    tauri::async_runtime::spawn_blocking(move || end_session(&app_handle, "stopped"))
+        .await
+        .map_err(|e| e.to_string())?
+}
 
-    let config_path_str = config_path.to_string_lossy().to_string();
-    let output_path_str = output_path.to_string_lossy().to_string();
-    let compressed_path_str = compressed_path.to_string_lossy().to_string();
+// stops the capture, finalizing and compressing it in the background
+fn stop_capture(app_handle: &tauri::AppHandle, project_id: &str) -> Result<(), String> {
+    let app_data_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or("Failed to get app data directory")?;
+    let project_path = app_data_dir.join("projects").join(project_id);
+    let output_path = project_path
+        .join("capture_pre.mp4")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let compressed_path = project_path
+        .join("capture.mp4")
+        .to_str()
+        .unwrap()
+        .to_string();
 
-    let config = Config {
-        duration: duration,
-        positions_file: positions_path.to_string_lossy().to_string(),
-        source_file: source_path.to_string_lossy().to_string(),
-        input_file: input_path.to_string_lossy().to_string(),
-        output_file: output_path.to_string_lossy().to_string(),
-        zoom_info: zoom_info,
-        background_info: background_info,
-    };
+    let state = app_handle.state::<MouseTrackingState>();
 
-    fs::write(config_path, serde_json::to_string_pretty(&config).unwrap())
-        .map_err(|e| e.to_string())?;
+    if !*state.is_recording.lock().unwrap() {
+        return Err("Not currently recording".to_string());
+    }
+
+    app_handle
+        .state::<Box<dyn capture::CaptureBackend>>()
+        .stop()?;
+
+    let app_handle = app_handle.clone();
 
     thread::spawn(move || {
-        do_transform_video(config_path_str).expect("Couldn't transform video");
+        let app_handle = app_handle.clone();
+
+        println!("waiting for capture to finalize");
+
+        let finished = app_handle
+            .state::<Box<dyn capture::CaptureBackend>>()
+            .wait_finished(CAPTURE_FINALIZE_TIMEOUT);
+
+        if let Err(e) = finished {
+            eprintln!("Capture did not finalize: {}", e);
+            app_handle.emit_all("video-compression", "error").unwrap();
+            return;
+        }
+
+        // without a finished webcam.mp4 the project just has no overlay
+        if let Err(e) = app_handle
+            .state::<capture::webcam::WebcamCapture>()
+            .finish(CAPTURE_FINALIZE_TIMEOUT)
+        {
+            eprintln!("Webcam did not finalize: {}", e);
+        }
+
+        println!("compress");
+
+        println!(
+            "PATH: {:?}",
+            env::var("PATH").unwrap_or_else(|_| "Not found".to_string())
+        );
+
+        // let status = Command::new("ffmpeg")
+        //     .arg("-i")
+        //     .arg(output_path)
+        //     .arg("-vcodec")
+        //     .arg("libx264") // or "libx265" for H.265/HEVC compression
+        //     .arg("-crf")
+        //     .arg("5")
+        //     .arg(compressed_path)
+        //     .status()
+        //     .expect("Failed to execute ffmpeg command");
+        // let ffmpeg_path = r"C:\Users\alext\scoop\shims\ffmpeg.exe"; // Replace with actual path
 
         let status = Command::new("ffmpeg")
             .arg("-i")
-            .arg(output_path_str)
-            .arg("-vcodec")
-            .arg("libx264") // or "libx265" for H.265/HEVC compression
+            .arg(output_path)
+            .arg("-nostdin")
+            .arg("-c:v")
+            .arg("libx264")
             .arg("-crf")
-            .arg("5")
-            .arg(compressed_path_str)
+            .arg("23")
+            .arg(compressed_path)
+            // .arg("-v")
+            // .arg("verbose")
+            // .arg("-ignore_editlist")
+            // .arg("1")
+            // .arg("-i")
+            // .arg(output_path)
+            // .arg("-c:v")
+            // .arg("libx264")
+            // .arg("-preset")
+            // .arg("medium")
+            // .arg("-crf")
+            // .arg("23")
+            // .arg("-c:a")
+            // .arg("copy")
+            // .arg(compressed_path)
             .status()
             .expect("Failed to execute ffmpeg command");
 
         if status.success() {
             println!("Video compressed successfully!");
-            app_handle.emit_all("video-export", "success").unwrap();
+            app_handle.emit_all("video-compression", "success").unwrap();
         } else {
             eprintln!("Error compressing the video.");
-            app_handle.emit_all("video-export", "failure").unwrap();
+            app_handle.emit_all("video-compression", "error").unwrap();
         }
     });
 
-    Ok("Transformation has been initiated in thread".to_string())
+    Ok(())
 }
 
-#[tauri::command]
-fn create_project(app_handle: tauri::AppHandle) -> Result<serde_json::Value, String> {
-    let current_project_id = Uuid::new_v4().to_string();
-    let save_path = app_handle.path_resolver().app_data_dir().unwrap();
-    let project_dir = save_path.join("projects").join(&current_project_id);
+// *** Recording Session *** //
+
+// global shortcuts, both toggle: start/stop the last configured recording, pause/resume it
+const RECORD_SHORTCUT: &str = "CmdOrCtrl+Shift+R";
+const PAUSE_SHORTCUT: &str = "CmdOrCtrl+Shift+P";
+const SESSION_LIMIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Deserialize, Debug, Clone)]
+struct RecordingConfig {
+    source: Source,
+    cursor_mode: Option<capture::CursorMode>,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+    #[serde(default)]
+    countdown: u32, // seconds before capture starts
+    max_duration: Option<u64>, // seconds of recorded time, paused spans don't count
+    max_file_size: Option<u64>, // bytes of capture_pre.mp4
+    webcam: Option<String>,
+    // keys are only captured for the keystroke HUD when this is set
+    #[serde(default)]
+    keystrokes: Option<KeystrokeFilter>,
+}
 
-    fs::create_dir_all(&project_dir).map_err(|e| e.to_string())?;
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum RecordingPhase {
+    #[default]
+    Idle,
+    Countdown,
+    Starting, // the capture is being set up, the session lock isn't held meanwhile
+    Recording,
+    Paused,
+    Stopping,
+}
 
-    Ok(json!({ "projectId": current_project_id }))
+#[derive(Default)]
+struct RecordingSession {
+    phase: RecordingPhase,
+    // bumped for every session, so countdown and limit threads of an old one bow out
+    generation: u64,
+    // kept after the session ends, the record shortcut starts the same recording again
+    config: Option<RecordingConfig>,
+    project_id: Option<String>,
+    // a stop that came in while Starting, carried out as soon as the capture runs
+    pending_stop: Option<String>,
+    // whether ending the session stops mouse tracking, start_video_capture leaves that to
+    // its caller
+    tracks_mouse: bool,
 }
 
-use device_query::{DeviceQuery, DeviceState, MouseState};
-use serde_json::json;
-use std::path::Path;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tauri::Manager;
-use uuid::Uuid;
+// payload of the "recording-state" event, sent on every phase change
+#[derive(Serialize, Debug, Clone)]
+struct RecordingStateEvent {
+    state: RecordingPhase,
+    project_id: Option<String>,
+    countdown: Option<u32>, // seconds left, while counting down
+    reason: Option<String>, // why a session went back to idle
+    error: Option<String>,
+}
+
+fn emit_recording_state(
+    app_handle: &tauri::AppHandle,
+    session: &RecordingSession,
+    countdown: Option<u32>,
+) {
+    emit_recording_event(
+        app_handle,
+        RecordingStateEvent {
+            state: session.phase,
+            project_id: session.project_id.clone(),
+            countdown,
+            reason: None,
+            error: None,
+        },
+    );
+}
+
+fn emit_recording_event(app_handle: &tauri::AppHandle, event: RecordingStateEvent) {
+    if let Err(e) = app_handle.emit_all("recording-state", event) {
+        eprintln!("Failed to emit recording state: {}", e);
+    }
+}
+
+// config can be left out to repeat the previous session
+#[tauri::command]
+fn start_recording_session(
+    app_handle: tauri::AppHandle,
+    config: Option<RecordingConfig>,
+) -> Result<(), String> {
+    begin_session(&app_handle, config)
+}
 
-// #[cfg(target_os = "windows")]
-// use windows_capture::{
-//     capture::GraphicsCaptureApiHandler,
-//     frame::Frame,
-//     graphics_capture_api::InternalCaptureControl,
-//     monitor::Monitor,
-//     settings::{ColorFormat, CursorCaptureSettings, DrawBorderSettings, Settings},
-// };
-
-#[cfg(target_os = "windows")]
-use windows::{
-    Win32::Foundation::{BOOL, HWND, LPARAM, RECT},
-    Win32::UI::WindowsAndMessaging::{EnumWindows, GetWindowRect, GetWindowTextW, IsWindowVisible},
-};
-
-#[cfg(target_os = "windows")]
+// returns once the stop is underway, the "recording-state" event reports when it's done
 #[tauri::command]
-fn get_sources() -> Result<Vec<WindowInfo>, String> {
-    // use windows::Win32::Foundation::BOOLEAN;
-
-    let mut windows: Vec<WindowInfo> = Vec::new();
-
-    // EnumWindows callback to enumerate all top-level windows
-    unsafe extern "system" fn enum_windows_callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
-        // Only capture windows that are visible
-        if IsWindowVisible(hwnd).as_bool() {
-            // Get the window title and its rect (position/size)
-            if let Ok((title, rect)) = get_window_info(hwnd) {
-                // let sources = lparam.0 as *mut Vec<serde_json::Value>;
-                let sources = lparam.0 as *mut Vec<WindowInfo>;
-                // let entry = json!({
-                //     "hwnd": hwnd.0 as usize,  // Converting HWND to usize for JSON compatibility
-                //     "title": title,
-                //     "rect": {
-                //         "left": rect.left,
-                //         "top": rect.top,
-                //         "right": rect.right,
-                //         "bottom": rect.bottom,
-                //     }
-                // });
-                let window_info = WindowInfo {
-                    hwnd: hwnd.0 as usize,
-                    title: title,
-                    rect: RectInfo {
-                        left: rect.left,
-                        top: rect.top,
-                        right: rect.right,
-                        bottom: rect.bottom,
-                        width: rect.right - rect.left,
-                        height: rect.bottom - rect.top,
+fn stop_recording_session(app_handle: tauri::AppHandle) -> Result<(), String> {
+    let phase = app_handle
+        .state::<Mutex<RecordingSession>>()
+        .lock()
+        .unwrap()
+        .phase;
+
+    if matches!(phase, RecordingPhase::Idle | RecordingPhase::Stopping) {
+        return Err("Not currently recording".to_string());
+    }
+
+    thread::spawn(move || {
+        if let Err(e) = end_session(&app_handle, "stopped") {
+            eprintln!("Failed to stop recording: {}", e);
+        }
+    });
+
+    Ok(())
+}
+
+fn begin_session(
+    app_handle: &tauri::AppHandle,
+    config: Option<RecordingConfig>,
+) -> Result<(), String> {
+    let state = app_handle.state::<Mutex<RecordingSession>>();
+    let mut session = state.lock().unwrap();
+
+    if session.phase != RecordingPhase::Idle {
+        return Err("Already recording".to_string());
+    }
+
+    let config = config
+        .or_else(|| session.config.clone())
+        .ok_or("No recording source selected")?;
+
+    session.generation += 1;
+    session.phase = RecordingPhase::Countdown;
+    session.config = Some(config.clone());
+    session.project_id = None;
+    session.pending_stop = None;
+    session.tracks_mouse = true;
+    emit_recording_state(app_handle, &session, Some(config.countdown));
+
+    let generation = session.generation;
+    let app_handle = app_handle.clone();
+
+    // the start goes through the async capture commands, which can't be blocked on from
+    // inside a command, so the whole session start runs on its own thread
+    thread::spawn(move || {
+        let state = app_handle.state::<Mutex<RecordingSession>>();
+
+        for remaining in (0..config.countdown).rev() {
+            thread::sleep(Duration::from_secs(1));
+
+            let session = state.lock().unwrap();
+
+            // stopped during the countdown
+            if session.generation != generation || session.phase != RecordingPhase::Countdown {
+                return;
+            }
+
+            if remaining > 0 {
+                emit_recording_state(&app_handle, &session, Some(remaining));
+            }
+        }
+
+        if let Err(e) = start_session_capture(&app_handle, generation, &config) {
+            eprintln!("Failed to start recording: {}", e);
+
+            let mut session = state.lock().unwrap();
+
+            if session.generation == generation {
+                session.phase = RecordingPhase::Idle;
+                emit_recording_event(
+                    &app_handle,
+                    RecordingStateEvent {
+                        state: session.phase,
+                        project_id: session.project_id.clone(),
+                        countdown: None,
+                        reason: None,
+                        error: Some(e),
                     },
-                };
-                // (*sources).push(window_info);
-                (*sources).push(window_info);
+                );
             }
         }
+    });
 
-        // 1 // Continue enumeration
-        true.into() // Continue enumeration
-    }
+    Ok(())
+}
+
+fn start_session_capture(
+    app_handle: &tauri::AppHandle,
+    generation: u64,
+    config: &RecordingConfig,
+) -> Result<(), String> {
+    let state = app_handle.state::<Mutex<RecordingSession>>();
+
+    // Starting keeps other starts out and turns stops into a pending stop, so the lock
+    // can go before the capture is started. The shortcuts lock it on the event loop
+    let project_id = {
+        let mut session = state.lock().unwrap();
+
+        if session.generation != generation || session.phase != RecordingPhase::Countdown {
+            return Ok(());
+        }
+
+        let project = create_project(app_handle.clone())?;
+        let project_id = project["projectId"]
+            .as_str()
+            .ok_or("Project has no id")?
+            .to_string();
+
+        session.phase = RecordingPhase::Starting;
+        session.project_id = Some(project_id.clone());
+        emit_recording_state(app_handle, &session, None);
+
+        project_id
+    };
 
-    unsafe {
-        // Enumerate all top-level windows
-        EnumWindows(
-            Some(enum_windows_callback),
-            LPARAM(&mut windows as *mut _ as isize),
+    let started = (|| -> Result<(), String> {
+        let source_data = save_source_data(
+            app_handle.clone(),
+            app_handle.state(),
+            config.source.clone(),
+            project_id.clone(),
+        )?;
+
+        start_mouse_tracking(
+            app_handle.clone(),
+            Some(config.source.clone()),
+            config.keystrokes.clone(),
+        )?;
+
+        start_capture(
+            app_handle,
+            config,
+            source_data["width"].as_u64().unwrap_or_default() as u32,
+            source_data["height"].as_u64().unwrap_or_default() as u32,
+            &project_id,
         )
-        .expect("Couldn't enumerate windows");
+    })();
+
+    // nothing was recorded, don't leave an empty project in the library
+    if let Err(e) = started {
+        app_handle
+            .state::<MouseTrackingState>()
+            .is_tracking
+            .store(false, Ordering::Relaxed);
+
+        if let Ok(project_path) = project_dir(app_handle, &project_id) {
+            let _ = fs::remove_dir_all(project_path);
+        }
+
+        state.lock().unwrap().project_id = None;
+        return Err(e);
     }
 
-    Ok(windows)
-}
+    capture_started(app_handle)?;
 
-#[cfg(target_os = "windows")]
-fn get_window_info(hwnd: HWND) -> Result<(String, RECT), String> {
-    unsafe {
-        let mut rect = RECT::default();
-        GetWindowRect(hwnd, &mut rect).expect("Couldn't get WindowRect");
-        // if GetWindowRect(hwnd, &mut rect)
-        //     .expect("Couldn't get WindowRect")
-        //     .as_bool()
-        // {
-        let mut title: [u16; 512] = [0; 512];
-        let len = GetWindowTextW(hwnd, &mut title);
-        let title = String::from_utf16_lossy(&title[..len as usize]);
-        Ok((title, rect))
-        // } else {
-        //     Err("Failed to get window information".to_string())
-        // }
+    if config.max_duration.is_some() || config.max_file_size.is_some() {
+        let app_handle = app_handle.clone();
+        let max_duration = config.max_duration.map(Duration::from_secs);
+        let max_file_size = config.max_file_size;
+        let capture_path = project_dir(&app_handle, &project_id)?.join("capture_pre.mp4");
+
+        thread::spawn(move || {
+            watch_session_limits(
+                &app_handle,
+                generation,
+                max_duration,
+                max_file_size,
+                &capture_path,
+            )
+        });
     }
-}
 
-#[derive(Deserialize, Serialize, Debug)]
-struct RectInfo {
-    left: i32,
-    right: i32,
-    top: i32,
-    bottom: i32,
-    width: i32,
-    height: i32,
+    Ok(())
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-struct WindowInfo {
-    hwnd: usize,
-    title: String,
-    rect: RectInfo,
-}
+// moves a Starting session on to Recording, or stops it straight away if a stop came in
+// while the capture was starting
+fn capture_started(app_handle: &tauri::AppHandle) -> Result<(), String> {
+    let state = app_handle.state::<Mutex<RecordingSession>>();
+    let mut session = state.lock().unwrap();
 
-#[cfg(target_os = "windows")]
-#[tauri::command]
-fn get_window_info_by_usize(hwnd_value: usize) -> Result<WindowInfo, String> {
-    // Convert the usize back into an HWND
-    let hwnd = HWND(hwnd_value as *mut _);
-
-    if let Ok((title, rect)) = get_window_info(hwnd) {
-        // let window_info = json!({
-        //     "hwnd": hwnd_value,
-        //     "title": title,
-        //     "rect": {
-        //         "left": rect.left,
-        //         "top": rect.top,
-        //         "right": rect.right,
-        //         "bottom": rect.bottom,
-        //     }
-        // });
-        let window_info = WindowInfo {
-            hwnd: hwnd_value,
-            title: title,
-            rect: RectInfo {
-                left: rect.left,
-                top: rect.top,
-                right: rect.right,
-                bottom: rect.bottom,
-                width: rect.right - rect.left,
-                height: rect.bottom - rect.top,
-            },
-        };
-        Ok(window_info)
-    } else {
-        Err("Failed to get window information".to_string())
+    session.phase = RecordingPhase::Recording;
+    emit_recording_state(app_handle, &session, None);
+
+    if let Some(reason) = session.pending_stop.take() {
+        drop(session);
+        return end_session(app_handle, &reason);
     }
+
+    Ok(())
 }
 
-#[cfg(target_os = "windows")]
-#[tauri::command]
-fn save_source_data(
-    app_handle: tauri::AppHandle,
-    hwnd: usize,
-    current_project_id: String,
-) -> Result<serde_json::Value, String> {
-    let window_info = get_window_info_by_usize(hwnd).expect("Couldn't get window info by usize");
+// stops the session once it has recorded for max_duration or its capture grew to max_file_size
+fn watch_session_limits(
+    app_handle: &tauri::AppHandle,
+    generation: u64,
+    max_duration: Option<Duration>,
+    max_file_size: Option<u64>,
+    capture_path: &Path,
+) {
+    let clock = app_handle.state::<MouseTrackingState>().clock.clone();
+
+    loop {
+        thread::sleep(SESSION_LIMIT_POLL_INTERVAL);
+
+        {
+            let session = app_handle.state::<Mutex<RecordingSession>>();
+            let session = session.lock().unwrap();
+
+            if session.generation != generation
+                || !matches!(
+                    session.phase,
+                    RecordingPhase::Recording | RecordingPhase::Paused
+                )
+            {
+                return;
+            }
+        }
 
-    let source_data = json!({
-        "id": hwnd.to_string(),
-        "name": window_info.title,
-        "width": window_info.rect.width,
-        "height": window_info.rect.height,
-        "x": window_info.rect.left,
-        "y": window_info.rect.top,
-        "scale_factor": 1.0
-    });
+        let reason = if max_duration.is_some_and(|max| clock.elapsed() >= max) {
+            "max-duration"
+        } else if max_file_size.is_some_and(|max| {
+            fs::metadata(capture_path)
+                .map(|metadata| metadata.len() >= max)
+                .unwrap_or(false)
+        }) {
+            "max-file-size"
+        } else {
+            continue;
+        };
 
-    let save_path = app_handle.path_resolver().app_data_dir().unwrap();
-    let file_path = save_path
-        .join("projects")
-        .join(&current_project_id)
-        .join("sourceData.json");
+        println!("Stopping recording: {}", reason);
 
-    fs::write(
-        file_path,
-        serde_json::to_string_pretty(&source_data).unwrap(),
-    )
-    .map_err(|e| e.to_string())?;
+        if let Err(e) = end_session(app_handle, reason) {
+            eprintln!("Failed to stop recording: {}", e);
+        }
 
-    Ok(source_data)
+        return;
+    }
 }
 
-use std::sync::atomic::{AtomicBool, Ordering};
+// blocks until the capture is stopped, compression carries on in the background
+fn end_session(app_handle: &tauri::AppHandle, reason: &str) -> Result<(), String> {
+    let state = app_handle.state::<Mutex<RecordingSession>>();
+    let mut session = state.lock().unwrap();
+
+    match session.phase {
+        // nothing was created yet, the countdown thread sees the phase change and quits
+        RecordingPhase::Countdown => {
+            session.phase = RecordingPhase::Idle;
+            emit_recording_event(
+                app_handle,
+                RecordingStateEvent {
+                    state: session.phase,
+                    project_id: None,
+                    countdown: None,
+                    reason: Some("cancelled".to_string()),
+                    error: None,
+                },
+            );
+            return Ok(());
+        }
+        // start_session_capture stops it once the capture is up
+        RecordingPhase::Starting => {
+            session.pending_stop = Some(reason.to_string());
+            return Ok(());
+        }
+        RecordingPhase::Recording | RecordingPhase::Paused => {}
+        RecordingPhase::Idle | RecordingPhase::Stopping => {
+            return Err("Not currently recording".to_string());
+        }
+    }
 
-struct MouseTrackingState {
-    mouse_positions: Arc<Mutex<Vec<serde_json::Value>>>,
-    start_time: SystemTime,
-    is_tracking: Arc<AtomicBool>,
-    is_recording: Arc<Mutex<bool>>,
-}
+    session.phase = RecordingPhase::Stopping;
+    emit_recording_state(app_handle, &session, None);
 
-#[tauri::command]
-fn start_mouse_tracking(app_handle: tauri::AppHandle) -> Result<bool, String> {
-    let state = MouseTrackingState {
-        mouse_positions: Arc::new(Mutex::new(Vec::new())),
-        start_time: SystemTime::now(),
-        is_tracking: Arc::new(AtomicBool::new(true)),
-        is_recording: Arc::new(Mutex::new(false)),
-    };
+    let project_id = session.project_id.clone().unwrap_or_default();
+    let tracks_mouse = session.tracks_mouse;
+    drop(session);
 
-    let mouse_positions = state.mouse_positions.clone();
-    let start_time = state.start_time;
-    let is_tracking = state.is_tracking.clone();
+    // both get stopped even if one of them fails
+    let tracked = if tracks_mouse {
+        stop_mouse_tracking(app_handle.clone(), project_id.clone()).map(|_| ())
+    } else {
+        Ok(())
+    };
+    let captured = stop_capture(app_handle, &project_id);
+    let stopped = tracked.and(captured);
+
+    let mut session = state.lock().unwrap();
+    session.phase = RecordingPhase::Idle;
+    emit_recording_event(
+        app_handle,
+        RecordingStateEvent {
+            state: session.phase,
+            project_id: session.project_id.clone(),
+            countdown: None,
+            reason: Some(reason.to_string()),
+            error: stopped.as_ref().err().cloned(),
+        },
+    );
 
-    thread::spawn(move || {
-        let device_state = DeviceState::new();
-        while is_tracking.load(Ordering::Relaxed) {
-            let mouse: MouseState = device_state.get_mouse();
-            let now = SystemTime::now();
-            let timestamp = now.duration_since(start_time).unwrap().as_millis();
+    stopped
+}
 
-            let position = json!({
-                "x": mouse.coords.0,
-                "y": mouse.coords.1,
-                "timestamp": timestamp
-            });
+fn toggle_recording(app_handle: &tauri::AppHandle) -> Result<(), String> {
+    let phase = app_handle
+        .state::<Mutex<RecordingSession>>()
+        .lock()
+        .unwrap()
+        .phase;
 
-            mouse_positions.lock().unwrap().push(position);
-            thread::sleep(Duration::from_millis(100));
-        }
-    });
+    match phase {
+        RecordingPhase::Idle => begin_session(app_handle, None),
+        RecordingPhase::Stopping => Ok(()),
+        _ => end_session(app_handle, "stopped"),
+    }
+}
 
-    app_handle.manage(state);
+fn toggle_pause(app_handle: &tauri::AppHandle) -> Result<(), String> {
+    let phase = app_handle
+        .state::<Mutex<RecordingSession>>()
+        .lock()
+        .unwrap()
+        .phase;
 
-    Ok(true)
+    match phase {
+        RecordingPhase::Recording => set_paused(app_handle, true),
+        RecordingPhase::Paused => set_paused(app_handle, false),
+        _ => Ok(()),
+    }
 }
 
-#[tauri::command]
-fn stop_mouse_tracking(app_handle: tauri::AppHandle, project_id: String) -> Result<bool, String> {
-    let state = app_handle.state::<MouseTrackingState>();
-
-    // Signal the tracking thread to stop
-    state.is_tracking.store(false, Ordering::Relaxed);
+fn register_recording_shortcuts(app_handle: &tauri::AppHandle) -> Result<(), tauri::Error> {
+    let mut shortcuts = app_handle.global_shortcut_manager();
 
-    // Give the thread some time to finish
-    thread::sleep(Duration::from_millis(200));
+    let handle = app_handle.clone();
+    shortcuts.register(RECORD_SHORTCUT, move || {
+        // stopping waits on the capture, keep it off the event loop
+        let handle = handle.clone();
+        thread::spawn(move || {
+            if let Err(e) = toggle_recording(&handle) {
+                eprintln!("{}: {}", RECORD_SHORTCUT, e);
+            }
+        });
+    })?;
 
-    let mouse_positions = state.mouse_positions.lock().unwrap().clone();
+    let handle = app_handle.clone();
+    shortcuts.register(PAUSE_SHORTCUT, move || {
+        if let Err(e) = toggle_pause(&handle) {
+            eprintln!("{}: {}", PAUSE_SHORTCUT, e);
+        }
+    })?;
 
-    let save_path = app_handle.path_resolver().app_data_dir().unwrap();
-    let file_path = save_path
-        .join("projects")
-        .join(&project_id)
-        .join("mousePositions.json");
+    Ok(())
+}
 
-    fs::write(
-        file_path,
-        serde_json::to_string_pretty(&mouse_positions).unwrap(),
-    )
-    .map_err(|e| e.to_string())?;
+fn main() {
+    tauri::Builder::default()
+        // .manage(Arc::new(Mutex::new(CaptureState {
+        //     capture_handler: None,
+        //     frame_handler: None,
+        // })))
+        .setup(|app| {
+            // another app holding the shortcuts shouldn't keep this one from starting
+            if let Err(e) = register_recording_shortcuts(&app.handle()) {
+                eprintln!("Failed to register recording shortcuts: {}", e);
+            }
 
-    Ok(true)
+            Ok(())
+        })
+        .manage(capture::default_backend())
+        .manage(MouseTrackingState::default())
+        .manage(capture::webcam::WebcamCapture::new())
+        .manage(Mutex::new(RecordingSession::default()))
+        .register_uri_scheme_protocol(MEDIA_PROTOCOL, project_media_protocol)
+        .invoke_handler(tauri::generate_handler![
+            transform_video,
+            create_project,
+            get_sources,
+            save_source_data,
+            start_mouse_tracking,
+            stop_mouse_tracking,
+            save_video_blob,
+            save_background_image,
+            get_project_data,
+            start_video_capture,
+            stop_video_capture,
+            pause_recording,
+            resume_recording,
+            start_recording_session,
+            stop_recording_session,
+            list_projects,
+            rename_project,
+            duplicate_project,
+            delete_project,
+            save_editor_state,
+            load_editor_state,
+            undo_editor_state,
+            redo_editor_state,
+            export_project_bundle,
+            import_project_bundle
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
 }
 
-#[tauri::command]
-fn save_video_blob(
-    app_handle: tauri::AppHandle,
-    project_id: String,
-    buffer: Vec<u8>,
-) -> Result<bool, String> {
-    let save_path = app_handle.path_resolver().app_data_dir().unwrap();
-    let file_path = save_path
-        .join("projects")
-        .join(&project_id)
-        .join("originalCapture.webm");
-
-    fs::write(file_path, buffer).map_err(|e| e.to_string())?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use capture::{CaptureBackend, CaptureOptions, CursorMode};
 
-    Ok(true)
-}
+    // a fresh folder under the system temp dir, removed by the caller
+    fn test_dir() -> PathBuf {
+        let dir = env::temp_dir().join(format!("sunshot-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
 
-#[tauri::command]
-fn get_project_data(
-    app_handle: tauri::AppHandle,
-    current_project_id: String,
-) -> Result<serde_json::Value, String> {
-    let save_path = app_handle.path_resolver().app_data_dir().unwrap();
-    let project_path = save_path.join("projects").join(&current_project_id);
+    // frame count and size of the first video stream
+    fn probe_video(path: &Path) -> (usize, u32, u32) {
+        ffmpeg::init().unwrap();
 
-    let mouse_positions: serde_json::Value = serde_json::from_str(
-        &fs::read_to_string(project_path.join("mousePositions.json")).map_err(|e| e.to_string())?,
-    )
-    .map_err(|e| e.to_string())?;
+        let mut input = ffmpeg::format::input(&path).unwrap();
+        let stream = input.streams().best(ffmpeg::media::Type::Video).unwrap();
+        let index = stream.index();
+        let decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())
+            .and_then(|context| context.decoder().video())
+            .unwrap();
+        let (width, height) = (decoder.width(), decoder.height());
 
-    let source_data: serde_json::Value = serde_json::from_str(
-        &fs::read_to_string(project_path.join("sourceData.json")).map_err(|e| e.to_string())?,
-    )
-    .map_err(|e| e.to_string())?;
+        let frames = input
+            .packets()
+            .filter(|(stream, _)| stream.index() == index)
+            .count();
 
-    let original_capture = fs::read(project_path.join("capture.mp4")).map_err(|e| e.to_string())?;
+        (frames, width, height)
+    }
 
-    // let screens = Screen::all().map_err(|e| e.to_string())?;
-    // let primary_screen = screens
-    //     .into_iter()
-    //     .find(|s| s.display_info.is_primary)
-    //     .unwrap();
-    // let width = primary_screen.display_info.width;
-    // let height = primary_screen.display_info.height;
+    fn editor_state(duration: i32) -> EditorState {
+        EditorState {
+            video_track: None,
+            zoom_tracks: Vec::new(),
+            export_settings: ExportSettings {
+                duration: Some(duration),
+                ..Default::default()
+            },
+        }
+    }
 
-    // let resolution = if width <= 1920 && height <= 1080 {
-    //     "hd"
-    // } else {
-    //     "4k"
-    // };
+    #[test]
+    fn history_skips_unchanged_states() {
+        let mut history = EditorHistory::default();
 
-    Ok(json!({
-        "currentProjectId": current_project_id,
-        "mousePositions": mouse_positions,
-        "originalCapture": original_capture,
-        "sourceData": source_data,
-        // "resolution": resolution,
-    }))
-}
+        assert!(history.push(editor_state(1)));
+        assert!(!history.push(editor_state(1)));
+        assert!(history.push(editor_state(2)));
 
-use std::ffi::c_void;
-use windows_capture::monitor::Monitor;
-use windows_capture::{
-    capture::GraphicsCaptureApiHandler,
-    encoder::{AudioSettingsBuilder, ContainerSettingsBuilder, VideoEncoder, VideoSettingsBuilder},
-    frame::Frame,
-    graphics_capture_api::InternalCaptureControl,
-    settings::{ColorFormat, CursorCaptureSettings, DrawBorderSettings, Settings},
-};
+        assert_eq!(history.snapshots.len(), 2);
+        assert_eq!(history.cursor, 1);
+    }
 
-struct Capture {
-    encoder: Option<VideoEncoder>,
-    is_recording: Arc<Mutex<bool>>,
-    output_path: String,
-    compressed_path: String,
-}
+    #[test]
+    fn history_undoes_and_redoes() {
+        let mut history = EditorHistory::default();
 
-impl GraphicsCaptureApiHandler for Capture {
-    type Flags = (String, String, u32, u32, Arc<Mutex<bool>>);
-    type Error = Box<dyn std::error::Error + Send + Sync>;
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.redo(), None);
 
-    fn new(
-        (output_path, compressed_path, width, height, is_recording): Self::Flags,
-    ) -> Result<Self, Self::Error> {
-        let encoder = VideoEncoder::new(
-            VideoSettingsBuilder::new(width, height),
-            AudioSettingsBuilder::default().disabled(true),
-            ContainerSettingsBuilder::default(),
-            &output_path,
-        )?;
+        for duration in 1..=3 {
+            history.push(editor_state(duration));
+        }
 
-        Ok(Self {
-            encoder: Some(encoder),
-            is_recording,
-            output_path,
-            compressed_path,
-        })
+        assert_eq!(history.undo(), Some(editor_state(2)));
+        assert_eq!(history.undo(), Some(editor_state(1)));
+        assert_eq!(history.undo(), None);
+
+        assert_eq!(history.redo(), Some(editor_state(2)));
+        assert_eq!(history.redo(), Some(editor_state(3)));
+        assert_eq!(history.redo(), None);
     }
 
-    fn on_frame_arrived(
-        &mut self,
-        frame: &mut Frame,
-        capture_control: InternalCaptureControl,
-    ) -> Result<(), Self::Error> {
-        if let Some(encoder) = &mut self.encoder {
-            encoder.send_frame(frame)?;
+    #[test]
+    fn history_drops_redo_branch_on_edit() {
+        let mut history = EditorHistory::default();
+
+        for duration in 1..=3 {
+            history.push(editor_state(duration));
         }
 
-        if !*self.is_recording.lock().unwrap() {
-            if let Some(encoder) = self.encoder.take() {
-                encoder.finish()?;
-            }
-            capture_control.stop();
+        history.undo();
+        history.undo();
+
+        // the autosave after an undo brings back the same state, that isn't an edit
+        assert!(!history.push(editor_state(1)));
+        assert_eq!(history.snapshots.len(), 3);
+
+        assert!(history.push(editor_state(4)));
+        assert_eq!(history.snapshots, vec![editor_state(1), editor_state(4)]);
+        assert_eq!(history.redo(), None);
+        assert_eq!(history.undo(), Some(editor_state(1)));
+    }
+
+    #[test]
+    fn history_keeps_the_latest_snapshots() {
+        let mut history = EditorHistory::default();
+
+        for duration in 0..(MAX_HISTORY_SNAPSHOTS as i32 + 10) {
+            history.push(editor_state(duration));
         }
 
-        Ok(())
+        assert_eq!(history.snapshots.len(), MAX_HISTORY_SNAPSHOTS);
+        assert_eq!(history.cursor, MAX_HISTORY_SNAPSHOTS - 1);
+        assert_eq!(history.snapshots[0], editor_state(10));
     }
 
-    fn on_closed(&mut self) -> Result<(), Self::Error> {
-        println!("Capture Session Closed");
-        Ok(())
+    // needs ffmpeg on the PATH, which the synthetic capture pipes into
+    #[test]
+    fn synthetic_recording_exports() {
+        let dir = test_dir();
+        let capture_path = dir.join("capture.mp4");
+
+        let backend = capture::synthetic::SyntheticBackend::new();
+        let is_recording = Arc::new(Mutex::new(true));
+
+        backend
+            .start(CaptureOptions {
+                source: Source::Window { id: 1 },
+                width: 1920,
+                height: 1080,
+                output_path: capture_path.to_string_lossy().to_string(),
+                max_size: Some((640, 360)),
+                cursor_mode: CursorMode::Hidden,
+                is_recording: is_recording.clone(),
+                clock: capture::RecordingClock::new(),
+                on_frame: None,
+                on_source_bounds: None,
+            })
+            .unwrap();
+
+        thread::sleep(Duration::from_millis(500));
+        backend.stop().unwrap();
+        backend.wait_finished(Duration::from_secs(30)).unwrap();
+
+        let (captured_frames, width, height) = probe_video(&capture_path);
+        assert!(captured_frames > 0);
+        assert_eq!((width, height), (640, 360));
+
+        fs::write(
+            dir.join("mousePositions.json"),
+            json!([{ "x": 960, "y": 540, "timestamp": 0 }]).to_string(),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("sourceData.json"),
+            json!({ "x": 0, "y": 0, "width": 1920, "height": 1080, "scale_factor": 1.0 })
+                .to_string(),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("config.json"),
+            json!({
+                "duration": 500,
+                "positions_file": "mousePositions.json",
+                "source_file": "sourceData.json",
+                "input_file": "capture.mp4",
+                "output_file": "output.mp4",
+                "zoom_info": [],
+                "background_info": [],
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        do_transform_video(dir.join("config.json").to_string_lossy().to_string()).unwrap();
+
+        let (exported_frames, width, height) = probe_video(&dir.join("output.mp4"));
+        assert_eq!(exported_frames, captured_frames);
+        assert_eq!((width, height), (640, 360));
+
+        fs::remove_dir_all(dir).unwrap();
     }
-}
 
-#[tauri::command]
-async fn start_video_capture(
-    app_handle: tauri::AppHandle,
-    hwnd: usize,
-    width: u32,
-    height: u32,
-    project_id: String,
-) -> Result<(), String> {
-    let state = app_handle.state::<MouseTrackingState>();
-    let mut is_recording = state.is_recording.lock().unwrap();
+    #[test]
+    fn fit_within_keeps_the_aspect_ratio() {
+        assert_eq!(capture::fit_within(1920, 1080, None), (1920, 1080));
+        assert_eq!(
+            capture::fit_within(1920, 1080, Some((1280, 720))),
+            (1280, 720)
+        );
+        assert_eq!(
+            capture::fit_within(1920, 1080, Some((1280, 1280))),
+            (1280, 720)
+        );
+        // never upscales
+        assert_eq!(
+            capture::fit_within(640, 360, Some((1920, 1080))),
+            (640, 360)
+        );
+        // encoders need even sizes
+        assert_eq!(capture::fit_within(1001, 701, None), (1000, 700));
+        assert_eq!(capture::fit_within(0, 0, None), (2, 2));
+    }
 
-    if *is_recording {
-        return Err("Already recording".to_string());
+    #[test]
+    fn recording_clock_stands_still_while_paused() {
+        let clock = capture::RecordingClock::new();
+
+        assert!(clock.resume().is_err());
+        clock.pause().unwrap();
+        assert!(clock.pause().is_err());
+
+        let paused = clock.elapsed();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(clock.elapsed(), paused);
+
+        clock.resume().unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(clock.elapsed() >= paused + Duration::from_millis(50));
+        assert!(!clock.is_paused());
     }
 
-    *is_recording = true;
-    drop(is_recording);
+    #[test]
+    fn sample_gradient_blends_between_stops() {
+        let stop = |position: f64, value: f64| GradientStop {
+            position,
+            color: RgbField {
+                r: value,
+                g: value,
+                b: 255.0 - value,
+            },
+        };
+        let stops = [stop(0.2, 0.0), stop(0.6, 200.0), stop(1.0, 100.0)];
 
-    let hwnd = HWND(hwnd as *mut _);
-    let raw_hwnd = hwnd.0 as *mut c_void;
-    let target_window: Window = unsafe { Window::from_raw_hwnd(raw_hwnd) };
+        assert_eq!(sample_gradient(&[], 0.5), (0.0, 0.0, 0.0));
+        // held flat before the first stop
+        assert_eq!(sample_gradient(&stops, 0.0), (0.0, 0.0, 255.0));
 
-    let app_data_dir = app_handle
-        .path_resolver()
-        .app_data_dir()
-        .ok_or("Failed to get app data directory")?;
-    let project_path = app_data_dir.join("projects").join(&project_id);
-    let output_path = project_path
-        .join("capture_pre.mp4")
-        .to_str()
-        .unwrap()
-        .to_string();
-    let compressed_path = project_path
-        .join("capture.mp4")
-        .to_str()
-        .unwrap()
-        .to_string();
+        for (t, expected) in [(0.4, 100.0), (0.8, 150.0), (1.0, 100.0)] {
+            let (r, g, b) = sample_gradient(&stops, t);
 
-    // hardcode hd for testing to avoid miscolored recording,
-    // TODO: scale to fullscreen width / height for users
-    if (width > 1920 || height > 1080) {
-        let primary_monitor = Monitor::primary().expect("There is no primary monitor");
+            assert!((r - expected).abs() < 1e-9, "r at {}", t);
+            assert!((g - expected).abs() < 1e-9, "g at {}", t);
+            assert!((b - (255.0 - expected)).abs() < 1e-9, "b at {}", t);
+        }
+    }
 
-        let settings = Settings::new(
-            primary_monitor,
-            CursorCaptureSettings::Default,
-            DrawBorderSettings::Default,
-            ColorFormat::Rgba8,
-            (
-                output_path,
-                compressed_path,
-                1920,
-                1080,
-                state.is_recording.clone(),
-            ),
+    #[test]
+    fn combo_label_filters_typing() {
+        let keys = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        let shortcuts = KeystrokeFilter::default();
+        let everything = KeystrokeFilter {
+            only_shortcuts: false,
+            ignore: Vec::new(),
+        };
+
+        assert_eq!(
+            combo_label(&keys(&["LShift", "KeyP", "LControl"]), &shortcuts),
+            Some("Ctrl+Shift+P".to_string())
+        );
+        assert_eq!(
+            combo_label(&keys(&["Enter"]), &shortcuts),
+            Some("Enter".to_string())
+        );
+        assert_eq!(combo_label(&keys(&["A"]), &shortcuts), None);
+        assert_eq!(combo_label(&keys(&["LShift", "A"]), &shortcuts), None);
+        assert_eq!(
+            combo_label(&keys(&["A"]), &everything),
+            Some("A".to_string())
+        );
+        // modifiers alone aren't a press
+        assert_eq!(
+            combo_label(&keys(&["LControl", "LShift"]), &everything),
+            None
         );
 
-        // std::thread::spawn(move || {
-        if let Err(e) = Capture::start(settings) {
-            eprintln!("Capture error: {}", e);
-            // Ensure is_recording is set to false if an error occurs
-            *state.is_recording.lock().unwrap() = false;
-        }
-        // });
-    } else {
-        let settings = Settings::new(
-            target_window,
-            CursorCaptureSettings::Default,
-            DrawBorderSettings::Default,
-            ColorFormat::Rgba8,
-            (
-                output_path,
-                compressed_path,
-                width,
-                height,
-                state.is_recording.clone(),
-            ),
+        let ignoring = KeystrokeFilter {
+            only_shortcuts: true,
+            ignore: vec!["ctrl+v".to_string(), "Enter".to_string()],
+        };
+
+        assert_eq!(combo_label(&keys(&["LControl", "V"]), &ignoring), None);
+        assert_eq!(combo_label(&keys(&["LAlt", "Enter"]), &ignoring), None);
+        assert_eq!(
+            combo_label(&keys(&["LControl", "C"]), &ignoring),
+            Some("Ctrl+C".to_string())
+        );
+    }
+
+    #[test]
+    fn resolve_config_path_falls_back_next_to_the_config() {
+        let dir = test_dir();
+        fs::write(dir.join("capture.mp4"), b"").unwrap();
+
+        let resolved = |path: &Path| resolve_config_path(&dir, &path.to_string_lossy());
+        let moved = env::temp_dir()
+            .join("sunshot-moved-project")
+            .join("capture.mp4");
+        let missing = env::temp_dir()
+            .join("sunshot-moved-project")
+            .join("other.mp4");
+
+        assert_eq!(
+            resolved(Path::new("capture.mp4")),
+            dir.join("capture.mp4").to_string_lossy()
         );
+        assert_eq!(resolved(&moved), dir.join("capture.mp4").to_string_lossy());
+        // kept as is when there is nothing to fall back to
+        assert_eq!(resolved(&missing), missing.to_string_lossy());
 
-        // std::thread::spawn(move || {
-        if let Err(e) = Capture::start(settings) {
-            eprintln!("Capture error: {}", e);
-            // Ensure is_recording is set to false if an error occurs
-            *state.is_recording.lock().unwrap() = false;
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn bundle_paths_stay_inside_the_project() {
+        assert!(is_valid_bundle_path("config.json"));
+        assert!(is_valid_bundle_path("backgrounds/sky.jpg"));
+
+        for path in [
+            "",
+            "../config.json",
+            "a/../../b",
+            "a//b",
+            "./a",
+            "C:/a",
+            "a\\b",
+            "/a",
+        ] {
+            assert!(!is_valid_bundle_path(path), "{} should be rejected", path);
         }
-        // });
     }
 
-    Ok(())
-}
+    // a bundle holding config.json, with the manifest claiming the given digest
+    fn write_test_bundle(path: &Path, entry: &str, sha256: &str) {
+        let contents = b"{}";
+        let manifest = json!({
+            "format_version": BUNDLE_FORMAT_VERSION,
+            "project_id": "test",
+            "meta": { "name": "Test", "created_at": 0 },
+            "exported_at": 0,
+            "files": [{ "path": entry, "size": contents.len(), "sha256": sha256 }],
+        });
+
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        zip.start_file(format!("files/{}", entry), SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(contents).unwrap();
+        zip.start_file("manifest.json", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(manifest.to_string().as_bytes()).unwrap();
+        zip.finish().unwrap();
+    }
 
-#[tauri::command]
-async fn stop_video_capture(
-    app_handle: tauri::AppHandle,
-    project_id: String,
-) -> Result<(), String> {
-    let app_data_dir = app_handle
-        .path_resolver()
-        .app_data_dir()
-        .ok_or("Failed to get app data directory")?;
-    let project_path = app_data_dir.join("projects").join(&project_id);
-    let output_path = project_path
-        .join("capture_pre.mp4")
-        .to_str()
-        .unwrap()
-        .to_string();
-    let compressed_path = project_path
-        .join("capture.mp4")
-        .to_str()
-        .unwrap()
-        .to_string();
+    #[test]
+    fn extract_bundle_checks_the_manifest() {
+        let dir = test_dir();
+        let bundle = dir.join("test.sunshot");
+        let bundle_path = bundle.to_string_lossy().to_string();
+        let (_, digest) = copy_and_hash(&mut b"{}".as_slice(), &mut io::sink()).unwrap();
 
-    let state = app_handle.state::<MouseTrackingState>();
-    let mut is_recording = state.is_recording.lock().unwrap();
+        write_test_bundle(&bundle, "backgrounds/config.json", &digest);
+        extract_bundle(&bundle_path, &dir.join("ok")).unwrap();
+        assert!(dir.join("ok/backgrounds/config.json").is_file());
 
-    if !*is_recording {
-        return Err("Not currently recording".to_string());
+        write_test_bundle(&bundle, "config.json", &"0".repeat(64));
+        let error = extract_bundle(&bundle_path, &dir.join("tampered")).unwrap_err();
+        assert!(error.contains("Integrity check failed"));
+
+        write_test_bundle(&bundle, "../config.json", &digest);
+        let error = extract_bundle(&bundle_path, &dir.join("escaped")).unwrap_err();
+        assert!(error.contains("Invalid file"));
+        assert!(!dir.join("config.json").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn bundles_import_only_once_complete() {
+        let dir = test_dir();
+        let project_id = Uuid::new_v4().to_string();
+        let project_path = dir.join("source").join(&project_id);
+        let projects_path = dir.join("projects");
+        fs::create_dir_all(project_path.join("backgrounds")).unwrap();
+        fs::create_dir_all(&projects_path).unwrap();
+
+        fs::write(project_path.join("capture.mp4"), b"video").unwrap();
+        fs::write(project_path.join("backgrounds/sky.jpg"), b"image").unwrap();
+        write_project_meta(
+            &project_path,
+            &ProjectMeta {
+                name: "Demo".to_string(),
+                created_at: 1,
+            },
+        )
+        .unwrap();
+        fs::write(
+            project_path.join("config.json"),
+            json!({
+                "duration": 500,
+                "positions_file": project_path.join("mousePositions.json"),
+                "source_file": "sourceData.json",
+                "input_file": project_path.join("capture.mp4"),
+                "output_file": "output.mp4",
+                "zoom_info": [],
+                "background_info": [],
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let bundle = dir.join("demo.sunshot");
+        write_bundle(
+            &project_path,
+            &project_id,
+            File::create(&bundle).unwrap(),
+            false,
+            false,
+        )
+        .unwrap();
+        let bundle_path = bundle.to_string_lossy().to_string();
+
+        let staging = |name: &str| dir.join("imports").join(name);
+        let imported = import_bundle(&bundle_path, &staging("a"), &projects_path).unwrap();
+        assert_eq!(imported, project_id);
+        assert!(!staging("a").exists());
+
+        let imported_path = projects_path.join(&imported);
+        assert!(imported_path.join("backgrounds/sky.jpg").is_file());
+        let config: Config =
+            serde_json::from_str(&fs::read_to_string(imported_path.join("config.json")).unwrap())
+                .unwrap();
+        assert_eq!(config.input_file, "capture.mp4");
+
+        // a second import of the same project gets its own id
+        let again = import_bundle(&bundle_path, &staging("b"), &projects_path).unwrap();
+        assert_ne!(again, project_id);
+        assert_eq!(
+            read_project_meta(&projects_path.join(&again)).name,
+            "Demo (Imported)"
+        );
+
+        // nothing reaches the library when the bundle doesn't check out
+        let mut data = fs::read(&bundle).unwrap();
+        let at = data.windows(5).position(|bytes| bytes == b"image").unwrap();
+        data[at] = b'I';
+        fs::write(&bundle, data).unwrap();
+
+        assert!(import_bundle(&bundle_path, &staging("c"), &projects_path).is_err());
+        assert_eq!(fs::read_dir(&projects_path).unwrap().count(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
     }
 
-    *is_recording = false;
+    #[test]
+    fn cursor_follows_a_window_resized_mid_recording() {
+        let bounds = |timestamp, x, width| SourceBounds {
+            timestamp,
+            x,
+            y: 50,
+            width,
+            height: 600,
+        };
+        let source = SourceFile {
+            x: 100,
+            y: 50,
+            width: 800,
+            height: 600,
+            scale_factor: 1.0,
+            bounds: vec![bounds(0, 100, 800), bounds(1000, 200, 1600)],
+        };
+        let to_frame = |x, y, timestamp| source.mouse_to_frame(x, y, timestamp, 800, 600);
 
-    println!("sleep");
+        assert_eq!(to_frame(500.0, 350.0, 500), (400.0, 300.0));
+        // moved right and twice as wide, capture scaled it back into the same 800x600
+        assert_eq!(to_frame(1000.0, 350.0, 1500), (400.0, 300.0));
+        assert_eq!(to_frame(1800.0, 650.0, 1500), (800.0, 600.0));
+    }
 
-    let app_handle = app_handle.clone();
+    #[test]
+    fn timed_backgrounds_fade_in_and_out() {
+        let solid = |value: f64, start_time, end_time| BackgroundInfo {
+            kind: BackgroundKind::Background(Background::Solid {
+                color: RgbField {
+                    r: value,
+                    g: value,
+                    b: value,
+                },
+            }),
+            start_time,
+            end_time,
+            animation: BackgroundAnimation::default(),
+        };
+        let timeline = BackgroundTimeline::new(
+            &[solid(0.0, None, None), solid(255.0, Some(1000), Some(2000))],
+            64,
+            36,
+        )
+        .unwrap();
+        let recording = Video::new(Pixel::YUV420P, 64, 36);
+        let luma = |time_ms| timeline.render(&recording, time_ms).unwrap().data(0)[0] as i32;
+
+        let (black, white) = (luma(0), luma(1500));
+        let halfway = (black + white) / 2;
+        assert!(white > black);
+
+        assert!((luma(1250) - halfway).abs() <= 2);
+        assert_eq!(luma(1500), white);
+        // the switch back fades too instead of cutting
+        assert!((luma(1750) - halfway).abs() <= 2);
+        assert!(luma(1900) > black + 2 && luma(1900) < halfway);
+        assert_eq!(luma(2000), black);
+    }
 
-    thread::spawn(move || {
-        let app_handle = app_handle.clone();
-        thread::sleep(Duration::from_millis(5000));
+    #[test]
+    fn media_ranges_stay_within_a_chunk() {
+        let big = 10 * MAX_RANGE_CHUNK;
+        let chunk_end = MAX_RANGE_CHUNK - 1;
 
-        println!("compress");
+        // small files go out whole, big ones a chunk at a time
+        assert_eq!(media_byte_range(None, 1000), Ok(None));
+        assert_eq!(media_byte_range(None, big), Ok(Some((0, chunk_end))));
 
-        println!(
-            "PATH: {:?}",
-            env::var("PATH").unwrap_or_else(|_| "Not found".to_string())
+        assert_eq!(
+            media_byte_range(Some("bytes=0-99"), 1000),
+            Ok(Some((0, 99)))
+        );
+        assert_eq!(
+            media_byte_range(Some("bytes=-100"), 1000),
+            Ok(Some((900, 999)))
+        );
+        assert_eq!(
+            media_byte_range(Some("bytes=100-"), 1000),
+            Ok(Some((100, 999)))
+        );
+        assert_eq!(
+            media_byte_range(Some("bytes=100-"), big),
+            Ok(Some((100, 100 + chunk_end)))
+        );
+        assert_eq!(
+            media_byte_range(Some("bytes=0-99, 200-299"), 1000),
+            Ok(Some((0, 99)))
         );
 
-        // let status = Command::new("ffmpeg")
-        //     .arg("-i")
-        //     .arg(output_path)
-        //     .arg("-vcodec")
-        //     .arg("libx264") // or "libx265" for H.265/HEVC compression
-        //     .arg("-crf")
-        //     .arg("5")
-        //     .arg(compressed_path)
-        //     .status()
-        //     .expect("Failed to execute ffmpeg command");
-        // let ffmpeg_path = r"C:\Users\alext\scoop\shims\ffmpeg.exe"; // Replace with actual path
+        assert_eq!(media_byte_range(Some("bytes=1000-"), 1000), Err(()));
+        assert_eq!(media_byte_range(Some("bytes=0-10"), 0), Err(()));
+        assert_eq!(media_byte_range(Some("frames=0-10"), 1000), Err(()));
+    }
 
+    #[test]
+    fn library_cache_thumbnails_short_clips_and_tracks_size() {
+        let dir = test_dir();
         let status = Command::new("ffmpeg")
-            .arg("-i")
-            .arg(output_path)
-            .arg("-nostdin")
-            .arg("-c:v")
-            .arg("libx264")
-            .arg("-crf")
-            .arg("23")
-            .arg(compressed_path)
-            // .arg("-v")
-            // .arg("verbose")
-            // .arg("-ignore_editlist")
-            // .arg("1")
-            // .arg("-i")
-            // .arg(output_path)
-            // .arg("-c:v")
-            // .arg("libx264")
-            // .arg("-preset")
-            // .arg("medium")
-            // .arg("-crf")
-            // .arg("23")
-            // .arg("-c:a")
-            // .arg("copy")
-            // .arg(compressed_path)
+            .args([
+                "-v",
+                "error",
+                "-f",
+                "lavfi",
+                "-i",
+                "testsrc=size=320x180:rate=60",
+            ])
+            .args(["-t", "0.5", "-pix_fmt", "yuv420p"])
+            .arg(dir.join("capture.mp4"))
             .status()
-            .expect("Failed to execute ffmpeg command");
+            .unwrap();
+        assert!(status.success());
 
-        if status.success() {
-            println!("Video compressed successfully!");
-            app_handle.emit_all("video-compression", "success").unwrap();
-        } else {
-            eprintln!("Error compressing the video.");
-            app_handle.emit_all("video-compression", "error").unwrap();
-        }
-    });
+        // shorter than the usual one second seek
+        let cache = library_cache(&dir);
+        assert!(cache.has_thumbnail);
+        assert!((cache.duration.unwrap() - 500).abs() < 50);
 
-    Ok(())
-}
+        let thumbnail_stamp = file_stamp(&dir.join("thumbnail.jpg"));
+        let cache = library_cache(&dir);
+        assert_eq!(file_stamp(&dir.join("thumbnail.jpg")), thumbnail_stamp);
 
-fn main() {
-    tauri::Builder::default()
-        // .manage(Arc::new(Mutex::new(CaptureState {
-        //     capture_handler: None,
-        //     frame_handler: None,
-        // })))
-        .setup(|app| {
-            // Any additional setup can go here
-            Ok(())
-        })
-        .invoke_handler(tauri::generate_handler![
-            transform_video,
-            create_project,
-            get_sources,
-            save_source_data,
-            start_mouse_tracking,
-            stop_mouse_tracking,
-            save_video_blob,
-            get_project_data,
-            start_video_capture,
-            stop_video_capture
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        // within the size of libraryCache.json itself, which is written after measuring
+        let measured = |cache: &LibraryCache| cache.size_on_disk.abs_diff(dir_size(&dir)) < 1024;
+        assert!(measured(&cache));
+
+        fs::create_dir_all(dir.join("backgrounds")).unwrap();
+        fs::write(dir.join("backgrounds/sky.jpg"), vec![0; 4096]).unwrap();
+        let grown = library_cache(&dir);
+        assert!(measured(&grown));
+        assert!(grown.size_on_disk >= cache.size_on_disk + 4096);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -261,10 +261,8 @@ const Video = ({
   // we need to use "useMemo" here, so we don't create new video elment on any render
   const videoElement = React.useMemo(() => {
     const element = document.createElement("video");
-    const blob = new Blob([new Uint8Array(src)], { type: "video/mp4" });
-    const url = URL.createObjectURL(blob);
-    element.src = url;
-    // element.src = src;
+    // src is a sunshot:// url, streamed with range requests by the backend
+    element.src = src;
 
     element.onerror = (e) => {
       console.error("Error occurred while loading video:", e, element.error);
@@ -329,7 +327,7 @@ const Video = ({
   //   }, [videoElement]);
 
   const playCanvasVideo = () => {
-    console.info("srces", src[0], src[1], src[2]);
+    console.info("src", src);
 
     videoElement.play();
 
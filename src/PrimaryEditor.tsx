@@ -1,9 +1,18 @@
-import { Box, CircularProgress } from "@mui/material";
+import { Box, Button, CircularProgress } from "@mui/material";
 import { invoke } from "@tauri-apps/api/tauri";
 import KonvaPreview from "./KonvaPreview";
-import { useState } from "react";
+import { useEffect, useState } from "react";
 import {
   Position,
+  VideoTrack,
+  WebcamSettings,
+  FrameSettings,
+  CanvasSettings,
+  Overlay,
+  BumperSettings,
+  Redaction,
+  KeystrokeSettings,
+  ZoomTrack,
   useEditorContext,
 } from "./context/EditorContext/EditorContext";
 import { listen } from "@tauri-apps/api/event";
@@ -21,10 +30,31 @@ export interface StoredSourceData {
   // scale_factor?
 }
 
+export interface EditorState {
+  videoTrack: VideoTrack | null;
+  zoomTracks: ZoomTrack[];
+  exportSettings: {
+    duration: number | null;
+    zoomInfo: any[];
+    backgroundInfo: any[];
+    webcamInfo: WebcamSettings | null;
+    frameStyle: FrameSettings | null;
+    canvas: CanvasSettings | null;
+    reframe: "fit" | "follow_cursor" | null;
+    overlays: Overlay[] | null;
+    bumpers: BumperSettings | null;
+    redactions: Redaction[] | null;
+    keystrokeInfo: KeystrokeSettings | null;
+  };
+}
+
 export interface ProjectData {
   mousePositions: Position[];
-  originalCapture: any;
+  originalCapture: string;
+  hasWebcam: boolean;
+  hasKeystrokes: boolean;
   sourceData: StoredSourceData;
+  editorState: EditorState | null;
 }
 
 let mounts = 0;
@@ -33,12 +63,12 @@ function PrimaryEditor({ projectId = null }: any) {
   const [{ videoTrack, zoomTracks }, dispatch] = useEditorContext();
 
   const [positions, setPositions] = useState<Position[] | null>(null);
-  const [originalCapture, setOriginalCapture] = useState<any>(null);
+  const [originalCapture, setOriginalCapture] = useState<string | null>(null);
   const [originalDuration, setOriginalDuration] = useState<number | null>(null);
   const [sourceData, setSourceData] = useState<StoredSourceData | null>(null);
 
   const getVideoInfo = async () => {
-    let { mousePositions, originalCapture, sourceData }: ProjectData =
+    let { mousePositions, originalCapture, sourceData, editorState }: ProjectData =
       await invoke("get_project_data", {
         currentProjectId: projectId,
       });
@@ -54,6 +84,17 @@ function PrimaryEditor({ projectId = null }: any) {
       duration
     );
 
+    if (editorState) {
+      // restore before Tracks mounts so it doesn't create the default tracks
+      dispatch({
+        type: "multiple",
+        values: [
+          { key: "videoTrack", value: editorState.videoTrack },
+          { key: "zoomTracks", value: editorState.zoomTracks },
+        ],
+      });
+    }
+
     setPositions(mousePositions);
     setOriginalCapture(originalCapture);
     setOriginalDuration(duration);
@@ -78,6 +119,120 @@ function PrimaryEditor({ projectId = null }: any) {
     };
   }, []);
 
+  const getExportSettings = () => {
+    return {
+      duration: originalDuration,
+      zoomInfo: (zoomTracks ?? []).map((zoomTrack) => {
+        return {
+          start: zoomTrack.start,
+          end: zoomTrack.end,
+          zoom: zoomTrack.zoomFactor.exportValue,
+        };
+      }),
+      backgroundInfo: videoTrack?.gradient
+        ? [
+            {
+              ...videoTrack.gradient.exportProps,
+              animation: videoTrack.backgroundAnimation ?? undefined,
+            },
+          ]
+        : [],
+      webcamInfo: videoTrack?.webcam ?? null,
+      frameStyle: videoTrack?.frame ?? null,
+      canvas: videoTrack?.canvas ?? null,
+      reframe: videoTrack?.reframe ?? null,
+      overlays: videoTrack?.overlays ?? null,
+      bumpers: videoTrack?.bumpers ?? null,
+      redactions: videoTrack?.redactions ?? null,
+      keystrokeInfo: videoTrack?.keystrokes ?? null,
+    };
+  };
+
+  const saveEditorState = () =>
+    invoke("save_editor_state", {
+      projectId,
+      editorState: {
+        videoTrack,
+        zoomTracks,
+        exportSettings: getExportSettings(),
+      },
+    });
+
+  // autosave, each save is also an undo snapshot on the backend
+  useEffect(() => {
+    if (!projectId || !videoTrack || !zoomTracks) {
+      return;
+    }
+
+    const timeout = setTimeout(() => {
+      saveEditorState().catch((e) => console.error("autosave failed", e));
+    }, 1000);
+
+    return () => clearTimeout(timeout);
+  }, [projectId, videoTrack, zoomTracks]);
+
+  const restoreSnapshot = async (
+    command: "undo_editor_state" | "redo_editor_state"
+  ) => {
+    if (!projectId || !videoTrack || !zoomTracks) {
+      return;
+    }
+
+    // the last edit may still be waiting on the autosave
+    await saveEditorState();
+
+    const editorState: EditorState | null = await invoke(command, {
+      projectId,
+    });
+
+    if (editorState) {
+      dispatch({
+        type: "multiple",
+        values: [
+          { key: "videoTrack", value: editorState.videoTrack },
+          { key: "zoomTracks", value: editorState.zoomTracks },
+        ],
+      });
+    }
+  };
+
+  const undo = () =>
+    restoreSnapshot("undo_editor_state").catch((e) =>
+      console.error("undo failed", e)
+    );
+  const redo = () =>
+    restoreSnapshot("redo_editor_state").catch((e) =>
+      console.error("redo failed", e)
+    );
+
+  // ctrl+z / ctrl+shift+z / ctrl+y, text fields keep their own undo
+  useEffect(() => {
+    const handleKeyDown = (event: KeyboardEvent) => {
+      const target = event.target as HTMLElement | null;
+
+      if (
+        !(event.ctrlKey || event.metaKey) ||
+        target?.closest("input, textarea, [contenteditable]")
+      ) {
+        return;
+      }
+
+      const key = event.key.toLowerCase();
+
+      if (key === "z" && !event.shiftKey) {
+        event.preventDefault();
+        undo();
+      } else if ((key === "z" && event.shiftKey) || key === "y") {
+        event.preventDefault();
+        redo();
+      }
+    };
+
+    window.addEventListener("keydown", handleKeyDown);
+
+    return () => window.removeEventListener("keydown", handleKeyDown);
+  });
+
   async function handleTransformVideo() {
     if (!videoTrack?.gradient || !zoomTracks) {
       console.warn("set all settings before export");
@@ -91,18 +246,7 @@ function PrimaryEditor({ projectId = null }: any) {
 
     await invoke("transform_video", {
       projectId,
-      duration: originalDuration,
-      zoomInfo: zoomTracks.map((zoomTrack) => {
-        return {
-          // id: zoomTrack.id,
-          // name: zoomTrack.name,
-          start: zoomTrack.start,
-          end: zoomTrack.end,
-          // easing: zoomTrack.easing,
-          zoom: zoomTrack.zoomFactor.exportValue,
-        };
-      }),
-      backgroundInfo: [videoTrack.gradient.exportProps],
+      settings: getExportSettings(),
     });
   }
 
@@ -114,6 +258,14 @@ function PrimaryEditor({ projectId = null }: any) {
     <Box display="flex" flexDirection="column">
       <Box display="flex" flexDirection="row">
         <Box display="flex" flexDirection="column">
+          <Box display="flex" gap="10px">
+            <Button size="small" onClick={undo}>
+              Undo
+            </Button>
+            <Button size="small" onClick={redo}>
+              Redo
+            </Button>
+          </Box>
           <KonvaPreview
             positions={positions}
             originalCapture={originalCapture}
@@ -124,7 +276,7 @@ function PrimaryEditor({ projectId = null }: any) {
           <Tracks originalDuration={originalDuration} />
         </Box>
         <Box>
-          <Properties />
+          <Properties projectId={projectId} />
         </Box>
       </Box>
     </Box>
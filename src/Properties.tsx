@@ -10,7 +10,7 @@ import { useEditorContext } from "./context/EditorContext/EditorContext";
 import ZoomProperties from "./ZoomProperties";
 import VideoProperties from "./VideoProperties";
 
-const Properties: React.FC<any> = () => {
+const Properties: React.FC<any> = ({ projectId = null }) => {
   const [{ videoTrack, zoomTracks, selectedTrack }, dispatch] =
     useEditorContext();
 
@@ -51,6 +51,7 @@ const Properties: React.FC<any> = () => {
         <>
           {trackKey === "videoTrack" ? (
             <VideoProperties
+              projectId={projectId}
               trackData={trackData}
               updateTrack={updateVideoTrack}
             />
@@ -5,7 +5,16 @@ import React from "react";
 import { invoke } from "@tauri-apps/api/tauri";
 
 import styles from "./SourceSelector.module.scss";
-import { Box, Button, MenuItem, Select, Typography } from "@mui/material";
+import {
+  Box,
+  Button,
+  Checkbox,
+  FormControlLabel,
+  MenuItem,
+  Select,
+  TextField,
+  Typography,
+} from "@mui/material";
 // import SourceSelector from "../components/SourceSelector/SourceSelector";
 // import toBuffer from "blob-to-buffer";
 import useAsyncEffect from "use-async-effect";
@@ -13,9 +22,22 @@ import { listen } from "@tauri-apps/api/event";
 
 // let currentMediaRecorder: MediaRecorder | null = null;
 
+export type CaptureSource =
+  | { kind: "window"; id: number }
+  | { kind: "monitor"; index: number }
+  | {
+      kind: "region";
+      monitor: number;
+      x: number;
+      y: number;
+      width: number;
+      height: number;
+    };
+
 export interface Source {
-  hwnd: number;
+  source: CaptureSource;
   title: string;
+  app_name: string | null;
   rect: {
     top: number;
     left: number;
@@ -24,10 +46,12 @@ export interface Source {
     width: number;
     height: number;
   };
+  scale_factor: number;
+  thumbnail: string | null;
 }
 
 export interface SourceData {
-  hwnd: string;
+  source: CaptureSource;
   name: string;
   width: number;
   height: number;
@@ -35,20 +59,68 @@ export interface SourceData {
   y: number;
 }
 
+// recordings are downscaled to fit, keeping their aspect ratio
+const maxOutputSizes: Record<string, { max_width: number; max_height: number }> =
+  {
+    "2160p": { max_width: 3840, max_height: 2160 },
+    "1440p": { max_width: 2560, max_height: 1440 },
+    "1080p": { max_width: 1920, max_height: 1080 },
+  };
+
+const countdowns = [0, 3, 5, 10];
+
+// payload of the backend's "recording-state" event
+export interface RecordingState {
+  state:
+    | "idle"
+    | "countdown"
+    | "starting"
+    | "recording"
+    | "paused"
+    | "stopping";
+  project_id: string | null;
+  countdown: number | null;
+  reason: string | null;
+  error: string | null;
+}
+
+const sourceKeyOf = (source: Source) => JSON.stringify(source.source);
+
 function SourceSelector({
   projectId = "",
   setProjectId = (value: string) => console.info("setProjectId", value),
   setCurrentView = (value: string) => console.info("setCurrentView", value),
 }: any) {
   const [sources, setSources] = React.useState<Source[]>([]);
-  const [selectedSource, setSelectedSource] = React.useState<number | null>(
+  const [selectedSource, setSelectedSource] = React.useState<string | null>(
     null
   );
+  const [useRegion, setUseRegion] = React.useState(false);
+  const [region, setRegion] = React.useState({
+    x: 0,
+    y: 0,
+    width: 1280,
+    height: 720,
+  });
+  const [maxOutput, setMaxOutput] = React.useState("native");
   const [isRecording, setIsRecording] = React.useState<boolean>(false);
+  const [isPaused, setIsPaused] = React.useState<boolean>(false);
+  const [countdown, setCountdown] = React.useState(3);
+  const [countdownLeft, setCountdownLeft] = React.useState<number | null>(
+    null
+  );
+  const [maxMinutes, setMaxMinutes] = React.useState("");
+  const [maxMegabytes, setMaxMegabytes] = React.useState("");
+  const [webcam, setWebcam] = React.useState("");
+  const [recordKeys, setRecordKeys] = React.useState(false);
+  const [shortcutsOnly, setShortcutsOnly] = React.useState(true);
+  const [ignoredKeys, setIgnoredKeys] = React.useState("");
   const [isLoading, setIsLoading] = React.useState(false);
 
   const loadSourcePreviews = async () => {
-    let sources: Source[] = await invoke("get_sources");
+    let sources: Source[] = await invoke("get_sources", {
+      includeThumbnails: true,
+    });
 
     sources = sources.filter(
       (source) => source.title !== "subshot-rs" && source.title !== ""
@@ -59,46 +131,53 @@ function SourceSelector({
     setSources(sources);
   };
 
-  const startRecording = async (sourceHwnd: number) => {
-    const source = sources.find((source) => source.hwnd === sourceHwnd);
+  const startRecording = async (sourceKey: string) => {
+    const source = sources.find((source) => sourceKeyOf(source) === sourceKey);
 
     if (!source) {
       return;
     }
 
-    const hwnd = source?.hwnd;
-
-    console.info("source", source, hwnd);
-
-    let { projectId }: { projectId: string } = await invoke("create_project");
-    let sourceData: SourceData = await invoke("save_source_data", {
-      // windowTitle: source.title,
-      hwnd,
-      currentProjectId: projectId,
-    });
-
-    setProjectId(projectId);
-
-    console.info("project", sourceHwnd, projectId, sourceData);
-
-    setIsRecording(true);
-    await invoke("start_mouse_tracking");
-    await invoke("start_video_capture", {
-      hwnd: sourceHwnd,
-      width: sourceData.width,
-      height: sourceData.height,
-      projectId,
+    // a region is a rectangle of the chosen monitor, relative to its top left
+    const captureSource: CaptureSource =
+      useRegion && source.source.kind === "monitor"
+        ? { kind: "region", monitor: source.source.index, ...region }
+        : source.source;
+
+    console.info("source", source, captureSource);
+
+    // the backend creates the project once the countdown is over, and reports
+    // every step through "recording-state" events
+    await invoke("start_recording_session", {
+      config: {
+        source: captureSource,
+        countdown,
+        max_duration: maxMinutes ? Math.round(Number(maxMinutes) * 60) : null,
+        max_file_size: maxMegabytes
+          ? Math.round(Number(maxMegabytes) * 1024 * 1024)
+          : null,
+        webcam: webcam || null,
+        // filtered while recording, so skipped keys never touch the disk
+        keystrokes: recordKeys
+          ? {
+              only_shortcuts: shortcutsOnly,
+              ignore: ignoredKeys
+                .split(",")
+                .map((key) => key.trim())
+                .filter((key) => key),
+            }
+          : null,
+        ...maxOutputSizes[maxOutput],
+      },
     });
   };
 
   const handleStopRecording = async () => {
-    // currentMediaRecorder?.stop();
-    setIsRecording(false);
-    setIsLoading(true);
-    await invoke("stop_mouse_tracking", { projectId });
-    await invoke("stop_video_capture", { projectId });
+    await invoke("stop_recording_session");
+  };
 
-    // wait so video capture can save files and such
+  const handleTogglePause = async () => {
+    await invoke(isPaused ? "resume_recording" : "pause_recording");
   };
 
   React.useEffect(() => {
@@ -109,6 +188,43 @@ function SourceSelector({
     };
   }, []);
 
+  // also covers sessions started and stopped with the global shortcuts
+  useAsyncEffect(async () => {
+    const unlisten: any = await listen<RecordingState>(
+      "recording-state",
+      (event) => {
+        const { state, project_id, countdown, reason, error } = event.payload;
+
+        console.info("recording-state event", event.payload);
+
+        if (project_id) {
+          setProjectId(project_id);
+        }
+
+        setCountdownLeft(state === "countdown" ? countdown : null);
+        setIsRecording(state !== "idle");
+        setIsPaused(state === "paused");
+
+        if (state === "stopping") {
+          setIsLoading(true);
+        }
+
+        // a cancelled countdown or a failed start leaves nothing to compress
+        if (state === "idle" && (error || reason === "cancelled")) {
+          setIsLoading(false);
+        }
+
+        if (error) {
+          console.error("recording error", error);
+        }
+      }
+    );
+
+    return () => {
+      unlisten();
+    };
+  }, []);
+
   useAsyncEffect(async () => {
     const unlisten: any = await listen<string>("video-compression", (event) => {
       console.log("video-compression event", event.payload); // Logs: "Hello from the backend!"
@@ -130,6 +246,10 @@ function SourceSelector({
   //   // ipcRenderer.sendSync("open-project");
   // };
 
+  const selected = sources.find(
+    (source) => sourceKeyOf(source) === selectedSource
+  );
+
   const handleStartRecording = () => {
     if (!selectedSource) {
       return;
@@ -157,7 +277,7 @@ function SourceSelector({
             }}
             value={selectedSource ? selectedSource : "init"}
             onChange={(e) => {
-              const value = e.target.value as number;
+              const value = e.target.value as string;
 
               if (!value) {
                 return;
@@ -168,15 +288,163 @@ function SourceSelector({
               setSelectedSource(value);
             }}
           >
-            <MenuItem value={0}>No source selected</MenuItem>
+            <MenuItem value={""}>No source selected</MenuItem>
             {sources?.map((source) => {
-              return <MenuItem value={source.hwnd}>{source.title}</MenuItem>;
+              const key = sourceKeyOf(source);
+
+              return (
+                <MenuItem key={key} value={key}>
+                  {source.thumbnail && (
+                    <img
+                      src={source.thumbnail}
+                      alt=""
+                      style={{ width: 64, marginRight: 8 }}
+                    />
+                  )}
+                  {source.app_name
+                    ? `${source.title} (${source.app_name})`
+                    : source.title}
+                </MenuItem>
+              );
             })}
           </Select>
 
+          {selected?.source.kind === "monitor" && (
+            <Box>
+              <FormControlLabel
+                control={
+                  <Checkbox
+                    checked={useRegion}
+                    onChange={(e) => setUseRegion(e.target.checked)}
+                  />
+                }
+                label="Record a region"
+              />
+              {useRegion &&
+                (["x", "y", "width", "height"] as const).map((field) => (
+                  <TextField
+                    key={field}
+                    label={field}
+                    type="number"
+                    size="small"
+                    style={{ width: "80px" }}
+                    value={region[field]}
+                    onChange={(e) =>
+                      setRegion({ ...region, [field]: Number(e.target.value) })
+                    }
+                  />
+                ))}
+            </Box>
+          )}
+
+          <Select
+            label="Max Output Size"
+            style={{
+              width: "300px",
+            }}
+            value={maxOutput}
+            onChange={(e) => setMaxOutput(e.target.value as string)}
+          >
+            <MenuItem value="native">Native resolution</MenuItem>
+            {Object.keys(maxOutputSizes).map((size) => (
+              <MenuItem key={size} value={size}>
+                Up to {size}
+              </MenuItem>
+            ))}
+          </Select>
+
+          <Select
+            label="Countdown"
+            style={{
+              width: "300px",
+            }}
+            value={countdown}
+            onChange={(e) => setCountdown(Number(e.target.value))}
+          >
+            {countdowns.map((seconds) => (
+              <MenuItem key={seconds} value={seconds}>
+                {seconds ? `${seconds} second countdown` : "No countdown"}
+              </MenuItem>
+            ))}
+          </Select>
+
+          <Box>
+            <TextField
+              label="Stop after (minutes)"
+              type="number"
+              size="small"
+              value={maxMinutes}
+              onChange={(e) => setMaxMinutes(e.target.value)}
+            />
+            <TextField
+              label="Stop at size (MB)"
+              type="number"
+              size="small"
+              value={maxMegabytes}
+              onChange={(e) => setMaxMegabytes(e.target.value)}
+            />
+          </Box>
+
+          <TextField
+            label="Webcam device or video file (optional)"
+            placeholder="/dev/video0"
+            size="small"
+            style={{ width: "300px" }}
+            value={webcam}
+            onChange={(e) => setWebcam(e.target.value)}
+          />
+
+          {/* only needed for the keystroke overlay */}
+          <Box>
+            <FormControlLabel
+              control={
+                <Checkbox
+                  checked={recordKeys}
+                  onChange={(e) => setRecordKeys(e.target.checked)}
+                />
+              }
+              label="Record keystrokes"
+            />
+            {recordKeys && (
+              <>
+                <FormControlLabel
+                  control={
+                    <Checkbox
+                      checked={shortcutsOnly}
+                      onChange={(e) => setShortcutsOnly(e.target.checked)}
+                    />
+                  }
+                  label="Shortcuts only"
+                />
+                <TextField
+                  label="Never record (e.g. Ctrl+V, Enter)"
+                  size="small"
+                  value={ignoredKeys}
+                  onChange={(e) => setIgnoredKeys(e.target.value)}
+                />
+              </>
+            )}
+          </Box>
+
+          <Typography variant="body2">
+            Ctrl+Shift+R starts or stops recording, Ctrl+Shift+P pauses it
+          </Typography>
+
           <Box className={styles.ctrls}>
-            {isRecording ? (
-              <Button onClick={handleStopRecording}>Stop Recording</Button>
+            {countdownLeft !== null ? (
+              <>
+                <Typography variant="h2">{countdownLeft}</Typography>
+                <Button onClick={handleStopRecording}>Cancel</Button>
+              </>
+            ) : isRecording ? (
+              <>
+                <Button onClick={handleTogglePause}>
+                  {isPaused ? "Resume Recording" : "Pause Recording"}
+                </Button>
+                <Button onClick={handleStopRecording} disabled={isLoading}>
+                  Stop Recording
+                </Button>
+              </>
             ) : (
               <Button
                 onClick={handleStartRecording}
@@ -1,5 +1,32 @@
-import { Box, styled } from "@mui/material";
+import {
+  Box,
+  Button,
+  Checkbox,
+  FormControlLabel,
+  MenuItem,
+  Select,
+  Slider,
+  TextField,
+  Typography,
+  styled,
+} from "@mui/material";
+import { invoke } from "@tauri-apps/api/tauri";
 import * as React from "react";
+import {
+  Bumper,
+  BumperSettings,
+  CanvasSettings,
+  Redaction,
+  Overlay,
+  createCaption,
+  FrameSettings,
+  KeystrokeSettings,
+  WebcamSettings,
+  defaultFrameSettings,
+  defaultFrameShadow,
+  defaultKeystrokeSettings,
+  defaultWebcamSettings,
+} from "./context/EditorContext/EditorContext";
 
 // import styles from "./VideoProperties.module.scss";
 
@@ -85,6 +112,67 @@ export const gradients = [
   },
 ];
 
+// exportProps are sent as-is in backgroundInfo, see Background in main.rs
+export const backgrounds = [
+  {
+    title: "Solid",
+    konvaProps: [0, "#1E1E24", 1, "#1E1E24"],
+    cssProps: "#1E1E24",
+    exportProps: {
+      kind: "solid",
+      color: { r: 30, g: 30, b: 36 },
+    },
+  },
+  {
+    title: "Linear gradient",
+    konvaProps: [0, "#4158D0", 0.46, "#C850C0", 1, "#FFCC70"],
+    cssProps: "linear-gradient(45deg, #4158D0 0%, #C850C0 46%, #FFCC70 100%)",
+    exportProps: {
+      kind: "linear_gradient",
+      angle: 45,
+      stops: [
+        { position: 0, color: { r: 65, g: 88, b: 208 } },
+        { position: 0.46, color: { r: 200, g: 80, b: 192 } },
+        { position: 1, color: { r: 255, g: 204, b: 112 } },
+      ],
+    },
+  },
+  {
+    title: "Radial gradient",
+    konvaProps: [0, "#8EC5FC", 1, "#3A1C71"],
+    cssProps: "radial-gradient(circle, #8EC5FC 0%, #3A1C71 100%)",
+    exportProps: {
+      kind: "radial_gradient",
+      center_x: 0.5,
+      center_y: 0.5,
+      stops: [
+        { position: 0, color: { r: 142, g: 197, b: 252 } },
+        { position: 1, color: { r: 58, g: 28, b: 113 } },
+      ],
+    },
+  },
+  {
+    title: "Blurred recording",
+    konvaProps: [0, "#9A9A9A", 1, "#3A3A3A"],
+    cssProps: "radial-gradient(circle, #9A9A9A 0%, #3A3A3A 100%)",
+    exportProps: {
+      kind: "blurred_recording",
+      blur: 24,
+      zoom: 1.2,
+    },
+  },
+];
+
+// copied into the project on pick, exportProps.path is filled in then
+const backgroundImages = [
+  {
+    title: "Image",
+    url: "/unsplash1.jpg",
+    konvaProps: [0, "#A7B6C2", 1, "#4B5A66"],
+    cssProps: "url(/unsplash1.jpg) center / cover",
+  },
+];
+
 const GradientGrid = styled(Box)`
   display: grid;
   grid-template-columns: repeat(5, 1fr);
@@ -99,10 +187,157 @@ const GradientGrid = styled(Box)`
   }
 `;
 
+const canvasPresets = {
+  source: "Same as recording",
+  landscape: "16:9 (1920x1080)",
+  vertical: "9:16 (1080x1920)",
+  square: "1:1 (1080x1080)",
+  portrait: "4:5 (1080x1350)",
+  custom: "Custom",
+};
+
+const redactionStyles = {
+  blur: "Blur",
+  pixelate: "Pixelate",
+  fill: "Solid fill",
+};
+
+const redactionFields: [keyof Redaction, string][] = [
+  ["x", "X"],
+  ["y", "Y"],
+  ["width", "Width"],
+  ["height", "Height"],
+];
+
+// where the keystroke HUD is centered, as fractions of the frame
+const keystrokePositions: Record<string, [number, number]> = {
+  bottom: [0.5, 0.9],
+  bottom_left: [0.2, 0.9],
+  bottom_right: [0.8, 0.9],
+  top: [0.5, 0.1],
+};
+
+const defaultBackgroundAnimation = { shift: 2, hue_rotation: 0 };
+
+const webcamCorners = {
+  top_left: "Top Left",
+  top_right: "Top Right",
+  bottom_left: "Bottom Left",
+  bottom_right: "Bottom Right",
+};
+
+const webcamShapes = {
+  rectangle: "Rectangle",
+  rounded: "Rounded",
+  circle: "Circle",
+};
+
+const webcamZoomBehaviors = {
+  stay: "Stay in place",
+  shrink: "Shrink",
+  move: "Move out of the way",
+};
+
 const VideoProperties: React.FC<any> = ({
-  // trackData = null,
+  projectId = null,
+  trackData = null,
   updateTrack = () => console.info("updateTrack"),
 }) => {
+  const webcam: WebcamSettings | null = trackData?.webcam ?? null;
+  const backgroundAnimation =
+    trackData?.backgroundAnimation ?? defaultBackgroundAnimation;
+  const frame: FrameSettings = trackData?.frame ?? defaultFrameSettings;
+  const canvas: CanvasSettings = trackData?.canvas ?? { preset: "source" };
+
+  const overlays: Overlay[] = trackData?.overlays ?? [];
+  const redactions: Redaction[] = trackData?.redactions ?? [];
+
+  const updateRedaction = (index: number, key: keyof Redaction, value: any) => {
+    updateTrack(
+      "redactions",
+      redactions.map((redaction, i) =>
+        i === index ? { ...redaction, [key]: value } : redaction
+      )
+    );
+  };
+
+  const bumpers: BumperSettings = trackData?.bumpers ?? { crossfade: 500 };
+
+  // an empty title removes the card
+  const setCardTitle = (key: "intro" | "outro", title: string) => {
+    updateTrack("bumpers", {
+      ...bumpers,
+      [key]: title ? { kind: "title_card", title, duration: 3000 } : null,
+    });
+  };
+
+  const cardTitle = (bumper?: Bumper | null) =>
+    bumper?.kind === "title_card" ? bumper.title : "";
+
+  const updateOverlay = (index: number, key: string, value: any) => {
+    updateTrack(
+      "overlays",
+      overlays.map((overlay, i) =>
+        i === index ? { ...overlay, [key]: value } : overlay
+      )
+    );
+  };
+
+  const setCanvasPreset = (preset: string) => {
+    updateTrack(
+      "canvas",
+      preset === "custom" ? { preset, width: 1920, height: 1080 } : { preset }
+    );
+  };
+
+  const updateFrame = (key: keyof FrameSettings, value: any) => {
+    updateTrack("frame", { ...frame, [key]: value });
+  };
+
+  // the editor sets the same percentage on every side
+  const setPadding = (percent: number) => {
+    const side = { unit: "percent", value: percent };
+    updateFrame("padding", {
+      top: side,
+      right: side,
+      bottom: side,
+      left: side,
+    });
+  };
+
+  const keystrokes: KeystrokeSettings | null = trackData?.keystrokes ?? null;
+
+  const updateKeystrokes = (key: keyof KeystrokeSettings, value: any) => {
+    updateTrack("keystrokes", { ...keystrokes, [key]: value });
+  };
+
+  const keystrokePosition =
+    Object.entries(keystrokePositions).find(
+      ([, [x, y]]) => x === keystrokes?.x && y === keystrokes?.y
+    )?.[0] ?? "bottom";
+
+  const updateWebcam = (key: keyof WebcamSettings, value: any) => {
+    updateTrack("webcam", { ...webcam, [key]: value });
+  };
+
+  const selectBackgroundImage = async (image: typeof backgroundImages[0]) => {
+    const fileName = image.url.split("/").pop();
+    const buffer = await (await fetch(image.url)).arrayBuffer();
+
+    const path = await invoke("save_background_image", {
+      projectId,
+      fileName,
+      buffer: Array.from(new Uint8Array(buffer)),
+    });
+
+    updateTrack("gradient", {
+      title: image.title,
+      konvaProps: image.konvaProps,
+      cssProps: image.cssProps,
+      exportProps: { kind: "image", path, fit: "cover" },
+    });
+  };
+
   return (
     <div>
       <h1 className={`spectrum-Heading spectrum-Heading--sizeL`}>
@@ -121,7 +356,462 @@ const VideoProperties: React.FC<any> = ({
             }}
           ></div>
         ))}
+        {backgrounds.map((background, i) => (
+          <div
+            key={`background-${i}`}
+            className="gradient"
+            title={background.title}
+            style={{
+              background: background.cssProps,
+            }}
+            onClick={() => {
+              updateTrack("gradient", background);
+            }}
+          ></div>
+        ))}
+        {backgroundImages.map((image, i) => (
+          <div
+            key={`image-${i}`}
+            className="gradient"
+            title={image.title}
+            style={{
+              background: image.cssProps,
+            }}
+            onClick={() => selectBackgroundImage(image)}
+          ></div>
+        ))}
       </GradientGrid>
+
+      {/* degrees per second, the export's default is a slow shift */}
+      <Typography variant="body2">Background motion</Typography>
+      <Slider
+        min={0}
+        max={20}
+        step={1}
+        value={backgroundAnimation.shift}
+        onChange={(_, value) =>
+          updateTrack("backgroundAnimation", {
+            ...backgroundAnimation,
+            shift: value,
+          })
+        }
+      />
+      <Typography variant="body2">Hue rotation</Typography>
+      <Slider
+        min={0}
+        max={60}
+        step={1}
+        value={backgroundAnimation.hue_rotation}
+        onChange={(_, value) =>
+          updateTrack("backgroundAnimation", {
+            ...backgroundAnimation,
+            hue_rotation: value,
+          })
+        }
+      />
+
+      <Typography variant="body2">Canvas</Typography>
+      <Select
+        size="small"
+        value={canvas.preset}
+        onChange={(e) => setCanvasPreset(e.target.value)}
+      >
+        {Object.entries(canvasPresets).map(([value, label]) => (
+          <MenuItem key={value} value={value}>
+            {label}
+          </MenuItem>
+        ))}
+      </Select>
+      {canvas.preset === "custom" && (
+        <Box display="flex" gap="10px" marginTop="10px">
+          <TextField
+            size="small"
+            type="number"
+            label="Width"
+            value={canvas.width}
+            onChange={(e) =>
+              updateTrack("canvas", { ...canvas, width: Number(e.target.value) })
+            }
+          />
+          <TextField
+            size="small"
+            type="number"
+            label="Height"
+            value={canvas.height}
+            onChange={(e) =>
+              updateTrack("canvas", {
+                ...canvas,
+                height: Number(e.target.value),
+              })
+            }
+          />
+        </Box>
+      )}
+
+      {/* crops instead of letterboxing, mostly for vertical exports */}
+      <FormControlLabel
+        control={
+          <Checkbox
+            checked={trackData?.reframe === "follow_cursor"}
+            onChange={(e) =>
+              updateTrack("reframe", e.target.checked ? "follow_cursor" : "fit")
+            }
+          />
+        }
+        label="Follow the cursor"
+      />
+
+      <Typography variant="body2">Padding</Typography>
+      <Slider
+        min={0}
+        max={25}
+        step={1}
+        value={
+          frame.padding.left.unit === "percent" ? frame.padding.left.value : 10
+        }
+        onChange={(_, value) => setPadding(value as number)}
+      />
+      <Typography variant="body2">Corner radius</Typography>
+      <Slider
+        min={0}
+        max={64}
+        step={1}
+        value={frame.corner_radius}
+        onChange={(_, value) => updateFrame("corner_radius", value)}
+      />
+      <Typography variant="body2">Frame border</Typography>
+      <Slider
+        min={0}
+        max={16}
+        step={1}
+        value={frame.border?.width ?? 0}
+        onChange={(_, value) =>
+          updateFrame(
+            "border",
+            value
+              ? {
+                  width: value,
+                  color: frame.border?.color ?? { r: 255, g: 255, b: 255 },
+                }
+              : null
+          )
+        }
+      />
+      <FormControlLabel
+        control={
+          <Checkbox
+            checked={!!frame.shadow}
+            onChange={(e) =>
+              updateFrame(
+                "shadow",
+                e.target.checked ? defaultFrameShadow : null
+              )
+            }
+          />
+        }
+        label="Drop shadow"
+      />
+
+      <Typography variant="body2">Captions</Typography>
+      {overlays.map((overlay, i) =>
+        overlay.kind === "text" ? (
+          <Box key={i} display="flex" gap="10px" marginTop="10px">
+            <TextField
+              size="small"
+              label="Text"
+              value={overlay.text}
+              onChange={(e) => updateOverlay(i, "text", e.target.value)}
+            />
+            <TextField
+              size="small"
+              type="number"
+              label="From (s)"
+              value={overlay.start / 1000}
+              onChange={(e) =>
+                updateOverlay(i, "start", Number(e.target.value) * 1000)
+              }
+            />
+            <TextField
+              size="small"
+              type="number"
+              label="To (s)"
+              value={overlay.end / 1000}
+              onChange={(e) =>
+                updateOverlay(i, "end", Number(e.target.value) * 1000)
+              }
+            />
+            <Button
+              size="small"
+              onClick={() =>
+                updateTrack(
+                  "overlays",
+                  overlays.filter((_, j) => j !== i)
+                )
+              }
+            >
+              Remove
+            </Button>
+          </Box>
+        ) : null
+      )}
+      <Button
+        size="small"
+        onClick={() => {
+          const lastEnd = overlays.reduce(
+            (end, overlay) => Math.max(end, overlay.end),
+            0
+          );
+          updateTrack("overlays", [...overlays, createCaption(lastEnd)]);
+        }}
+      >
+        Add caption
+      </Button>
+
+      <Typography variant="body2">Intro and outro</Typography>
+      <Box display="flex" gap="10px" marginTop="10px">
+        <TextField
+          size="small"
+          label="Intro title"
+          value={cardTitle(bumpers.intro)}
+          onChange={(e) => setCardTitle("intro", e.target.value)}
+        />
+        <TextField
+          size="small"
+          label="Outro title"
+          value={cardTitle(bumpers.outro)}
+          onChange={(e) => setCardTitle("outro", e.target.value)}
+        />
+      </Box>
+
+      {/* in capture pixels, applied before zooming */}
+      <Typography variant="body2">Redactions</Typography>
+      {redactions.map((redaction, i) => (
+        <Box key={i} display="flex" flexWrap="wrap" gap="10px" marginTop="10px">
+          {redactionFields.map(([key, label]) => (
+            <TextField
+              key={key}
+              size="small"
+              type="number"
+              label={label}
+              value={redaction[key]}
+              onChange={(e) => updateRedaction(i, key, Number(e.target.value))}
+            />
+          ))}
+          <TextField
+            size="small"
+            type="number"
+            label="From (s)"
+            value={(redaction.start_time ?? 0) / 1000}
+            onChange={(e) =>
+              updateRedaction(i, "start_time", Number(e.target.value) * 1000)
+            }
+          />
+          <TextField
+            size="small"
+            type="number"
+            label="To (s)"
+            value={
+              redaction.end_time === undefined ? "" : redaction.end_time / 1000
+            }
+            onChange={(e) =>
+              updateRedaction(
+                i,
+                "end_time",
+                e.target.value === ""
+                  ? undefined
+                  : Number(e.target.value) * 1000
+              )
+            }
+          />
+          <Select
+            size="small"
+            value={redaction.style}
+            onChange={(e) => updateRedaction(i, "style", e.target.value)}
+          >
+            {Object.entries(redactionStyles).map(([value, label]) => (
+              <MenuItem key={value} value={value}>
+                {label}
+              </MenuItem>
+            ))}
+          </Select>
+          <Button
+            size="small"
+            onClick={() =>
+              updateTrack(
+                "redactions",
+                redactions.filter((_, j) => j !== i)
+              )
+            }
+          >
+            Remove
+          </Button>
+        </Box>
+      ))}
+      <Button
+        size="small"
+        onClick={() =>
+          updateTrack("redactions", [
+            ...redactions,
+            { x: 0, y: 0, width: 200, height: 50, style: "blur" },
+          ])
+        }
+      >
+        Add redaction
+      </Button>
+
+      {/* needs keyboard events, recorded since the HUD was added */}
+      <FormControlLabel
+        control={
+          <Checkbox
+            checked={!!keystrokes}
+            onChange={(e) =>
+              updateTrack(
+                "keystrokes",
+                e.target.checked ? defaultKeystrokeSettings : null
+              )
+            }
+          />
+        }
+        label="Keystroke overlay"
+      />
+      {keystrokes && (
+        <Box display="flex" flexDirection="column" gap="10px">
+          <Select
+            size="small"
+            value={keystrokePosition}
+            onChange={(e) => {
+              const [x, y] = keystrokePositions[e.target.value];
+              updateTrack("keystrokes", { ...keystrokes, x, y });
+            }}
+          >
+            <MenuItem value="bottom">Bottom</MenuItem>
+            <MenuItem value="bottom_left">Bottom Left</MenuItem>
+            <MenuItem value="bottom_right">Bottom Right</MenuItem>
+            <MenuItem value="top">Top</MenuItem>
+          </Select>
+          <Typography variant="body2">Size</Typography>
+          <Slider
+            min={16}
+            max={96}
+            step={1}
+            value={keystrokes.size}
+            onChange={(_, value) => updateKeystrokes("size", value)}
+          />
+          <Typography variant="body2">Fade after (s)</Typography>
+          <Slider
+            min={0.5}
+            max={5}
+            step={0.1}
+            value={keystrokes.timeout / 1000}
+            onChange={(_, value) =>
+              updateKeystrokes("timeout", (value as number) * 1000)
+            }
+          />
+          {/* hides plain typing, keeps typed passwords out */}
+          <FormControlLabel
+            control={
+              <Checkbox
+                checked={keystrokes.only_shortcuts}
+                onChange={(e) =>
+                  updateKeystrokes("only_shortcuts", e.target.checked)
+                }
+              />
+            }
+            label="Shortcuts only"
+          />
+          <TextField
+            size="small"
+            label="Ignore (comma separated, e.g. Ctrl+V, Enter)"
+            defaultValue={keystrokes.ignore.join(", ")}
+            onBlur={(e) =>
+              updateKeystrokes(
+                "ignore",
+                e.target.value
+                  .split(",")
+                  .map((key) => key.trim())
+                  .filter((key) => key)
+              )
+            }
+          />
+        </Box>
+      )}
+
+      {/* only shows up in exports of recordings made with a webcam */}
+      <FormControlLabel
+        control={
+          <Checkbox
+            checked={!!webcam}
+            onChange={(e) =>
+              updateTrack(
+                "webcam",
+                e.target.checked ? defaultWebcamSettings : null
+              )
+            }
+          />
+        }
+        label="Webcam overlay"
+      />
+      {webcam && (
+        <Box display="flex" flexDirection="column" gap="10px">
+          <Select
+            size="small"
+            value={webcam.corner}
+            onChange={(e) => updateWebcam("corner", e.target.value)}
+          >
+            {Object.entries(webcamCorners).map(([value, label]) => (
+              <MenuItem key={value} value={value}>
+                {label}
+              </MenuItem>
+            ))}
+          </Select>
+          <Select
+            size="small"
+            value={webcam.shape}
+            onChange={(e) => updateWebcam("shape", e.target.value)}
+          >
+            {Object.entries(webcamShapes).map(([value, label]) => (
+              <MenuItem key={value} value={value}>
+                {label}
+              </MenuItem>
+            ))}
+          </Select>
+          <Select
+            size="small"
+            value={webcam.zoom_behavior}
+            onChange={(e) => updateWebcam("zoom_behavior", e.target.value)}
+          >
+            {Object.entries(webcamZoomBehaviors).map(([value, label]) => (
+              <MenuItem key={value} value={value}>
+                {label}
+              </MenuItem>
+            ))}
+          </Select>
+          <Typography variant="body2">Size</Typography>
+          <Slider
+            min={0.1}
+            max={0.5}
+            step={0.01}
+            value={webcam.size}
+            onChange={(_, value) => updateWebcam("size", value)}
+          />
+          <Typography variant="body2">Border</Typography>
+          <Slider
+            min={0}
+            max={16}
+            step={1}
+            value={webcam.border_width}
+            onChange={(_, value) => updateWebcam("border_width", value)}
+          />
+          <Typography variant="body2">Shadow</Typography>
+          <Slider
+            min={0}
+            max={64}
+            step={1}
+            value={webcam.shadow}
+            onChange={(_, value) => updateWebcam("shadow", value)}
+          />
+        </Box>
+      )}
     </div>
   );
 };
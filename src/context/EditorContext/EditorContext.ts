@@ -58,8 +58,166 @@ export interface Track {
   end: number;
 }
 
+// mirrors WebcamInfo in the renderer, the recorded webcam.mp4 is overlaid with these
+export interface WebcamSettings {
+  corner: "top_left" | "top_right" | "bottom_left" | "bottom_right";
+  size: number;
+  margin: number;
+  shape: "rectangle" | "rounded" | "circle";
+  corner_radius: number;
+  border_width: number;
+  border_color: { r: number; g: number; b: number };
+  shadow: number;
+  zoom_behavior: "stay" | "shrink" | "move";
+}
+
+export const defaultWebcamSettings: WebcamSettings = {
+  corner: "bottom_right",
+  size: 0.25,
+  margin: 32,
+  shape: "rounded",
+  corner_radius: 24,
+  border_width: 4,
+  border_color: { r: 255, g: 255, b: 255 },
+  shadow: 24,
+  zoom_behavior: "shrink",
+};
+
+export interface FrameShadowSettings {
+  offset_x: number;
+  offset_y: number;
+  blur: number;
+  color: { r: number; g: number; b: number };
+  opacity: number;
+}
+
+// how the recording sits on the background, see FrameStyle in main.rs
+export interface FrameSettings {
+  padding: {
+    top: { unit: "percent" | "px"; value: number };
+    right: { unit: "percent" | "px"; value: number };
+    bottom: { unit: "percent" | "px"; value: number };
+    left: { unit: "percent" | "px"; value: number };
+  };
+  corner_radius: number;
+  shadow: FrameShadowSettings | null;
+  border: { width: number; color: { r: number; g: number; b: number } } | null;
+}
+
+const defaultPaddingSide = { unit: "percent" as const, value: 10 };
+
+export const defaultFrameSettings: FrameSettings = {
+  padding: {
+    top: defaultPaddingSide,
+    right: defaultPaddingSide,
+    bottom: defaultPaddingSide,
+    left: defaultPaddingSide,
+  },
+  corner_radius: 0,
+  shadow: null,
+  border: null,
+};
+
+export const defaultFrameShadow: FrameShadowSettings = {
+  offset_x: 0,
+  offset_y: 12,
+  blur: 40,
+  color: { r: 0, g: 0, b: 0 },
+  opacity: 0.5,
+};
+
+// timed text, arrows, rectangles and highlights, see Overlay in main.rs. Positions
+// are fractions of the output frame, times are ms
+export interface Overlay {
+  kind: "text" | "arrow" | "rectangle" | "highlight";
+  start: number;
+  end: number;
+  fade_in?: number;
+  fade_out?: number;
+  [key: string]: any;
+}
+
+export const createCaption = (start: number): Overlay => ({
+  kind: "text",
+  text: "Caption",
+  start,
+  end: start + 3000,
+  fade_in: 300,
+  fade_out: 300,
+  size: 48,
+  bold: true,
+  color: { r: 255, g: 255, b: 255 },
+  background: { r: 0, g: 0, b: 0 },
+  background_opacity: 0.7,
+  x: 0.5,
+  y: 0.88,
+});
+
+// title cards or clips around the recording, see Bumpers in main.rs
+export type Bumper =
+  | { kind: "title_card"; title: string; subtitle?: string; duration: number }
+  | { kind: "clip"; path: string };
+
+export interface BumperSettings {
+  intro?: Bumper | null;
+  outro?: Bumper | null;
+  crossfade: number;
+}
+
+// a hidden region of the recording in capture pixels, see Redaction in main.rs
+export interface Redaction {
+  start_time?: number;
+  end_time?: number;
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+  space?: "source" | "desktop";
+  style: "blur" | "pixelate" | "fill";
+  strength?: number;
+}
+
+// the HUD of keys pressed while recording, see KeystrokeInfo in main.rs
+export interface KeystrokeSettings {
+  x: number;
+  y: number;
+  size: number;
+  timeout: number;
+  only_shortcuts: boolean;
+  ignore: string[];
+}
+
+export const defaultKeystrokeSettings: KeystrokeSettings = {
+  x: 0.5,
+  y: 0.9,
+  size: 36,
+  timeout: 1500,
+  only_shortcuts: true,
+  ignore: [],
+};
+
+// output size, see Canvas in main.rs
+export type CanvasSettings =
+  | { preset: "source" | "landscape" | "vertical" | "square" | "portrait" }
+  | { preset: "custom"; width: number; height: number };
+
+// degrees per second, see BackgroundAnimation in main.rs
+export interface BackgroundAnimation {
+  shift: number;
+  hue_rotation: number;
+}
+
 export interface VideoTrack extends Track {
   gradient: any;
+  webcam?: WebcamSettings | null;
+  backgroundAnimation?: BackgroundAnimation;
+  frame?: FrameSettings | null;
+  canvas?: CanvasSettings | null;
+  reframe?: "fit" | "follow_cursor";
+  overlays?: Overlay[];
+  bumpers?: BumperSettings | null;
+  redactions?: Redaction[];
+  keystrokes?: KeystrokeSettings | null;
 }
 
 export interface ZoomFactor {